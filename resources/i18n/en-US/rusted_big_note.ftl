@@ -0,0 +1,200 @@
+window-title = Rusted Big Note
+
+## Modes
+
+mode-profiles = Profiles
+mode-note = Note
+mode-scale = Scale
+mode-dictation = Dictation
+mode-trainer = Trainer
+mode-tuner = Tuner
+mode-statistics = Statistics
+mode-settings = Settings
+
+## Inputs
+
+midi-input = MIDI input:
+midi-failed = MIDI input failed: { $error }
+audio-input = Audio input:
+audio-start = Start
+audio-stop = Stop
+audio-failed = Audio input failed: { $error }
+audio-detected = { $note } { $cents } ct, { $hz } Hz
+
+## Clefs and accidentals
+
+clef-treble = Treble
+clef-bass = Bass
+clef-auto = Auto
+note-clef = Clef:
+note-spelling = Spelling:
+note-reference = Mark notes outside:
+note-degrees = Degrees:
+spelling-key = Key signature
+spelling-sharps = Sharps
+spelling-flats = Flats
+reference-off = Off
+reference-key = Key, major
+reference-minor = Key, harmonic minor
+reference-scale = Scale of the scale view
+degrees-off = Off
+degrees-numbers = Numbers
+degrees-functions = Functions
+accidental-sharp = sharp
+accidental-flat = flat
+accidental-natural = natural
+
+## Scales
+
+scale-tonic = Tonic: { $tonic }
+scale-major = Major
+scale-natural-minor = Natural minor
+scale-harmonic-minor = Harmonic minor
+scale-melodic-minor = Melodic minor
+scale-dorian = Dorian
+scale-phrygian = Phrygian
+scale-lydian = Lydian
+scale-mixolydian = Mixolydian
+scale-locrian = Locrian
+scale-major-pentatonic = Major pentatonic
+scale-minor-pentatonic = Minor pentatonic
+scale-blues = Blues
+scale-whole-tone = Whole tone
+scale-whole-half = Whole-half diminished
+scale-half-whole = Half-whole diminished
+
+## Trainer
+
+trainer-from = From: { $note }
+trainer-to = To: { $note }
+trainer-key = Key: { $key }
+trainer-accidentals = Accidentals
+trainer-spaced = Spaced repetition
+trainer-next = Next note
+trainer-start = Press "Next note" to start
+trainer-right = Right: { $note } in { $seconds } s
+trainer-wrong = Wrong: played { $played }, expected { $expected }
+trainer-score = Score: { $correct } / { $total }
+
+## Transposing instruments
+
+transpose-from-written = Input is written pitch
+transpose-input-key = Input key: { $key }
+transpose-second-staff = Show input staff
+transpose-written = Written, { $key }
+transpose-concert = Concert pitch, { $key }
+instrument-concert = Concert pitch
+instrument-clarinet-bb = Clarinet in B♭
+instrument-trumpet-bb = Trumpet in B♭
+instrument-clarinet-a = Clarinet in A
+instrument-horn-f = Horn in F
+instrument-alto-sax-eb = Alto sax in E♭
+instrument-tenor-sax-bb = Tenor sax in B♭
+instrument-baritone-sax-eb = Baritone sax in E♭
+instrument-piccolo = Piccolo (8va)
+instrument-guitar = Guitar (8vb)
+instrument-double-bass = Double bass (8vb)
+
+## Tuner
+
+tuner-reference = A4 = { $hz } Hz
+tuner-tonic = Tonic: { $tonic }
+tuner-reading = { $note } { $cents } ct, { $hz } Hz (target { $target } Hz)
+tuner-play = Play a note
+temperament-equal = Equal
+temperament-werckmeister3 = Werckmeister III
+temperament-meantone = Quarter-comma meantone
+temperament-vallotti = Vallotti
+
+## Note label
+
+caption-frequency = { $hz } Hz
+caption-midi = MIDI { $number }
+interval-unison = Unison
+interval-augmented-unison = Augmented unison
+interval-diminished-second = Diminished second
+interval-minor-second = Minor second
+interval-major-second = Major second
+interval-augmented-second = Augmented second
+interval-diminished-third = Diminished third
+interval-minor-third = Minor third
+interval-major-third = Major third
+interval-augmented-third = Augmented third
+interval-diminished-fourth = Diminished fourth
+interval-perfect-fourth = Perfect fourth
+interval-augmented-fourth = Augmented fourth
+interval-diminished-fifth = Diminished fifth
+interval-perfect-fifth = Perfect fifth
+interval-augmented-fifth = Augmented fifth
+interval-diminished-sixth = Diminished sixth
+interval-minor-sixth = Minor sixth
+interval-major-sixth = Major sixth
+interval-augmented-sixth = Augmented sixth
+interval-diminished-seventh = Diminished seventh
+interval-minor-seventh = Minor seventh
+interval-major-seventh = Major seventh
+interval-augmented-seventh = Augmented seventh
+interval-diminished-octave = Diminished octave
+interval-octave = Octave
+interval-augmented-octave = Augmented octave
+
+## Dictation
+
+dictation-tempo = Tempo: { $bpm } bpm
+dictation-follow-clock = Follow MIDI clock
+dictation-clear = Clear
+
+## Profiles and statistics
+
+profiles-choose = Choose a profile:
+profiles-create = Create
+stats-profile = Profile: { $name }
+stats-clef = Clef
+stats-accidental = Accidental
+stats-register = Register
+stats-line = { $value }: { $accuracy }% of { $answers }, { $seconds } s
+stats-export = Export CSV and JSON
+stats-saved = Saved { $paths }
+stats-export-failed = Export failed: { $error }
+
+## Settings
+
+settings-language = Language:
+language-system = System
+language-english = English
+language-russian = Русский
+settings-names = Note names:
+settings-octaves = Octaves:
+settings-example = Example: { $names }
+naming-english = English (C D E)
+naming-german = German (C D E H B)
+naming-italian = Fixed do, Italian
+naming-french = Fixed do, French
+naming-russian = Fixed do, Russian
+naming-japanese = Fixed do, Japanese
+naming-movable-do = Movable do
+octaves-c3 = Middle C is C3
+octaves-c4 = Middle C is C4
+octaves-c5 = Middle C is C5
+octaves-helmholtz = Helmholtz (c′)
+settings-theme = Colours:
+theme-light = Light
+theme-dark = Dark
+theme-high-contrast = High contrast
+theme-color-blind = Colour-blind safe
+theme-custom = Custom
+settings-caption = Note label:
+caption-show-name = Note name
+caption-show-frequency = Frequency
+caption-show-midi = MIDI number
+caption-font = Font, empty for the default:
+caption-size = Size: { $percent }% of the staff
+settings-ottava = Octave lines:
+ottava-enabled = Write far notes an octave nearer, under 8va or 8vb
+ottava-above = Ledger lines above the staff: { $count }
+ottava-below = Ledger lines below the staff: { $count }
+
+## Worksheets
+worksheet-title = Name the notes
+worksheet-answers = Answer key
+worksheet-footer = Page { $page } of { $pages }, seed { $seed }
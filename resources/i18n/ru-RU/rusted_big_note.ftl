@@ -0,0 +1,200 @@
+window-title = Rusted Big Note
+
+## Modes
+
+mode-profiles = Профили
+mode-note = Нота
+mode-scale = Гамма
+mode-dictation = Диктант
+mode-trainer = Тренажёр
+mode-tuner = Тюнер
+mode-statistics = Статистика
+mode-settings = Настройки
+
+## Inputs
+
+midi-input = Вход MIDI:
+midi-failed = Не удалось подключить вход MIDI: { $error }
+audio-input = Аудиовход:
+audio-start = Включить
+audio-stop = Выключить
+audio-failed = Не удалось открыть аудиовход: { $error }
+audio-detected = { $note } { $cents } ц, { $hz } Гц
+
+## Clefs and accidentals
+
+clef-treble = Скрипичный
+clef-bass = Басовый
+clef-auto = Авто
+note-clef = Ключ:
+note-spelling = Знаки альтерации:
+note-reference = Отмечать ноты вне:
+note-degrees = Ступени:
+spelling-key = По тональности
+spelling-sharps = Диезы
+spelling-flats = Бемоли
+reference-off = Выкл.
+reference-key = Тональности, мажор
+reference-minor = Тональности, гармонический минор
+reference-scale = Гаммы из режима гамм
+degrees-off = Выкл.
+degrees-numbers = Номера
+degrees-functions = Функции
+accidental-sharp = диез
+accidental-flat = бемоль
+accidental-natural = без знака
+
+## Scales
+
+scale-tonic = Тоника: { $tonic }
+scale-major = Мажор
+scale-natural-minor = Натуральный минор
+scale-harmonic-minor = Гармонический минор
+scale-melodic-minor = Мелодический минор
+scale-dorian = Дорийский
+scale-phrygian = Фригийский
+scale-lydian = Лидийский
+scale-mixolydian = Миксолидийский
+scale-locrian = Локрийский
+scale-major-pentatonic = Мажорная пентатоника
+scale-minor-pentatonic = Минорная пентатоника
+scale-blues = Блюзовая
+scale-whole-tone = Целотоновая
+scale-whole-half = Уменьшённая тон-полутон
+scale-half-whole = Уменьшённая полутон-тон
+
+## Trainer
+
+trainer-from = От: { $note }
+trainer-to = До: { $note }
+trainer-key = Тональность: { $key }
+trainer-accidentals = Знаки альтерации
+trainer-spaced = Интервальные повторения
+trainer-next = Следующая нота
+trainer-start = Нажмите «Следующая нота», чтобы начать
+trainer-right = Верно: { $note } за { $seconds } с
+trainer-wrong = Ошибка: сыграно { $played }, нужно { $expected }
+trainer-score = Счёт: { $correct } / { $total }
+
+## Transposing instruments
+
+transpose-from-written = На входе записанная высота
+transpose-input-key = Тональность входа: { $key }
+transpose-second-staff = Показать нотоносец входа
+transpose-written = Запись, { $key }
+transpose-concert = Реальное звучание, { $key }
+instrument-concert = Реальное звучание
+instrument-clarinet-bb = Кларнет in B
+instrument-trumpet-bb = Труба in B
+instrument-clarinet-a = Кларнет in A
+instrument-horn-f = Валторна in F
+instrument-alto-sax-eb = Альт-саксофон in Es
+instrument-tenor-sax-bb = Тенор-саксофон in B
+instrument-baritone-sax-eb = Баритон-саксофон in Es
+instrument-piccolo = Флейта-пикколо (8va)
+instrument-guitar = Гитара (8vb)
+instrument-double-bass = Контрабас (8vb)
+
+## Tuner
+
+tuner-reference = Ля¹ = { $hz } Гц
+tuner-tonic = Тоника: { $tonic }
+tuner-reading = { $note } { $cents } ц, { $hz } Гц (цель { $target } Гц)
+tuner-play = Сыграйте ноту
+temperament-equal = Равномерный
+temperament-werckmeister3 = Веркмейстер III
+temperament-meantone = Среднетоновый (1/4 коммы)
+temperament-vallotti = Валлотти
+
+## Note label
+
+caption-frequency = { $hz } Гц
+caption-midi = MIDI { $number }
+interval-unison = Прима
+interval-augmented-unison = Увеличенная прима
+interval-diminished-second = Уменьшённая секунда
+interval-minor-second = Малая секунда
+interval-major-second = Большая секунда
+interval-augmented-second = Увеличенная секунда
+interval-diminished-third = Уменьшённая терция
+interval-minor-third = Малая терция
+interval-major-third = Большая терция
+interval-augmented-third = Увеличенная терция
+interval-diminished-fourth = Уменьшённая кварта
+interval-perfect-fourth = Чистая кварта
+interval-augmented-fourth = Увеличенная кварта
+interval-diminished-fifth = Уменьшённая квинта
+interval-perfect-fifth = Чистая квинта
+interval-augmented-fifth = Увеличенная квинта
+interval-diminished-sixth = Уменьшённая секста
+interval-minor-sixth = Малая секста
+interval-major-sixth = Большая секста
+interval-augmented-sixth = Увеличенная секста
+interval-diminished-seventh = Уменьшённая септима
+interval-minor-seventh = Малая септима
+interval-major-seventh = Большая септима
+interval-augmented-seventh = Увеличенная септима
+interval-diminished-octave = Уменьшённая октава
+interval-octave = Октава
+interval-augmented-octave = Увеличенная октава
+
+## Dictation
+
+dictation-tempo = Темп: { $bpm } уд/мин
+dictation-follow-clock = Следовать MIDI clock
+dictation-clear = Очистить
+
+## Profiles and statistics
+
+profiles-choose = Выберите профиль:
+profiles-create = Создать
+stats-profile = Профиль: { $name }
+stats-clef = Ключ
+stats-accidental = Знак
+stats-register = Регистр
+stats-line = { $value }: { $accuracy }% из { $answers }, { $seconds } с
+stats-export = Экспорт в CSV и JSON
+stats-saved = Сохранено: { $paths }
+stats-export-failed = Ошибка экспорта: { $error }
+
+## Settings
+
+settings-language = Язык:
+language-system = Системный
+language-english = English
+language-russian = Русский
+settings-names = Названия нот:
+settings-octaves = Октавы:
+settings-example = Пример: { $names }
+naming-english = Английские (C D E)
+naming-german = Немецкие (C D E H B)
+naming-italian = Сольмизация, итальянская
+naming-french = Сольмизация, французская
+naming-russian = Сольмизация, русская
+naming-japanese = Сольмизация, японская
+naming-movable-do = Относительная сольмизация
+octaves-c3 = До первой октавы — C3
+octaves-c4 = До первой октавы — C4
+octaves-c5 = До первой октавы — C5
+octaves-helmholtz = Гельмгольц (c′)
+settings-theme = Цвета:
+theme-light = Светлые
+theme-dark = Тёмные
+theme-high-contrast = Высокий контраст
+theme-color-blind = Для дальтоников
+theme-custom = Свои
+settings-caption = Подпись ноты:
+caption-show-name = Название ноты
+caption-show-frequency = Частота
+caption-show-midi = Номер MIDI
+caption-font = Шрифт, пусто — по умолчанию:
+caption-size = Размер: { $percent }% от нотного стана
+settings-ottava = Октавные линии:
+ottava-enabled = Писать далёкие ноты на октаву ближе, под 8va или 8vb
+ottava-above = Добавочных линеек над станом: { $count }
+ottava-below = Добавочных линеек под станом: { $count }
+
+## Worksheets
+worksheet-title = Назовите ноты
+worksheet-answers = Ответы
+worksheet-footer = Страница { $page } из { $pages }, вариант { $seed }
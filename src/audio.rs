@@ -0,0 +1,105 @@
+use std::sync::mpsc;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use druid::{Data, ExtEventSink, Lens, Selector, Target};
+use tracing::{error, info};
+
+use super::pitch::{DetectedPitch, PitchTracker, PITCH};
+
+pub const TOGGLE: Selector = Selector::new("rusted_big_note.audio-toggle");
+
+// Samples are handed from the audio callback to the analysis thread in
+// fixed blocks, so the callback never allocates or waits.
+const BLOCK: usize = 256;
+// Blocks buffered for the analysis thread, about a second at 48 kHz.
+const BLOCKS: usize = 192;
+
+#[derive(Clone, Data, Lens)]
+pub struct AudioState {
+    pub running: bool,
+    pub detected: Option<DetectedPitch>,
+    pub message: String,
+}
+impl AudioState {
+    pub fn new() -> Self {
+        AudioState {
+            running: false,
+            detected: None,
+            message: String::new(),
+        }
+    }
+}
+
+// Live microphone input. Only the first channel is analysed, on a worker
+// thread; results are sent to the app as PITCH commands, one per hop.
+pub struct AudioInput {
+    stream: Option<cpal::Stream>,
+}
+impl AudioInput {
+    pub fn new() -> Self {
+        AudioInput { stream: None }
+    }
+    pub fn is_running(&self) -> bool {
+        self.stream.is_some()
+    }
+    pub fn stop(&mut self) {
+        self.stream = None;
+    }
+    pub fn start(&mut self, sink: ExtEventSink, a4: f64) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop();
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or("no audio input device")?;
+        info!("Audio input: {}", device.name()?);
+        let config = device.default_input_config()?;
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config.into(), sink, a4)?,
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config.into(), sink, a4)?,
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config.into(), sink, a4)?,
+        };
+        stream.play()?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+}
+
+fn build_stream<T: cpal::Sample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sink: ExtEventSink,
+    a4: f64,
+) -> Result<cpal::Stream, Box<dyn std::error::Error>> {
+    let channels = config.channels as usize;
+    let mut tracker = PitchTracker::new(config.sample_rate.0, a4);
+    let (sender, receiver) = mpsc::sync_channel::<[f32; BLOCK]>(BLOCKS);
+    // Ends when the stream and with it the sender are dropped.
+    std::thread::Builder::new()
+        .name(String::from("pitch"))
+        .spawn(move || {
+            for block in receiver {
+                for detected in tracker.push(&block) {
+                    if let Err(err) = sink.submit_command(PITCH, detected, Target::Auto) {
+                        error!("{}", err);
+                    }
+                }
+            }
+        })?;
+    let mut block = [0.0; BLOCK];
+    let mut len = 0;
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            for sample in data.iter().step_by(channels) {
+                block[len] = sample.to_f32();
+                len += 1;
+                if len == BLOCK {
+                    len = 0;
+                    // A lagging analysis loses blocks rather than stalling the input.
+                    let _ = sender.try_send(block);
+                }
+            }
+        },
+        |err| error!("{}", err),
+    )?;
+    Ok(stream)
+}
@@ -0,0 +1,405 @@
+use druid::{
+    piet::{FontFamily, FontWeight, Text, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, Lens, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget,
+};
+use tracing::error;
+
+use super::i18n::{self, Language};
+use super::naming::Naming;
+use super::note::{Key, Note, NoteAlt};
+use super::settings;
+use super::staff::Spelling;
+
+// Interval numbers by the letters between the notes, and the
+// semitones of their major or perfect interval.
+const NUMBERS: [(&str, i32); 8] = [
+    ("unison", 0),
+    ("second", 2),
+    ("third", 4),
+    ("fourth", 5),
+    ("fifth", 7),
+    ("sixth", 9),
+    ("seventh", 11),
+    ("octave", 12),
+];
+// Numbers with a perfect interval, the others are major or minor.
+const PERFECT: [usize; 4] = [0, 3, 4, 7];
+
+// Pitch classes above the root and the symbol suffix of the chords
+// that are recognised.
+const CHORDS: [(&[u8], &str); 11] = [
+    (&[4, 7], ""),
+    (&[3, 7], "m"),
+    (&[3, 6], "°"),
+    (&[4, 8], "+"),
+    (&[2, 7], "sus2"),
+    (&[5, 7], "sus4"),
+    (&[4, 7, 10], "7"),
+    (&[4, 7, 11], "maj7"),
+    (&[3, 7, 10], "m7"),
+    (&[3, 6, 10], "ø7"),
+    (&[3, 6, 9], "°7"),
+];
+
+// The main text takes this share of the height when the detail line
+// is shown.
+const TITLE_SHARE: f64 = 0.7;
+
+#[test]
+fn test_intervals() {
+    let name = |low, high, alteration| {
+        interval(
+            Language::English,
+            &Note::new(low),
+            &Note::new(high),
+            alteration,
+        )
+    };
+    assert_eq!(name(60, 64, NoteAlt::Sharp), "Major third");
+    assert_eq!(name(64, 60, NoteAlt::Sharp), "Major third");
+    assert_eq!(name(60, 60, NoteAlt::Sharp), "Unison");
+    assert_eq!(name(60, 72, NoteAlt::Sharp), "Octave");
+    // The tritone is named from the spelling.
+    assert_eq!(name(60, 66, NoteAlt::Sharp), "Augmented fourth");
+    assert_eq!(name(60, 66, NoteAlt::Flat), "Diminished fifth");
+    assert_eq!(name(65, 71, NoteAlt::Flat), "Augmented fourth");
+    assert_eq!(name(60, 61, NoteAlt::Sharp), "Augmented unison");
+    assert_eq!(name(61, 65, NoteAlt::Sharp), "Diminished fourth");
+    assert_eq!(name(63, 72, NoteAlt::Sharp), "Diminished seventh");
+    assert_eq!(name(61, 72, NoteAlt::Sharp), "Diminished octave");
+    // Compound intervals are named by their simple interval.
+    assert_eq!(name(60, 79, NoteAlt::Sharp), "Perfect fifth");
+    assert_eq!(name(48, 72, NoteAlt::Sharp), "Octave");
+    assert_eq!(name(48, 73, NoteAlt::Sharp), "Augmented octave");
+    assert_eq!(
+        interval(
+            Language::Russian,
+            &Note::new(60),
+            &Note::new(66),
+            NoteAlt::Flat
+        ),
+        "Уменьшённая квинта"
+    );
+}
+
+#[test]
+fn test_chords() {
+    let naming = Naming::default();
+    let key = Key::new(0);
+    let symbol = |keys: &[u8], alteration| {
+        let notes: Vec<Note> = keys.iter().map(|nr| Note::new(*nr)).collect();
+        chord(&naming, &notes, alteration, &key)
+    };
+    assert_eq!(symbol(&[60, 64, 67], NoteAlt::Sharp).unwrap(), "C");
+    assert_eq!(symbol(&[57, 60, 64], NoteAlt::Sharp).unwrap(), "Am");
+    assert_eq!(symbol(&[55, 59, 62, 65], NoteAlt::Sharp).unwrap(), "G7");
+    assert_eq!(symbol(&[59, 62, 65, 69], NoteAlt::Sharp).unwrap(), "Bø7");
+    assert_eq!(symbol(&[58, 62, 65], NoteAlt::Flat).unwrap(), "Bb");
+    // Doubled notes and inversions.
+    assert_eq!(symbol(&[48, 60, 64, 67, 72], NoteAlt::Sharp).unwrap(), "C");
+    assert_eq!(symbol(&[64, 67, 72], NoteAlt::Sharp).unwrap(), "C/E");
+    // Symmetric chords are named from the bass.
+    assert_eq!(symbol(&[60, 64, 68], NoteAlt::Sharp).unwrap(), "C+");
+    assert_eq!(symbol(&[60, 62, 67], NoteAlt::Sharp).unwrap(), "Csus2");
+    assert_eq!(symbol(&[60, 61, 62], NoteAlt::Sharp), None);
+    let german = Naming::new(super::naming::System::German, naming.octaves);
+    let notes = [Note::new(59), Note::new(62), Note::new(66)];
+    assert_eq!(chord(&german, &notes, NoteAlt::Sharp, &key).unwrap(), "Hm");
+}
+
+#[test]
+fn test_text() {
+    let shown = Shown {
+        notes: vec![Note::new(60)],
+        key: Key::new(0),
+        spelling: Spelling::Key,
+        keys: vec![60],
+        frequencies: vec![261.6256],
+        options: settings::Caption::default(),
+    };
+    let naming = Naming::default();
+    assert_eq!(shown.title(&naming, Language::English), "C3");
+    assert_eq!(shown.detail(Language::English), "");
+    let shown = Shown {
+        options: settings::Caption {
+            frequency: true,
+            midi_number: true,
+            ..settings::Caption::default()
+        },
+        ..shown
+    };
+    assert_eq!(shown.detail(Language::English), "261.63 Hz · MIDI 60");
+    let chord = Shown {
+        notes: vec![Note::new(60), Note::new(67)],
+        keys: vec![60, 67],
+        frequencies: vec![261.6256, 391.9954],
+        ..shown.clone()
+    };
+    assert_eq!(chord.title(&naming, Language::English), "Perfect fifth");
+    assert_eq!(
+        chord.detail(Language::English),
+        "261.63 392.00 Hz · MIDI 60 67"
+    );
+    let hidden = Shown {
+        options: settings::Caption {
+            name: false,
+            ..shown.options.clone()
+        },
+        ..shown
+    };
+    assert_eq!(hidden.title(&naming, Language::English), "");
+    let empty = Shown {
+        notes: Vec::new(),
+        keys: Vec::new(),
+        frequencies: Vec::new(),
+        ..hidden
+    };
+    assert_eq!(empty.detail(Language::English), "");
+}
+
+// Interval name from the letters of the notes as spelled and the
+// semitones between them, so a tritone is an augmented fourth or a
+// diminished fifth.
+pub fn interval(language: Language, low: &Note, high: &Note, alteration: NoteAlt) -> String {
+    let (low, high) = if low.midi_nr() <= high.midi_nr() {
+        (low, high)
+    } else {
+        (high, low)
+    };
+    let letters = |note: &Note| {
+        let pitch = note.spelled(alteration.clone());
+        pitch.octave() * 7 + pitch.step() as i32
+    };
+    let mut number = letters(high) - letters(low);
+    let mut semitones = high.midi_nr() as i32 - low.midi_nr() as i32;
+    // Compound intervals are named by their simple interval, an octave
+    // stays one.
+    while number > 7 {
+        number -= 7;
+        semitones -= 12;
+    }
+    let (name, major) = NUMBERS[number as usize];
+    let quality = match (PERFECT.contains(&(number as usize)), semitones - major) {
+        (true, 0) => "perfect",
+        (false, 0) => "major",
+        (false, -1) => "minor",
+        (_, more) if more > 0 => "augmented",
+        _ => "diminished",
+    };
+    let id = match (quality, name) {
+        ("perfect", "unison") | ("perfect", "octave") => format!("interval-{}", name),
+        _ => format!("interval-{}-{}", quality, name),
+    };
+    i18n::translate(language, &id, None)
+}
+
+// Chord symbol of the notes, with the bass after a slash when the chord
+// is inverted. Roots are tried from the bass up.
+pub fn chord(naming: &Naming, notes: &[Note], alteration: NoteAlt, key: &Key) -> Option<String> {
+    let bass = notes.iter().min_by_key(|note| note.midi_nr())?;
+    let mut classes: Vec<u8> = notes.iter().map(|note| note.midi_nr() % 12).collect();
+    classes.sort_unstable();
+    classes.dedup();
+    let mut roots = classes.clone();
+    roots.sort_by_key(|class| (class + 12 - bass.midi_nr() % 12) % 12);
+    for root in roots {
+        let mut above: Vec<u8> = classes
+            .iter()
+            .filter(|class| **class != root)
+            .map(|class| (class + 12 - root) % 12)
+            .collect();
+        above.sort_unstable();
+        let suffix = match CHORDS
+            .iter()
+            .find(|(intervals, _)| *intervals == &above[..])
+        {
+            Some((_, suffix)) => suffix,
+            None => continue,
+        };
+        let name = |class: u8| naming.pitch_class(&Note::new(60 + class), alteration.clone(), key);
+        let mut symbol = format!("{}{}", name(root), suffix);
+        if root != bass.midi_nr() % 12 {
+            symbol = format!("{}/{}", symbol, name(bass.midi_nr() % 12));
+        }
+        return Some(symbol);
+    }
+    None
+}
+
+// What the staff shows, with the keys played and the pitches they sound
+// at for the detail line.
+#[derive(Clone, Data, Lens, PartialEq, Debug)]
+pub struct Shown {
+    // Lowest first.
+    #[data(same_fn = "PartialEq::eq")]
+    pub notes: Vec<Note>,
+    pub key: Key,
+    pub spelling: Spelling,
+    #[data(same_fn = "PartialEq::eq")]
+    pub keys: Vec<u8>,
+    #[data(same_fn = "PartialEq::eq")]
+    pub frequencies: Vec<f64>,
+    pub options: settings::Caption,
+}
+impl Shown {
+    // A note name, an interval for two notes and a chord symbol for more.
+    // Unknown chords list their notes.
+    pub fn title(&self, naming: &Naming, language: Language) -> String {
+        if !self.options.name {
+            return String::new();
+        }
+        let alteration = self.spelling.alteration(&self.key);
+        match &self.notes[..] {
+            [] => String::new(),
+            [note] => naming.name(note, alteration, &self.key),
+            [low, high] => interval(language, low, high, alteration),
+            notes => chord(naming, notes, alteration.clone(), &self.key).unwrap_or_else(|| {
+                let names: Vec<String> = notes
+                    .iter()
+                    .map(|note| naming.name(note, alteration.clone(), &self.key))
+                    .collect();
+                names.join(" ")
+            }),
+        }
+    }
+    pub fn detail(&self, language: Language) -> String {
+        let mut parts = Vec::new();
+        if self.options.frequency && !self.frequencies.is_empty() {
+            let hz: Vec<String> = self
+                .frequencies
+                .iter()
+                .map(|hz| format!("{:.2}", hz))
+                .collect();
+            parts.push(i18n::translate_args(
+                language,
+                "caption-frequency",
+                &[("hz", hz.join(" "))],
+            ));
+        }
+        if self.options.midi_number && !self.keys.is_empty() {
+            let numbers: Vec<String> = self.keys.iter().map(|key| key.to_string()).collect();
+            parts.push(i18n::translate_args(
+                language,
+                "caption-midi",
+                &[("number", numbers.join(" "))],
+            ));
+        }
+        parts.join(" · ")
+    }
+}
+
+// Name of the shown note in type as large as the area allows, with the
+// frequency and MIDI number underneath.
+pub struct BigNote;
+impl BigNote {
+    fn family(ctx: &mut PaintCtx, font: &str) -> FontFamily {
+        if font.is_empty() {
+            return FontFamily::SERIF;
+        }
+        ctx.text().font_family(font).unwrap_or(FontFamily::SERIF)
+    }
+    // Draws the text centred in the band, shrunk to fit its width.
+    fn draw(
+        ctx: &mut PaintCtx,
+        family: &FontFamily,
+        ink: &Color,
+        text: &str,
+        top: f64,
+        height: f64,
+    ) {
+        if text.is_empty() || height <= 0.0 {
+            return;
+        }
+        let width = ctx.size().width;
+        // A text that can't be laid out is left out.
+        let build = |ctx: &mut PaintCtx, size: f64| {
+            ctx.text()
+                .new_text_layout(String::from(text))
+                .font(family.clone(), size)
+                .default_attribute(FontWeight::BOLD)
+                .text_color(ink.clone())
+                .build()
+                .map_err(|err| error!("Can't lay out \"{}\": {}", text, err))
+                .ok()
+        };
+        let mut size = height * 0.8;
+        let mut layout = match build(ctx, size) {
+            Some(layout) => layout,
+            None => return,
+        };
+        if layout.size().width > width * 0.95 {
+            size *= width * 0.95 / layout.size().width;
+            layout = match build(ctx, size) {
+                Some(layout) => layout,
+                None => return,
+            };
+        }
+        let text_size = layout.size();
+        ctx.draw_text(
+            &layout,
+            Point::new(
+                (width - text_size.width) / 2.0,
+                top + (height - text_size.height) / 2.0,
+            ),
+        );
+    }
+}
+
+impl Widget<Shown> for BigNote {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut Shown, _env: &Env) {}
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &Shown,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &Shown, data: &Shown, _env: &Env) {
+        if !old_data.same(data)
+            || ctx.env_key_changed(&settings::BACKGROUND)
+            || ctx.env_key_changed(&settings::NOTEHEAD)
+            || ctx.env_key_changed(&super::naming::NAMING)
+            || ctx.env_key_changed(&i18n::LANGUAGE)
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &Shown,
+        _env: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Shown, env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &env.get(settings::BACKGROUND));
+        let language = Language::from_env(env);
+        let title = data.title(&Naming::from_env(env), language);
+        let detail = data.detail(language);
+        let family = Self::family(ctx, &data.options.font);
+        let ink = env.get(settings::NOTEHEAD);
+        let title_height = match (title.is_empty(), detail.is_empty()) {
+            (_, true) => size.height,
+            (true, false) => 0.0,
+            (false, false) => size.height * TITLE_SHARE,
+        };
+        Self::draw(ctx, &family, &ink, &title, 0.0, title_height);
+        Self::draw(
+            ctx,
+            &family,
+            &ink,
+            &detail,
+            title_height,
+            size.height - title_height,
+        );
+    }
+}
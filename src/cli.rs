@@ -0,0 +1,366 @@
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use structopt::StructOpt;
+
+use super::i18n::Language;
+use super::midi::Port;
+use super::naming::{Octaves, System};
+use super::note::{Key, Note};
+use super::settings::{Settings, Theme};
+use super::staff::{ClefType, Spelling};
+
+#[test]
+fn test_parse() {
+    assert_eq!(parse_key("-3").unwrap(), Key::new(-3));
+    assert_eq!(parse_key("eb").unwrap(), Key::new(-3));
+    assert_eq!(parse_key("F#").unwrap(), Key::new(6));
+    assert!(parse_key("8").is_err());
+    assert!(parse_key("H").is_err());
+    assert_eq!(parse_clef("Bass").unwrap(), ClefType::Bass);
+    assert!(parse_clef("alto").is_err());
+    assert_eq!(
+        parse_theme("High-Contrast").unwrap(),
+        ThemeChoice::Preset(Theme::HighContrast)
+    );
+    assert_eq!(
+        parse_theme("Cargo.toml").unwrap(),
+        ThemeChoice::File(PathBuf::from("Cargo.toml"))
+    );
+    assert!(parse_theme("no-such-theme.toml").is_err());
+    assert_eq!(
+        parse_name::<System>("movable-do").unwrap(),
+        System::MovableDo
+    );
+    assert!(parse_name::<Spelling>("both").is_err());
+    let options = Options::from_iter(&[
+        "rusted_big_note",
+        "--key",
+        "Bb",
+        "--naming",
+        "german",
+        "--note",
+        "Fis3",
+    ]);
+    assert_eq!(options.key, Some(Key::new(-2)));
+    assert_eq!(options.note, Some(Note::new(66)));
+    assert!(Options::from_iter_safe(&["rusted_big_note", "--note", "Q3"]).is_err());
+    assert!(Options::from_iter_safe(&["rusted_big_note", "--note", "128"]).is_err());
+    assert_eq!(options.naming, Some(System::German));
+    assert!(!options.fullscreen);
+    assert!(options.command.is_none());
+    assert!(options.state.is_none());
+    let session = Options::from_iter(&["rusted_big_note", "--state", "session.toml"]);
+    assert_eq!(session.state, Some(PathBuf::from("session.toml")));
+    assert!(session.is_display());
+    let options = Options::from_iter(&[
+        "rusted_big_note",
+        "--clef",
+        "bass",
+        "--font",
+        "fonts/Bravura.otf",
+        "render",
+        "-o",
+        "chord.png",
+        "C2",
+        "52",
+        "G2",
+    ]);
+    assert_eq!(options.clef, Some(ClefType::Bass));
+    let mut settings = Settings::default();
+    options.apply(&mut settings);
+    assert_eq!(settings.font, "fonts/Bravura.otf");
+    let themed = Options::from_iter(&["rusted_big_note", "--theme", "Cargo.toml"]);
+    themed.apply(&mut settings);
+    assert_eq!(settings.theme, Theme::Custom);
+    assert_eq!(settings.theme_file, "Cargo.toml");
+    match options.command {
+        Some(Command::Render(render)) => {
+            assert_eq!(render.output, PathBuf::from("chord.png"));
+            assert_eq!(
+                render.notes,
+                vec![Note::new(48), Note::new(52), Note::new(55)]
+            );
+            assert_eq!((render.width, render.height), (400, 300));
+        }
+        _ => panic!("no render command"),
+    }
+    let options = Options::from_iter(&[
+        "rusted_big_note",
+        "worksheet",
+        "-o",
+        "sheet.pdf",
+        "--low",
+        "C3",
+        "--seed",
+        "7",
+    ]);
+    match options.command {
+        Some(Command::Worksheet(worksheet)) => {
+            assert_eq!(worksheet.low, Some(Note::new(60)));
+            assert_eq!(worksheet.high, None);
+            assert_eq!((worksheet.count, worksheet.chord), (20, 1));
+            assert_eq!(worksheet.seed, Some(7));
+        }
+        _ => panic!("no worksheet command"),
+    }
+}
+
+#[test]
+fn test_find_port() {
+    let ports: Vec<Port> = ["Midi Through:0", "Keystation 49 MIDI 1"]
+        .iter()
+        .enumerate()
+        .map(|(index, name)| Port {
+            index,
+            name: Box::new(String::from(*name)),
+        })
+        .collect();
+    assert_eq!(find_port(&ports, "keystation").unwrap().index, 1);
+    assert_eq!(find_port(&ports, "Midi Through:0").unwrap().index, 0);
+    assert_eq!(find_port(&ports, "piano"), None);
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(about = "Shows the notes played on a MIDI keyboard on a staff.")]
+pub struct Options {
+    #[structopt(long, help = "Prints the MIDI input ports and exits")]
+    pub list_ports: bool,
+    #[structopt(
+        long,
+        value_name = "FILE",
+        help = "Prints the notes detected in a WAV file and exits"
+    )]
+    pub detect_wav: Option<PathBuf>,
+    #[structopt(
+        long,
+        value_name = "NAME",
+        help = "Connects to the first MIDI input whose name contains NAME, ignoring case, \
+                and exits when none does"
+    )]
+    pub port: Option<String>,
+    #[structopt(long, parse(try_from_str = parse_clef), help = "treble, bass or auto")]
+    pub clef: Option<ClefType>,
+    #[structopt(
+        long,
+        allow_hyphen_values = true,
+        parse(try_from_str = parse_key),
+        help = "Key signature as a major key (Eb, F#) or in fifths (-3, 6)"
+    )]
+    pub key: Option<Key>,
+    #[structopt(long, parse(try_from_str = parse_name), help = "key, sharps or flats")]
+    pub spelling: Option<Spelling>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_name),
+        help = "english, german, italian, french, russian, japanese or movable-do"
+    )]
+    pub naming: Option<System>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_name),
+        help = "middle-c3, middle-c4, middle-c5 or helmholtz"
+    )]
+    pub octaves: Option<Octaves>,
+    #[structopt(long, parse(try_from_str = parse_name), help = "system, english or russian")]
+    pub language: Option<Language>,
+    #[structopt(long, help = "Maximized window without title bar")]
+    pub fullscreen: bool,
+    #[structopt(
+        long,
+        value_name = "NOTE",
+        help = "Note shown before anything is played, as a MIDI number or a name like C3"
+    )]
+    pub note: Option<Note>,
+    #[structopt(
+        long,
+        value_name = "FILE",
+        help = "Session file, the staff is shown as saved in it and saved there on close"
+    )]
+    pub state: Option<PathBuf>,
+    #[structopt(
+        long,
+        value_name = "FILE",
+        help = "SMuFL music font for the staff symbols, such as Bravura.otf"
+    )]
+    pub font: Option<PathBuf>,
+    #[structopt(
+        long,
+        value_name = "THEME",
+        parse(try_from_str = parse_theme),
+        help = "light, dark, high-contrast, color-blind, custom or a theme file"
+    )]
+    pub theme: Option<ThemeChoice>,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+// A preset or the file of a custom theme.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThemeChoice {
+    Preset(Theme),
+    File(PathBuf),
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    #[structopt(
+        about = "Writes the staff with the given notes to an image, without a window. \
+                        Clef, key and spelling come from the options before the command."
+    )]
+    Render(Render),
+    #[structopt(
+        about = "Writes worksheets of random notes or chords with an answer key. \
+                        Clef, key and note names come from the options before the command."
+    )]
+    Worksheet(Worksheet),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Render {
+    #[structopt(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Image to write, .svg or .png"
+    )]
+    pub output: PathBuf,
+    #[structopt(long, default_value = "400", help = "Image width in pixels")]
+    pub width: u32,
+    #[structopt(long, default_value = "300", help = "Image height in pixels")]
+    pub height: u32,
+    #[structopt(
+        value_name = "NOTE",
+        help = "Notes as MIDI numbers or names like C3, F#3, Es4 or Sol3, middle C is C3"
+    )]
+    pub notes: Vec<Note>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Worksheet {
+    #[structopt(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Worksheet to write, .pdf or .svg with one file per page"
+    )]
+    pub output: PathBuf,
+    #[structopt(long, default_value = "20", help = "Number of notes or chords")]
+    pub count: usize,
+    #[structopt(
+        long,
+        default_value = "1",
+        help = "Notes per chord, 1 for single notes"
+    )]
+    pub chord: usize,
+    #[structopt(long, default_value = "4", help = "Staves per row")]
+    pub columns: usize,
+    #[structopt(long, help = "Lowest note, the trainer range by default")]
+    pub low: Option<Note>,
+    #[structopt(long, help = "Highest note, the trainer range by default")]
+    pub high: Option<Note>,
+    #[structopt(long, help = "Also picks notes outside the key")]
+    pub accidentals: bool,
+    #[structopt(long, help = "Makes the same worksheet again, printed on every page")]
+    pub seed: Option<u64>,
+}
+
+impl Options {
+    // Overrides the settings file. The MIDI port is matched later,
+    // against the list of ports.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(clef) = self.clef {
+            settings.clef = clef;
+        }
+        if let Some(key) = &self.key {
+            settings.transposition.key = key.fifths();
+        }
+        if let Some(spelling) = self.spelling {
+            settings.spelling = spelling;
+        }
+        if let Some(naming) = self.naming {
+            settings.naming = naming;
+        }
+        if let Some(octaves) = self.octaves {
+            settings.octaves = octaves;
+        }
+        if let Some(language) = self.language {
+            settings.language = language;
+        }
+        if let Some(font) = &self.font {
+            settings.font = font.to_string_lossy().into_owned();
+        }
+        match &self.theme {
+            Some(ThemeChoice::Preset(theme)) => settings.theme = *theme,
+            Some(ThemeChoice::File(path)) => {
+                settings.theme = Theme::Custom;
+                settings.theme_file = path.to_string_lossy().into_owned();
+            }
+            None => {}
+        }
+    }
+    pub fn is_display(&self) -> bool {
+        self.port.is_some()
+            || self.clef.is_some()
+            || self.key.is_some()
+            || self.spelling.is_some()
+            || self.note.is_some()
+            || self.state.is_some()
+            || self.fullscreen
+    }
+}
+
+// Names are the ones used in the settings file.
+fn parse_name<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    T::deserialize(toml::Value::String(text.to_lowercase())).map_err(|err| err.to_string())
+}
+
+fn parse_clef(text: &str) -> Result<ClefType, String> {
+    match text.to_lowercase().as_str() {
+        "treble" => Ok(ClefType::Treble),
+        "bass" => Ok(ClefType::Bass),
+        "auto" => Ok(ClefType::Auto),
+        _ => Err(format!(
+            "unknown clef {}, expected treble, bass or auto",
+            text
+        )),
+    }
+}
+
+fn parse_theme(text: &str) -> Result<ThemeChoice, String> {
+    if let Ok(theme) = parse_name::<Theme>(text) {
+        return Ok(ThemeChoice::Preset(theme));
+    }
+    let path = PathBuf::from(text);
+    if path.is_file() {
+        return Ok(ThemeChoice::File(path));
+    }
+    Err(format!(
+        "unknown theme {}, expected light, dark, high-contrast, color-blind, custom or a theme file",
+        text
+    ))
+}
+
+fn parse_key(text: &str) -> Result<Key, String> {
+    if let Ok(fifths) = text.parse::<i8>() {
+        if (-7..=7).contains(&fifths) {
+            return Ok(Key::new(fifths));
+        }
+        return Err(format!(
+            "{} is out of range, keys have -7 to 7 fifths",
+            fifths
+        ));
+    }
+    (-7..=7)
+        .map(Key::new)
+        .find(|key| key.name().eq_ignore_ascii_case(text))
+        .ok_or_else(|| format!("unknown key {}", text))
+}
+
+pub fn find_port(ports: &[Port], pattern: &str) -> Option<Port> {
+    let pattern = pattern.to_lowercase();
+    ports
+        .iter()
+        .find(|port| port.name.to_lowercase().contains(&pattern))
+        .cloned()
+}
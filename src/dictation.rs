@@ -0,0 +1,771 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use druid::{
+    kurbo::{BezPath, Circle, Ellipse, Line},
+    piet::{FontFamily, FontWeight, Text, TextLayout, TextLayoutBuilder},
+    widget::Svg,
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, Lens, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, Rect, RenderContext, Size, TimerToken, UpdateCtx, Vec2, Widget, WidgetPod,
+};
+
+use super::layout::{self, Bracket, Glyph, Ottava};
+use super::midi::{MidiEvent, MidiMessage};
+use super::note;
+use super::rhythm::{
+    transcribe, ClockTempo, Measure, PlayedNote, RhythmEvent, Tempo, TimeSignature,
+};
+use super::settings;
+use super::staff::{glyph_svg, paint_label};
+
+const MEASURES_SHOWN: usize = 4;
+// Lines always shown, in staff spaces above the bottom line, with room
+// for ledger lines above and below. Notes and ottava labels past them
+// make the staff smaller, keeping this much room at the edge.
+const ROOM: (f64, f64) = (-4.0, 8.0);
+const EDGE: f64 = 0.5;
+const STEM_LEN: f64 = 3.5;
+// Ottava labels keep to the edges unless the notes under them reach
+// further, their boxes start at these lines.
+const LABEL_ABOVE: f64 = 6.4;
+const LABEL_BELOW: f64 = -2.4;
+const LABEL_HEIGHT: f64 = 1.5;
+const LABEL_GAP: f64 = 0.5;
+// Held notes and rests grow on screen at this interval between MIDI
+// events.
+const FRAME: std::time::Duration = std::time::Duration::from_millis(50);
+
+#[test]
+fn test_time_without_events() {
+    let mut state = DictationState::new();
+    state.time = TimeSignature::new(4, 4);
+    state.tempo = Tempo::new(60.0);
+    // Nothing moves before the first note.
+    state.advance(1_000_000);
+    assert!(state.measures().is_empty());
+    state.handle(&MidiEvent {
+        stamp: 5_000_000,
+        message: MidiMessage::NoteOn(60, 100),
+    });
+    let ticks = |state: &DictationState| -> u32 {
+        let measures = state.measures();
+        let held = measures[0].events.iter().filter(|e| !e.is_rest());
+        held.map(|e| e.value.ticks()).sum()
+    };
+    state.advance(1_000_000);
+    assert_eq!(ticks(&state), 4);
+    // A quarter at 60 bpm later the held note has grown by a quarter.
+    state.advance(1_000_000);
+    assert_eq!(ticks(&state), 8);
+    state.handle(&MidiEvent {
+        stamp: 7_000_000,
+        message: MidiMessage::NoteOff(60),
+    });
+    assert_eq!(state.measures().len(), 1);
+    // The rest after it grows into the next measure.
+    state.advance(4_000_000);
+    let measures = state.measures();
+    assert_eq!(measures.len(), 2);
+    assert_eq!(measures[0].events[0].keys, vec![60]);
+    assert!(measures[1].events.iter().all(|e| e.is_rest()));
+    assert_eq!(state.now, 11_000_000);
+    // A late event never moves the clock back.
+    state.handle(&MidiEvent {
+        stamp: 10_900_000,
+        message: MidiMessage::Clock,
+    });
+    assert_eq!(state.now, 11_000_000);
+}
+
+#[test]
+fn test_ottava_groups() {
+    fn play(state: &mut DictationState, key: u8, on: u64, off: u64) {
+        state.handle(&MidiEvent {
+            stamp: on * 1_000_000,
+            message: MidiMessage::NoteOn(key, 100),
+        });
+        state.handle(&MidiEvent {
+            stamp: off * 1_000_000,
+            message: MidiMessage::NoteOff(key),
+        });
+    }
+    let mut state = DictationState::new();
+    state.time = TimeSignature::new(4, 4);
+    state.tempo = Tempo::new(60.0);
+    // G5, then C7 tied over the barline and a low E3.
+    play(&mut state, 79, 0, 1);
+    play(&mut state, 96, 1, 6);
+    play(&mut state, 52, 6, 7);
+    state.advance(1_000_000);
+    let measures = state.measures();
+    assert_eq!(measures.len(), 2);
+    let limits = settings::OttavaLimits::default();
+    // On its own the first measure would be under an 8va.
+    assert_eq!(ottavas(&measures[..1], &limits), vec![Some(Ottava::Alta)]);
+    assert_eq!(ottavas(&measures, &limits), vec![None, None]);
+    play(&mut state, 98, 9, 10);
+    let measures = state.measures();
+    assert_eq!(ottavas(&measures, &limits)[2], Some(Ottava::Alta));
+}
+
+#[test]
+fn test_label_lines() {
+    assert_eq!(label_lines(Ottava::Alta, &[3.0]), (6.4, 7.9));
+    // A note left on ledger lines pushes the label up.
+    assert_eq!(label_lines(Ottava::Alta, &[7.0]), (8.0, 9.5));
+    assert_eq!(label_lines(Ottava::Bassa, &[1.0]), (-2.4, -3.9));
+    assert_eq!(label_lines(Ottava::Bassa, &[-3.5, 2.0]), (-4.5, -6.0));
+}
+
+// Written line of the key above the bottom line of the treble staff,
+// moved by the ottava shift.
+fn key_line(key: u8, shift: f64) -> (f64, note::NoteAlt) {
+    let note = note::Note::new(key);
+    let alt = note.alteration(note::NoteAlt::Flat);
+    let line = note.line(note::NoteAlt::Flat) - note::Note::new(64).line(note::NoteAlt::White);
+    (line - shift, alt)
+}
+
+// Ottava line of each measure. Measures joined by a tie over the barline
+// share one, so the tie stays level.
+fn ottavas(measures: &[Measure], limits: &settings::OttavaLimits) -> Vec<Option<Ottava>> {
+    let mut ottavas = Vec::new();
+    let mut start = 0;
+    for (idx, measure) in measures.iter().enumerate() {
+        let tied = measure.events.last().is_some_and(|event| event.tied);
+        if tied && idx + 1 < measures.len() {
+            continue;
+        }
+        let lines: Vec<f64> = measures[start..=idx]
+            .iter()
+            .flat_map(|measure| measure_lines(measure, 0.0))
+            .collect();
+        let ottava = Ottava::pick(&lines, limits);
+        ottavas.extend(std::iter::repeat(ottava).take(idx + 1 - start));
+        start = idx + 1;
+    }
+    ottavas
+}
+
+// Lines the label box spans, nearest the staff first, clear of the
+// written notes under the bracket.
+fn label_lines(ottava: Ottava, lines: &[f64]) -> (f64, f64) {
+    let reach = 0.5 + LABEL_GAP;
+    if ottava.is_above() {
+        let high = lines.iter().cloned().fold(f64::MIN, f64::max);
+        let near = LABEL_ABOVE.max(high + reach);
+        (near, near + LABEL_HEIGHT)
+    } else {
+        let low = lines.iter().cloned().fold(f64::MAX, f64::min);
+        let near = LABEL_BELOW.min(low - reach);
+        (near, near - LABEL_HEIGHT)
+    }
+}
+
+// Written lines of the keys in the measure.
+fn measure_lines(measure: &Measure, shift: f64) -> Vec<f64> {
+    measure
+        .events
+        .iter()
+        .flat_map(|event| event.keys.iter().map(|key| key_line(*key, shift).0))
+        .collect()
+}
+
+// Lines shown from the bottom of the widget to its top, with the size
+// of a staff space that follows.
+#[derive(Clone, Copy)]
+struct View {
+    size: Size,
+    low: f64,
+    high: f64,
+}
+impl View {
+    fn new(size: Size, measures: &[Measure], ottavas: &[Option<Ottava>]) -> Self {
+        let (mut low, mut high) = ROOM;
+        for (measure, ottava) in measures.iter().zip(ottavas) {
+            let lines = measure_lines(measure, ottava.map_or(0.0, |ottava| ottava.shift()));
+            for line in lines.iter() {
+                low = low.min(line - 0.5 - EDGE);
+                high = high.max(line + 0.5 + EDGE);
+            }
+            if let Some(ottava) = ottava {
+                let (_, far) = label_lines(*ottava, &lines);
+                low = low.min(far - EDGE);
+                high = high.max(far + EDGE);
+            }
+        }
+        View { size, low, high }
+    }
+    fn space(&self) -> f64 {
+        self.size.height / (self.high - self.low)
+    }
+    fn line_y(&self, line: f64) -> f64 {
+        self.space() * (self.high - line)
+    }
+}
+
+#[derive(Clone, Data, Lens)]
+pub struct DictationState {
+    pub notes: Arc<Vec<PlayedNote>>,
+    pub start: Option<u64>,
+    pub now: u64,
+    pub tempo: Tempo,
+    pub time: TimeSignature,
+    pub follow_clock: bool,
+    pub clock: ClockTempo,
+}
+impl DictationState {
+    pub fn new() -> Self {
+        DictationState {
+            notes: Arc::new(Vec::new()),
+            start: None,
+            now: 0,
+            tempo: Tempo::new(90.0),
+            time: TimeSignature::new(4, 4),
+            follow_clock: false,
+            clock: ClockTempo::new(),
+        }
+    }
+    pub fn clear(&mut self) {
+        self.notes = Arc::new(Vec::new());
+        self.start = None;
+    }
+    // Moves the clock on by wall time, as MIDI stamps only come with
+    // events.
+    pub fn advance(&mut self, elapsed_us: u64) {
+        if self.start.is_some() {
+            self.now += elapsed_us;
+        }
+    }
+    pub fn handle(&mut self, event: &MidiEvent) {
+        self.now = self.now.max(event.stamp);
+        match event.message {
+            MidiMessage::NoteOn(key, _) => {
+                if self.start.is_none() {
+                    self.start = Some(event.stamp);
+                }
+                Arc::make_mut(&mut self.notes).push(PlayedNote::new(key, event.stamp, None));
+            }
+            MidiMessage::NoteOff(key) => {
+                let notes = Arc::make_mut(&mut self.notes);
+                if let Some(note) = notes
+                    .iter_mut()
+                    .rev()
+                    .find(|n| n.key == key && n.off.is_none())
+                {
+                    note.off = Some(event.stamp);
+                }
+            }
+            MidiMessage::Clock => {
+                self.clock.tick(event.stamp);
+                if self.follow_clock {
+                    if let Some(bpm) = self.clock.bpm() {
+                        self.tempo = Tempo::new(bpm.round());
+                    }
+                }
+            }
+            MidiMessage::Start => {
+                self.clear();
+                self.clock.reset();
+                self.start = Some(event.stamp);
+            }
+            _ => {}
+        }
+    }
+    pub fn measures(&self) -> Vec<Measure> {
+        match self.start {
+            Some(start) => transcribe(&self.notes, start, self.now, &self.tempo, &self.time),
+            None => Vec::new(),
+        }
+    }
+}
+
+pub struct Dictation {
+    clef_svg: WidgetPod<DictationState, Svg>,
+    timer: TimerToken,
+    last_frame: Instant,
+}
+impl Dictation {
+    pub fn new() -> Self {
+        Dictation {
+            clef_svg: WidgetPod::new(glyph_svg(Glyph::TrebleClef, &Color::BLACK)),
+            timer: TimerToken::INVALID,
+            last_frame: Instant::now(),
+        }
+    }
+    fn tint(&mut self, env: &Env) {
+        let staff = settings::Colors::from_env(env).staff;
+        self.clef_svg = WidgetPod::new(glyph_svg(Glyph::TrebleClef, &staff));
+    }
+    // Measures in view with their ottava lines.
+    fn shown(data: &DictationState, env: &Env) -> (Vec<Measure>, Vec<Option<Ottava>>) {
+        let mut measures = data.measures();
+        let mut ottavas = ottavas(&measures, &settings::OttavaLimits::from_env(env));
+        let hidden = measures.len().saturating_sub(MEASURES_SHOWN);
+        measures.drain(..hidden);
+        ottavas.drain(..hidden);
+        (measures, ottavas)
+    }
+    fn stroke_line(ctx: &mut PaintCtx, ink: &Color, from: Point, to: Point, width: f64) {
+        ctx.stroke(Line::new(from, to), ink, width);
+    }
+    fn paint_ledgers(ctx: &mut PaintCtx, ink: &Color, view: View, x: f64, line: f64) {
+        let space = view.space();
+        let line_w = layout::line_width(view.size.height);
+        let mut ledgers = Vec::new();
+        let mut i = -1;
+        while i as f64 >= line {
+            ledgers.push(i);
+            i -= 1;
+        }
+        let mut i = 5;
+        while i as f64 <= line {
+            ledgers.push(i);
+            i += 1;
+        }
+        for i in ledgers {
+            let y = view.line_y(i as f64);
+            Self::stroke_line(
+                ctx,
+                ink,
+                Point::new(x - space, y),
+                Point::new(x + space, y),
+                line_w,
+            );
+        }
+    }
+    fn paint_text(ctx: &mut PaintCtx, ink: &Color, text: &str, center: Point, font_size: f64) {
+        let layout = ctx
+            .text()
+            .new_text_layout(String::from(text))
+            .font(FontFamily::SERIF, font_size)
+            .default_attribute(FontWeight::BOLD)
+            .text_color(ink.clone())
+            .build();
+        if let Ok(layout) = layout {
+            let text_size = layout.size();
+            ctx.draw_text(
+                &layout,
+                center - Vec2::new(text_size.width / 2.0, text_size.height / 2.0),
+            );
+        }
+    }
+    fn paint_rest(ctx: &mut PaintCtx, ink: &Color, view: View, x: f64, event: &RhythmEvent) {
+        use super::rhythm::Duration;
+        let space = view.space();
+        let line_w = layout::line_width(view.size.height);
+        match event.value.duration {
+            Duration::Whole => {
+                let y = view.line_y(3.0);
+                ctx.fill(
+                    Rect::new(x - space * 0.6, y, x + space * 0.6, y + space * 0.5),
+                    ink,
+                );
+            }
+            Duration::Half => {
+                let y = view.line_y(2.0);
+                ctx.fill(
+                    Rect::new(x - space * 0.6, y - space * 0.5, x + space * 0.6, y),
+                    ink,
+                );
+            }
+            Duration::Quarter => {
+                let mut path = BezPath::new();
+                path.move_to(Point::new(x - space * 0.3, view.line_y(3.5)));
+                path.line_to(Point::new(x + space * 0.3, view.line_y(2.7)));
+                path.line_to(Point::new(x - space * 0.3, view.line_y(2.0)));
+                path.line_to(Point::new(x + space * 0.3, view.line_y(1.3)));
+                path.quad_to(
+                    Point::new(x - space * 0.6, view.line_y(1.0)),
+                    Point::new(x, view.line_y(0.5)),
+                );
+                ctx.stroke(path, ink, line_w * 2.0);
+            }
+            Duration::Eighth | Duration::Sixteenth => {
+                let flags = event.value.duration.flags();
+                for i in 0..flags {
+                    let line = 2.5 - i as f64;
+                    let dot = Point::new(x - space * 0.3, view.line_y(line));
+                    ctx.fill(Circle::new(dot, space * 0.2), ink);
+                    Self::stroke_line(
+                        ctx,
+                        ink,
+                        dot,
+                        Point::new(x + space * 0.4, view.line_y(line) - space * 0.1),
+                        line_w,
+                    );
+                }
+                Self::stroke_line(
+                    ctx,
+                    ink,
+                    Point::new(x + space * 0.4, view.line_y(2.5)),
+                    Point::new(x, view.line_y(1.0 - flags as f64)),
+                    line_w,
+                );
+            }
+        }
+        if event.value.dotted {
+            let dot = Point::new(x + space, view.line_y(2.5));
+            ctx.fill(Circle::new(dot, space * 0.15), ink);
+        }
+    }
+    // Paints noteheads, accidentals and ledger lines.
+    // Returns the stem root point for the given stem direction.
+    fn paint_chord(
+        ctx: &mut PaintCtx,
+        ink: &Color,
+        view: View,
+        x: f64,
+        event: &RhythmEvent,
+        stem_up: bool,
+        shift: f64,
+    ) -> (Point, Point) {
+        let space = view.space();
+        let line_w = layout::line_width(view.size.height);
+        let mut low = f64::MAX;
+        let mut high = f64::MIN;
+        for key in event.keys.iter() {
+            let (line, alt) = key_line(*key, shift);
+            low = low.min(line);
+            high = high.max(line);
+            Self::paint_ledgers(ctx, ink, view, x, line);
+            let center = Point::new(x, view.line_y(line));
+            let head = Ellipse::new(center, Vec2::new(space * 0.6, space * 0.45), -0.35);
+            if event.value.duration.is_hollow() {
+                ctx.stroke(head, ink, line_w * 1.5);
+            } else {
+                ctx.fill(head, ink);
+            }
+            match alt {
+                note::NoteAlt::Sharp => Self::paint_text(
+                    ctx,
+                    ink,
+                    "♯",
+                    center - Vec2::new(space * 1.4, 0.0),
+                    space * 2.0,
+                ),
+                note::NoteAlt::Flat => Self::paint_text(
+                    ctx,
+                    ink,
+                    "♭",
+                    center - Vec2::new(space * 1.4, 0.0),
+                    space * 2.0,
+                ),
+                note::NoteAlt::White => {}
+            }
+            if event.value.dotted {
+                let dot_line = if line.fract() == 0.0 {
+                    line + 0.5
+                } else {
+                    line
+                };
+                let dot = Point::new(x + space, view.line_y(dot_line));
+                ctx.fill(Circle::new(dot, space * 0.15), ink);
+            }
+        }
+        let head_dx = space * 0.55;
+        if stem_up {
+            (
+                Point::new(x + head_dx, view.line_y(low)),
+                Point::new(x + head_dx, view.line_y(high) - space * STEM_LEN),
+            )
+        } else {
+            (
+                Point::new(x - head_dx, view.line_y(high)),
+                Point::new(x - head_dx, view.line_y(low) + space * STEM_LEN),
+            )
+        }
+    }
+    fn stem_up(events: &[RhythmEvent], shift: f64) -> bool {
+        let lines: Vec<f64> = events
+            .iter()
+            .flat_map(|e| e.keys.iter().map(|k| key_line(*k, shift).0))
+            .collect();
+        if lines.is_empty() {
+            return true;
+        }
+        lines.iter().sum::<f64>() / (lines.len() as f64) < 2.0
+    }
+    fn paint_measure(
+        ctx: &mut PaintCtx,
+        ink: &Color,
+        view: View,
+        measure: &Measure,
+        time: &TimeSignature,
+        x0: f64,
+        x1: f64,
+        ties: &mut Vec<(u8, Point)>,
+        ottava: Option<Ottava>,
+    ) {
+        let space = view.space();
+        let shift = ottava.map_or(0.0, |ottava| ottava.shift());
+        let line_w = layout::line_width(view.size.height);
+        let measure_ticks = time.measure_ticks() as f64;
+        let pad = space * 1.5;
+        let mut xs = Vec::new();
+        let mut pos = 0;
+        for event in measure.events.iter() {
+            xs.push(x0 + pad + (x1 - x0 - pad * 2.0) * pos as f64 / measure_ticks);
+            pos += event.value.ticks();
+        }
+        let beams = measure.beams(time);
+        let beamed = |idx: usize| beams.iter().find(|range| range.contains(&idx)).cloned();
+        let mut stems: Vec<Option<(Point, Point)>> = vec![None; measure.events.len()];
+        for (idx, event) in measure.events.iter().enumerate() {
+            let x = xs[idx];
+            if event.is_rest() {
+                Self::paint_rest(ctx, ink, view, x, event);
+                continue;
+            }
+            let stem_up = match beamed(idx) {
+                Some(range) => Self::stem_up(&measure.events[range], shift),
+                None => Self::stem_up(std::slice::from_ref(event), shift),
+            };
+            let stem = Self::paint_chord(ctx, ink, view, x, event, stem_up, shift);
+            // Close ties from the previous event.
+            let head_y = |key: u8| view.line_y(key_line(key, shift).0);
+            for (key, from) in ties.drain(..) {
+                if event.keys.contains(&key) {
+                    let to = Point::new(x - space * 0.6, head_y(key) + space * 0.5);
+                    let mut path = BezPath::new();
+                    path.move_to(from);
+                    path.quad_to(Point::new((from.x + to.x) / 2.0, from.y + space * 0.8), to);
+                    ctx.stroke(path, ink, line_w);
+                }
+            }
+            if event.tied {
+                for key in event.keys.iter() {
+                    ties.push((
+                        *key,
+                        Point::new(x + space * 0.6, head_y(*key) + space * 0.5),
+                    ));
+                }
+            }
+            if event.value.duration.has_stem() {
+                stems[idx] = Some(stem);
+            }
+        }
+        // Beamed groups share one stem end line between first and last notes.
+        for range in beams.iter() {
+            let first = stems[range.start].unwrap();
+            let last = stems[range.end - 1].unwrap();
+            let up = first.1.y < first.0.y;
+            let end_y = range.clone().map(|idx| stems[idx].unwrap().1.y).fold(
+                if up { f64::MAX } else { f64::MIN },
+                |acc, y| {
+                    if up {
+                        acc.min(y)
+                    } else {
+                        acc.max(y)
+                    }
+                },
+            );
+            let beam_w = space * 0.5;
+            let step = if up { space * 0.75 } else { -space * 0.75 };
+            for idx in range.clone() {
+                let (root, end) = stems[idx].unwrap();
+                stems[idx] = Some((root, Point::new(end.x, end_y)));
+            }
+            Self::stroke_line(
+                ctx,
+                ink,
+                Point::new(first.1.x, end_y),
+                Point::new(last.1.x, end_y),
+                beam_w,
+            );
+            for idx in range.clone() {
+                let flags = measure.events[idx].value.duration.flags();
+                if flags < 2 {
+                    continue;
+                }
+                let x = stems[idx].unwrap().1.x;
+                let (from, to) = if idx + 1 < range.end {
+                    (x, stems[idx + 1].unwrap().1.x)
+                } else {
+                    (x - space, x)
+                };
+                Self::stroke_line(
+                    ctx,
+                    ink,
+                    Point::new(from, end_y + step),
+                    Point::new(to, end_y + step),
+                    beam_w,
+                );
+            }
+        }
+        for (idx, stem) in stems.iter().enumerate() {
+            if let Some((root, end)) = stem {
+                Self::stroke_line(ctx, ink, *root, *end, line_w * 1.2);
+                let flags = measure.events[idx].value.duration.flags();
+                if beamed(idx).is_none() {
+                    let dir = if end.y < root.y { 1.0 } else { -1.0 };
+                    for i in 0..flags {
+                        let y = end.y + dir * space * 0.8 * i as f64;
+                        let mut path = BezPath::new();
+                        path.move_to(Point::new(end.x, y));
+                        path.quad_to(
+                            Point::new(end.x + space * 1.2, y + dir * space * 0.8),
+                            Point::new(end.x + space * 0.8, y + dir * space * 2.2),
+                        );
+                        ctx.stroke(path, ink, line_w * 1.5);
+                    }
+                }
+            }
+        }
+        Self::stroke_line(
+            ctx,
+            ink,
+            Point::new(x1, view.line_y(0.0)),
+            Point::new(x1, view.line_y(4.0)),
+            line_w,
+        );
+        // One ottava line over the whole measure.
+        if let Some(ottava) = ottava {
+            let (near, far) = label_lines(ottava, &measure_lines(measure, shift));
+            let (y0, y1) = if ottava.is_above() {
+                (view.line_y(far), view.line_y(near))
+            } else {
+                (view.line_y(near), view.line_y(far))
+            };
+            let bracket = Bracket::new(
+                ottava,
+                Rect::new(x0 + pad / 2.0, y0, x0 + pad / 2.0, y1),
+                x1 - pad / 2.0,
+                space,
+            );
+            for dash in bracket.dashes.iter() {
+                ctx.stroke(*dash, ink, line_w);
+            }
+            paint_label(ctx, &bracket, ink);
+        }
+    }
+}
+
+impl Widget<DictationState> for Dictation {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DictationState, env: &Env) {
+        if let Event::Timer(token) = event {
+            if *token == self.timer {
+                let now = Instant::now();
+                data.advance((now - self.last_frame).as_micros() as u64);
+                self.last_frame = now;
+                self.timer = ctx.request_timer(FRAME);
+                return;
+            }
+        }
+        self.clef_svg.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &DictationState,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.tint(env);
+            self.last_frame = Instant::now();
+            self.timer = ctx.request_timer(FRAME);
+        }
+        self.clef_svg.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &DictationState,
+        data: &DictationState,
+        env: &Env,
+    ) {
+        if ctx.env_key_changed(&settings::STAFF) {
+            self.tint(env);
+            ctx.children_changed();
+            ctx.request_paint();
+            return;
+        }
+        self.clef_svg.update(ctx, data, env);
+        if !old_data.same(data)
+            || ctx.env_key_changed(&settings::BACKGROUND)
+            || ctx.env_key_changed(&settings::OTTAVA)
+            || ctx.env_key_changed(&settings::OTTAVA_ABOVE)
+            || ctx.env_key_changed(&settings::OTTAVA_BELOW)
+        {
+            // Notes and labels past the shown lines change the spacing.
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &DictationState,
+        env: &Env,
+    ) -> Size {
+        let size = bc.max();
+        let (measures, ottavas) = Self::shown(data, env);
+        let view = View::new(size, &measures, &ottavas);
+        let space = view.space();
+        let clef_size = Size::new(size.width, space * 6.0);
+        let clef_bc = BoxConstraints::new(Size::new(0.0, clef_size.height), clef_size);
+        self.clef_svg.layout(ctx, &clef_bc, data, env);
+        self.clef_svg
+            .set_origin(ctx, data, env, Point::new(space * 0.5, view.line_y(5.0)));
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DictationState, env: &Env) {
+        let size = ctx.size();
+        let (measures, ottavas) = Self::shown(data, env);
+        let view = View::new(size, &measures, &ottavas);
+        let space = view.space();
+        let line_w = layout::line_width(view.size.height);
+        let colors = settings::Colors::from_env(env);
+        let ink = &colors.staff;
+        ctx.fill(size.to_rect(), &colors.background);
+        for i in 0..5 {
+            let y = view.line_y(i as f64);
+            Self::stroke_line(
+                ctx,
+                ink,
+                Point::new(0.0, y),
+                Point::new(size.width, y),
+                line_w,
+            );
+        }
+        self.clef_svg.paint(ctx, data, env);
+        let time_x = space * 5.0;
+        Self::paint_text(
+            ctx,
+            ink,
+            &data.time.numerator.to_string(),
+            Point::new(time_x, view.line_y(3.0)),
+            space * 2.5,
+        );
+        Self::paint_text(
+            ctx,
+            ink,
+            &data.time.denominator.to_string(),
+            Point::new(time_x, view.line_y(1.0)),
+            space * 2.5,
+        );
+
+        let start_x = time_x + space * 1.5;
+        let measure_w = (size.width - start_x) / MEASURES_SHOWN as f64;
+        let mut ties = Vec::new();
+        for (idx, (measure, ottava)) in measures.iter().zip(ottavas).enumerate() {
+            let x0 = start_x + measure_w * idx as f64;
+            Self::paint_measure(
+                ctx,
+                ink,
+                view,
+                measure,
+                &data.time,
+                x0,
+                x0 + measure_w,
+                &mut ties,
+                ottava,
+            );
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use druid::{widget::LabelText, Data, Env, Key as EnvKey};
+use fluent::{FluentArgs, FluentBundle, FluentResource, FluentValue};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use unic_langid::LanguageIdentifier;
+
+// Current language, packed by Language::code.
+pub const LANGUAGE: EnvKey<u64> = EnvKey::new("rusted_big_note.language");
+
+// English comes first and is the fallback for missing messages.
+const RESOURCES: [(&str, &str); 2] = [
+    (
+        "en-US",
+        include_str!("../resources/i18n/en-US/rusted_big_note.ftl"),
+    ),
+    (
+        "ru-RU",
+        include_str!("../resources/i18n/ru-RU/rusted_big_note.ftl"),
+    ),
+];
+
+thread_local! {
+    static TRANSLATIONS: Translations = Translations::new();
+    // The system locale is looked up once, not on every message.
+    static SYSTEM_RESOURCE: usize = system_resource();
+}
+
+fn system_resource() -> usize {
+    let locale = sys_locale::get_locale().unwrap_or_default();
+    RESOURCES
+        .iter()
+        .position(|(tag, _)| locale.starts_with(&tag[..2]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+fn message_ids(source: &str) -> Vec<&str> {
+    source
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with(' '))
+        .filter_map(|line| line.split(" =").next())
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+#[test]
+fn test_resources_match() {
+    let english = message_ids(RESOURCES[0].1);
+    assert!(english.contains(&"trainer-next"));
+    for (locale, source) in RESOURCES.iter() {
+        assert_eq!(message_ids(source), english, "{}", locale);
+    }
+    assert_eq!(RESOURCES[Language::Russian.resource()].0, "ru-RU");
+}
+
+#[test]
+fn test_translate() {
+    let mut args = FluentArgs::new();
+    args.set("correct", FluentValue::from("3"));
+    args.set("total", FluentValue::from("5"));
+    assert_eq!(
+        translate(Language::English, "trainer-score", Some(&args)),
+        "Score: 3 / 5"
+    );
+    assert_eq!(
+        translate(Language::Russian, "trainer-score", Some(&args)),
+        "Счёт: 3 / 5"
+    );
+    assert_eq!(
+        translate(Language::Russian, "no-such-message", None),
+        "no-such-message"
+    );
+    for language in Language::ALL.iter() {
+        assert_eq!(Language::from_code(language.code()), *language);
+    }
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Language {
+    // Follows the system locale.
+    System,
+    English,
+    Russian,
+}
+impl Language {
+    pub const ALL: [Language; 3] = [Language::System, Language::English, Language::Russian];
+    pub fn id(&self) -> &'static str {
+        match self {
+            Language::System => "language-system",
+            Language::English => "language-english",
+            Language::Russian => "language-russian",
+        }
+    }
+    pub fn code(&self) -> u64 {
+        Self::ALL.iter().position(|l| l == self).unwrap_or(0) as u64
+    }
+    pub fn from_code(code: u64) -> Self {
+        *Self::ALL.get(code as usize).unwrap_or(&Language::System)
+    }
+    pub fn from_env(env: &Env) -> Self {
+        Self::from_code(env.get(LANGUAGE))
+    }
+    // Index into RESOURCES.
+    fn resource(&self) -> usize {
+        match self {
+            Language::English => 0,
+            Language::Russian => 1,
+            Language::System => SYSTEM_RESOURCE.with(|resource| *resource),
+        }
+    }
+}
+
+struct Translations {
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+impl Translations {
+    fn new() -> Self {
+        let bundles = RESOURCES
+            .iter()
+            .map(|(tag, source)| {
+                let langid: LanguageIdentifier = tag.parse().unwrap_or_default();
+                let mut bundle = FluentBundle::new(vec![langid]);
+                // No Unicode isolation marks around arguments, labels
+                // are plain text.
+                bundle.set_use_isolating(false);
+                let resource = match FluentResource::try_new(String::from(*source)) {
+                    Ok(resource) => resource,
+                    Err((resource, errors)) => {
+                        error!("{}: {:?}", tag, errors);
+                        resource
+                    }
+                };
+                if let Err(errors) = bundle.add_resource(resource) {
+                    error!("{}: {:?}", tag, errors);
+                }
+                bundle
+            })
+            .collect();
+        Translations { bundles }
+    }
+    fn format(&self, resource: usize, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+        let bundle = &self.bundles[resource];
+        let pattern = bundle.get_message(id)?.value()?;
+        let mut errors = Vec::new();
+        let text = bundle.format_pattern(pattern, args, &mut errors);
+        if !errors.is_empty() {
+            error!("{}: {:?}", id, errors);
+        }
+        Some(text.into_owned())
+    }
+}
+
+// Falls back to English, then to the message id itself.
+pub fn translate(language: Language, id: &str, args: Option<&FluentArgs>) -> String {
+    TRANSLATIONS.with(|translations| {
+        translations
+            .format(language.resource(), id, args)
+            .or_else(|| translations.format(0, id, args))
+            .unwrap_or_else(|| {
+                error!("Missing translation: {}", id);
+                String::from(id)
+            })
+    })
+}
+
+pub fn tr(env: &Env, id: &str) -> String {
+    translate(Language::from_env(env), id, None)
+}
+
+// Arguments are formatted by the caller, so numbers look the same in
+// every language.
+pub fn translate_args(language: Language, id: &str, args: &[(&str, String)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, FluentValue::from(value.clone()));
+    }
+    translate(language, id, Some(&fluent_args))
+}
+
+pub fn tr_args(env: &Env, id: &str, args: &[(&str, String)]) -> String {
+    translate_args(Language::from_env(env), id, args)
+}
+
+// Label text that follows the language setting.
+pub fn label<T: Data>(id: &'static str) -> LabelText<T> {
+    LabelText::from(move |_data: &T, env: &Env| tr(env, id))
+}
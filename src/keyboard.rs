@@ -0,0 +1,141 @@
+use druid::{
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, Rect, RenderContext, Selector, Size, UpdateCtx, Widget,
+};
+
+use super::midi::MidiData;
+use super::settings;
+
+pub const KEY_PRESSED: Selector<u8> = Selector::new("rusted_big_note.key-pressed");
+
+const BLACK_KEYS: [usize; 5] = [1, 3, 6, 8, 10];
+
+#[test]
+fn test_key_rects() {
+    let keyboard = Keyboard::new(48, 72);
+    let rects = keyboard.key_rects(Size::new(150.0, 100.0));
+    assert_eq!(rects.len(), 25);
+    let whites: Vec<&(u8, Rect)> = rects.iter().filter(|(nr, _)| !is_black(*nr)).collect();
+    assert_eq!(whites.len(), 15);
+    assert_eq!(whites[0].1, Rect::new(0.0, 0.0, 10.0, 100.0));
+    assert_eq!(
+        keyboard.key_at(Size::new(150.0, 100.0), Point::new(5.0, 90.0)),
+        Some(48)
+    );
+    assert_eq!(
+        keyboard.key_at(Size::new(150.0, 100.0), Point::new(10.0, 10.0)),
+        Some(49)
+    );
+    assert_eq!(
+        keyboard.key_at(Size::new(150.0, 100.0), Point::new(149.0, 90.0)),
+        Some(72)
+    );
+}
+
+pub fn is_black(midi_nr: u8) -> bool {
+    BLACK_KEYS.contains(&(midi_nr as usize % 12))
+}
+
+// On-screen piano keyboard. Highlights the current note, held or let
+// go, and submits KEY_PRESSED with the MIDI number of a clicked key.
+pub struct Keyboard {
+    low: u8,
+    high: u8,
+}
+impl Keyboard {
+    pub fn new(low: u8, high: u8) -> Self {
+        let low = if is_black(low) { low - 1 } else { low };
+        let high = if is_black(high) { high + 1 } else { high };
+        Keyboard { low, high }
+    }
+    fn white_amount(&self) -> usize {
+        (self.low..=self.high).filter(|nr| !is_black(*nr)).count()
+    }
+    // White keys come first, so black keys are painted over them.
+    pub fn key_rects(&self, size: Size) -> Vec<(u8, Rect)> {
+        let white_w = size.width / self.white_amount() as f64;
+        let black_w = white_w * 0.6;
+        let black_h = size.height * 0.6;
+        let mut whites = Vec::new();
+        let mut blacks = Vec::new();
+        let mut x = 0.0;
+        for nr in self.low..=self.high {
+            if is_black(nr) {
+                blacks.push((
+                    nr,
+                    Rect::new(x - black_w / 2.0, 0.0, x + black_w / 2.0, black_h),
+                ));
+            } else {
+                whites.push((nr, Rect::new(x, 0.0, x + white_w, size.height)));
+                x += white_w;
+            }
+        }
+        whites.extend(blacks);
+        whites
+    }
+    pub fn key_at(&self, size: Size, pos: Point) -> Option<u8> {
+        self.key_rects(size)
+            .iter()
+            .rev()
+            .find(|(_nr, rect)| rect.contains(pos))
+            .map(|(nr, _rect)| *nr)
+    }
+}
+
+impl Widget<MidiData> for Keyboard {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut MidiData, _env: &Env) {
+        if let Event::MouseDown(mouse) = event {
+            if let Some(nr) = self.key_at(ctx.size(), mouse.pos) {
+                ctx.submit_command(KEY_PRESSED.with(nr));
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &MidiData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &MidiData, data: &MidiData, _env: &Env) {
+        if !old_data.same(data)
+            || ctx.env_key_changed(&settings::HIGHLIGHT)
+            || ctx.env_key_changed(&settings::SUSTAINED)
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &MidiData,
+        _env: &Env,
+    ) -> Size {
+        let width = bc.max().width;
+        bc.constrain(Size::new(width, width / self.white_amount() as f64 * 5.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &MidiData, env: &Env) {
+        let size = ctx.size();
+        let highlight = if data.held {
+            env.get(settings::HIGHLIGHT)
+        } else {
+            env.get(settings::SUSTAINED)
+        };
+        for (nr, rect) in self.key_rects(size) {
+            let pressed = data.note.as_ref().map(|n| n.midi_nr()) == Some(nr);
+            let color = match (pressed, is_black(nr)) {
+                (true, _) => highlight.clone(),
+                (false, true) => Color::BLACK,
+                (false, false) => Color::WHITE,
+            };
+            ctx.fill(rect, &color);
+            ctx.stroke(rect, &Color::BLACK, 1.0);
+        }
+    }
+}
@@ -0,0 +1,1399 @@
+use std::collections::HashMap;
+
+use druid::kurbo::{Affine, BezPath, Line, Point, Rect, Size};
+use druid::Color;
+
+use super::note::{Key, Note, NoteAlt, SpelledPitch};
+use super::settings::OttavaLimits;
+use super::smufl::{self, Font};
+use super::staff::ClefType;
+
+// Staff height in staff spaces, the five lines are in the middle.
+const MAX_LINES: i32 = 11;
+const STAFF_LINES: i32 = 5;
+// Room left above and below the outermost heads and accidentals when
+// they reach past the usual height, in staff spaces. Far notes are moved
+// by ottava lines first, the staff only shrinks to keep what is left in
+// view: the bracket label, the ledger lines the limits allow and notes
+// beyond two octaves.
+const EDGE_MARGIN: f64 = 0.5;
+// Horizontal spacing, in staff spaces.
+const SIGNATURE_GAP: f64 = 0.2;
+const ACCIDENTAL_GAP: f64 = 0.4;
+const NOTE_CLEARANCE: f64 = 3.0;
+// Room between the key signature and the first note of a sequence, and
+// after the last.
+const SEQUENCE_GAP: f64 = 1.0;
+// Notes less than this many lines apart are a second, or the same line
+// with different accidentals, and cannot share a column.
+const SECOND: f64 = 0.75;
+// Accidentals that would collide move left by their width times this.
+const ACCIDENTAL_STEP: f64 = 1.2;
+// Ledger lines of the SVG glyphs, as a share of the staff width.
+const ASSET_LEDGER: f64 = 1.0 / 7.0;
+// Line width of the SVG glyphs, as a share of the staff height, and
+// its minimum in pixels.
+const ASSET_LINE: f64 = 1.0 / 200.0;
+const MIN_LINE_WIDTH: f64 = 2.0;
+// Ottava lines, in staff spaces. An octave moves a note this far.
+const OCTAVE: f64 = 3.5;
+// Room between the label and the staff or the notes.
+const BRACKET_GAP: f64 = 0.5;
+const BRACKET_HEIGHT: f64 = 1.5;
+// Width of a label letter as a share of its height.
+const LABEL_ADVANCE: f64 = 0.55;
+const DASH: f64 = 0.6;
+const DASH_GAP: f64 = 0.4;
+// Degree labels under the notes, in staff spaces: the gap below the
+// lowest of the staff, the notes and a bracket, the font size and the
+// room a line of labels takes.
+const DEGREE_GAP: f64 = 0.5;
+pub const DEGREE_SIZE: f64 = 1.5;
+const DEGREE_HEIGHT: f64 = 2.0;
+
+#[test]
+fn test_glyph_aspect() {
+    assert!((Glyph::Notehead.aspect() - 16.847145 / 12.933291).abs() < 1e-9);
+    assert!((Glyph::TrebleClef.aspect() - 28.961012 / 82.419127).abs() < 1e-9);
+    for glyph in Glyph::ALL.iter() {
+        assert!(glyph.aspect() > 0.0, "{:?}", glyph);
+    }
+}
+
+#[test]
+fn test_tinted() {
+    let red = Color::rgb8(0xff, 0, 0);
+    for glyph in Glyph::ALL.iter() {
+        let tinted = glyph.tinted(&red);
+        assert!(tinted.contains("#ff0000"), "{:?}", glyph);
+        assert!(!tinted.contains("#000000") && !tinted.contains("#1a1a1a"));
+    }
+}
+
+#[test]
+fn test_single_note() {
+    let size = Size::new(700.0, 500.0);
+    let layout = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &[],
+        &OttavaLimits::default(),
+    );
+    assert_eq!(layout.lines.len(), 5);
+    assert!(layout.ledgers.is_empty());
+    assert_eq!(layout.clef.glyph, Glyph::TrebleClef);
+    assert!(layout.notes.is_empty());
+    // A4 sits in the second space, F#5 needs a sharp, C4 a ledger line.
+    let a4 = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &[Note::new(69)],
+        &OttavaLimits::default(),
+    );
+    assert_eq!(a4.notes[0].rect.center().y, 275.0);
+    assert!(a4.accidentals.is_empty());
+    let fis5 = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &[Note::new(78)],
+        &OttavaLimits::default(),
+    );
+    assert_eq!(fis5.accidentals[0].glyph, Glyph::Sharp);
+    assert!(fis5.accidentals[0].rect.x1 < fis5.notes[0].rect.x0);
+    let c4 = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &[Note::new(60)],
+        &OttavaLimits::default(),
+    );
+    assert_eq!(c4.ledgers.len(), 1);
+    assert_eq!(c4.ledgers[0].p0.y, c4.notes[0].rect.center().y);
+    // F in G major takes a natural.
+    let f = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(1),
+        NoteAlt::Sharp,
+        &[Note::new(77)],
+        &OttavaLimits::default(),
+    );
+    assert_eq!(f.signature.len(), 1);
+    assert_eq!(f.accidentals[0].glyph, Glyph::Natural);
+    assert!(f.notes[0].rect.x0 > f.signature[0].rect.x1);
+}
+
+#[test]
+fn test_chord() {
+    let size = Size::new(700.0, 500.0);
+    // C E G in the bass clef, chosen by the middle note.
+    let triad: Vec<Note> = [48, 52, 55].iter().map(|nr| Note::new(*nr)).collect();
+    let layout = StaffLayout::new(
+        size,
+        ClefType::Auto,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &triad,
+        &OttavaLimits::default(),
+    );
+    assert_eq!(layout.clef.glyph, Glyph::BassClef);
+    assert_eq!(layout.notes.len(), 3);
+    assert!(layout
+        .notes
+        .iter()
+        .all(|head| head.rect.x0 == layout.notes[0].rect.x0));
+    // The upper note of a second moves beside the lower one.
+    let seconds: Vec<Note> = [64, 66, 67].iter().map(|nr| Note::new(*nr)).collect();
+    let layout = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &seconds,
+        &OttavaLimits::default(),
+    );
+    assert_eq!(layout.notes[1].rect.x0, layout.notes[0].rect.x1);
+    assert_eq!(layout.notes[2].rect.x0, layout.notes[0].rect.x0);
+    // Accidentals closer than their height alternate between columns.
+    let sharps: Vec<Note> = [63, 66, 70].iter().map(|nr| Note::new(*nr)).collect();
+    let layout = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &sharps,
+        &OttavaLimits::default(),
+    );
+    let accidentals: Vec<Rect> = layout
+        .accidentals
+        .iter()
+        .map(|placed| placed.rect)
+        .collect();
+    assert_eq!(accidentals.len(), 3);
+    assert!(accidentals[1].x1 < accidentals[0].x0);
+    assert_eq!(accidentals[2].x0, accidentals[0].x0);
+    // The glyphs are known before the size, highest accidental first.
+    let mixed: Vec<Note> = [73, 71, 60].iter().map(|nr| Note::new(*nr)).collect();
+    let layout = StaffLayout::new(
+        size,
+        ClefType::Treble,
+        &Key::new(-1),
+        NoteAlt::Sharp,
+        &mixed,
+        &OttavaLimits::default(),
+    );
+    let glyphs: Vec<Glyph> = layout
+        .accidentals
+        .iter()
+        .map(|placed| placed.glyph)
+        .collect();
+    assert_eq!(glyphs, [Glyph::Sharp, Glyph::Natural]);
+    assert_eq!(
+        StaffLayout::chord_glyphs(&Key::new(-1), NoteAlt::Sharp, &mixed),
+        (vec![Glyph::Notehead; 3], glyphs)
+    );
+}
+
+#[test]
+fn test_sequence() {
+    let size = Size::new(700.0, 500.0);
+    let d_major: Vec<SpelledPitch> = ["D3", "E3", "F#3", "G3", "A3", "B3", "C#4", "D4"]
+        .iter()
+        .map(|name| name.parse().unwrap())
+        .collect();
+    let layout = StaffLayout::sequence(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        &d_major,
+        &OttavaLimits::default(),
+    );
+    assert_eq!(layout.notes.len(), 8);
+    // Left to right and step by step upwards.
+    for pair in layout.notes.windows(2) {
+        assert!(pair[1].rect.x0 > pair[0].rect.x1);
+        assert!(pair[1].rect.center().y < pair[0].rect.center().y);
+    }
+    let glyphs: Vec<Glyph> = layout
+        .accidentals
+        .iter()
+        .map(|placed| placed.glyph)
+        .collect();
+    assert_eq!(glyphs, [Glyph::Sharp, Glyph::Sharp]);
+    assert!(layout.ledgers.is_empty());
+    // The key signature has the sharps.
+    let in_key = StaffLayout::sequence(
+        size,
+        ClefType::Treble,
+        &Key::new(2),
+        &d_major,
+        &OttavaLimits::default(),
+    );
+    assert!(in_key.accidentals.is_empty());
+    assert!(in_key.notes[0].rect.x0 > in_key.signature[1].rect.x1);
+    // An accidental holds for the rest of the notes on its line.
+    let held: Vec<SpelledPitch> = ["C3", "Cx3", "Cx3", "C3"]
+        .iter()
+        .map(|name| name.parse().unwrap())
+        .collect();
+    let layout = StaffLayout::sequence(
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        &held,
+        &OttavaLimits::default(),
+    );
+    let glyphs: Vec<Glyph> = layout
+        .accidentals
+        .iter()
+        .map(|placed| placed.glyph)
+        .collect();
+    assert_eq!(glyphs, [Glyph::DoubleSharp, Glyph::Natural]);
+    assert_eq!(
+        StaffLayout::sequence_glyphs(&Key::new(0), &held),
+        (vec![Glyph::Notehead; 4], glyphs)
+    );
+    assert_eq!(layout.ledgers.len(), 4);
+    assert!(layout.ledgers[0].p1.x < layout.ledgers[1].p0.x);
+}
+
+#[test]
+fn test_full_range() {
+    let size = Size::new(700.0, 500.0);
+    let canvas = size.to_rect();
+    let mut cases = Vec::new();
+    for clef in [ClefType::Treble, ClefType::Bass, ClefType::Auto].iter() {
+        for fifths in -7..=7 {
+            cases.push((*clef, fifths, NoteAlt::Sharp));
+            cases.push((*clef, fifths, NoteAlt::Flat));
+        }
+    }
+    // Without ottava lines, so every note is written where it sounds.
+    for (clef, fifths, alteration) in cases {
+        let key = Key::new(fifths);
+        let mut previous: Option<(Glyph, f64)> = None;
+        for nr in 0..=127 {
+            let context = format!("{:?} {} {:?} {}", clef, fifths, alteration, nr);
+            let note = Note::new(nr);
+            let layout = StaffLayout::with_glyphs(
+                Glyphs::Assets,
+                size,
+                clef,
+                &key,
+                alteration.clone(),
+                std::slice::from_ref(&note),
+                &OttavaLimits::off(),
+                false,
+            );
+            assert_eq!(layout.lines.len(), 5, "{}", context);
+            assert_eq!(layout.signature.len(), fifths.unsigned_abs() as usize);
+            // Everything is on the canvas, the staff shrinks for notes
+            // far from it.
+            let placed = std::iter::once(&layout.clef)
+                .chain(layout.signature.iter())
+                .chain(layout.notes.iter())
+                .chain(layout.accidentals.iter());
+            for placed in placed {
+                assert_eq!(canvas.intersect(placed.rect), placed.rect, "{}", context);
+            }
+            let bottom = layout.lines[0].p0.y;
+            let space = bottom - layout.lines[1].p0.y;
+            let diff = note.line(alteration.clone())
+                - clef.resolve(Some(&note)).bottom_note().line(NoteAlt::White);
+            if (-1.5..=5.0).contains(&diff) {
+                assert!((space - 50.0).abs() < 1e-9, "{}", context);
+            }
+            let head = layout.notes[0].rect;
+            // Heads sit on a line or in a space, never lower for a higher
+            // note on the same clef.
+            let steps = (bottom - head.center().y) / space * 2.0;
+            assert!((steps - steps.round()).abs() < 1e-9, "{}", context);
+            if let Some((glyph, below)) = previous {
+                if glyph == layout.clef.glyph {
+                    assert!(steps.round() >= below, "{}", context);
+                }
+            }
+            previous = Some((layout.clef.glyph, steps.round()));
+            // One ledger line for every line between the head and the
+            // staff, each reaching past the head.
+            let line = steps.round() / 2.0;
+            let ledgers = if line < 0.0 {
+                (-line).floor()
+            } else {
+                (line - 4.0).floor().max(0.0)
+            };
+            assert_eq!(layout.ledgers.len(), ledgers as usize, "{}", context);
+            for ledger in layout.ledgers.iter() {
+                let y = (bottom - ledger.p0.y) / space;
+                assert!((y - y.round()).abs() < 1e-9, "{}", context);
+                assert!(
+                    ledger.p0.x < head.x0 && ledger.p1.x > head.x1,
+                    "{}",
+                    context
+                );
+            }
+            // The head is clear of the clef and the signature.
+            let start = layout
+                .signature
+                .last()
+                .map_or(layout.clef.rect.x1, |placed| placed.rect.x1);
+            assert!(head.x0 > start, "{}", context);
+            // An accidental unless the signature alters the step the same
+            // way, between the signature and the head.
+            let (step, alt) = note.step(alteration.clone());
+            let signed = if key.altered_steps().contains(&step) {
+                key.alteration()
+            } else {
+                NoteAlt::White
+            };
+            let accidental = layout.accidentals.first();
+            assert_eq!(accidental.is_some(), alt != signed, "{}", context);
+            if let Some(placed) = accidental {
+                assert_eq!(placed.glyph, Glyph::accidental(&alt), "{}", context);
+                assert!(placed.rect.x0 > start && placed.rect.x1 < head.x0);
+                assert!(placed.rect.y0 < head.center().y && placed.rect.y1 > head.center().y);
+            }
+        }
+    }
+    // Octaves are three and a half spaces apart.
+    for nr in 0..=115 {
+        let layout = StaffLayout::with_glyphs(
+            Glyphs::Assets,
+            size,
+            ClefType::Treble,
+            &Key::new(0),
+            NoteAlt::Sharp,
+            &[Note::new(nr), Note::new(nr + 12)],
+            &OttavaLimits::off(),
+            false,
+        );
+        let space = layout.lines[0].p0.y - layout.lines[1].p0.y;
+        let octave = layout.notes[0].rect.center().y - layout.notes[1].rect.center().y;
+        assert!((octave - space * 3.5).abs() < 1e-9, "{}", nr);
+    }
+}
+
+#[test]
+fn test_ottava() {
+    let limits = OttavaLimits::default();
+    assert_eq!(Ottava::pick(&[6.0], &limits), None);
+    assert_eq!(Ottava::pick(&[9.5], &limits), Some(Ottava::Alta));
+    assert_eq!(
+        Ottava::pick(&[11.5], &limits),
+        Some(Ottava::QuindicesimaAlta)
+    );
+    assert_eq!(Ottava::pick(&[-5.0, -2.0], &limits), Some(Ottava::Bassa));
+    assert_eq!(Ottava::pick(&[9.5], &OttavaLimits::off()), None);
+    // A chord that would move its low notes too far keeps its place.
+    assert_eq!(Ottava::pick(&[-2.0, 8.0], &limits), None);
+
+    let size = Size::new(700.0, 500.0);
+    let layout = |clef, nr| {
+        StaffLayout::with_glyphs(
+            Glyphs::Assets,
+            size,
+            clef,
+            &Key::new(0),
+            NoteAlt::Sharp,
+            &[Note::new(nr)],
+            &limits,
+            false,
+        )
+    };
+    // C7 is written as C6 with two ledger lines under an 8va.
+    let c7 = layout(ClefType::Treble, 96);
+    let bracket = c7.bracket.as_ref().unwrap();
+    assert_eq!(bracket.ottava, Ottava::Alta);
+    assert_eq!(c7.ledgers.len(), 2);
+    let head = c7.notes[0].rect;
+    assert!(bracket.label.y1 < head.y0 && bracket.label.x0 == head.x0);
+    let hook = bracket.dashes.last().unwrap();
+    assert!(hook.p0.x > head.x1 && hook.p1.y > hook.p0.y);
+    // C0 on the bass staff under an 8vb, the hook points up.
+    let c0 = layout(ClefType::Bass, 24);
+    let bracket = c0.bracket.as_ref().unwrap();
+    assert_eq!(bracket.ottava, Ottava::Bassa);
+    assert!(bracket.label.y0 > c0.notes[0].rect.y1);
+    let hook = bracket.dashes.last().unwrap();
+    assert!(hook.p1.y < hook.p0.y);
+    // Over the whole range the ledger lines stay within the limits
+    // unless even two octaves are not enough, and all is on the canvas.
+    for clef in [ClefType::Treble, ClefType::Bass, ClefType::Auto].iter() {
+        for nr in 0..=127 {
+            let layout = layout(*clef, nr);
+            let fifteen = match &layout.bracket {
+                Some(bracket) => {
+                    assert_eq!(
+                        size.to_rect().intersect(bracket.label),
+                        bracket.label,
+                        "{:?} {}",
+                        clef,
+                        nr
+                    );
+                    bracket.ottava.octaves().abs() == 2
+                }
+                None => false,
+            };
+            assert!(layout.ledgers.len() <= 3 || fifteen, "{:?} {}", clef, nr);
+            let head = layout.notes[0].rect;
+            assert_eq!(size.to_rect().intersect(head), head, "{:?} {}", clef, nr);
+        }
+    }
+}
+
+#[test]
+fn test_degree_band() {
+    let limits = OttavaLimits::default();
+    let size = Size::new(700.0, 500.0);
+    let layout = |clef, nr, degrees| {
+        StaffLayout::with_glyphs(
+            Glyphs::Assets,
+            size,
+            clef,
+            &Key::new(0),
+            NoteAlt::Sharp,
+            &[Note::new(nr)],
+            &limits,
+            degrees,
+        )
+    };
+    assert_eq!(layout(ClefType::Treble, 60, false).degrees, None);
+    // The labels go under the staff, the notes and an 8vb and fit on
+    // the canvas.
+    let fits = |layout: &StaffLayout, name: &str| {
+        let space = layout.lines[0].p0.y - layout.lines[1].p0.y;
+        let top = layout.degrees.unwrap();
+        let lowest = layout
+            .lines
+            .iter()
+            .chain(layout.ledgers.iter())
+            .map(|line| line.p0.y)
+            .chain(layout.notes.iter().map(|head| head.rect.y1))
+            .chain(layout.bracket.iter().map(|bracket| bracket.label.y1))
+            .fold(f64::MIN, f64::max);
+        assert!(top > lowest, "{}", name);
+        assert!(top + space * DEGREE_SIZE <= size.height, "{}", name);
+    };
+    for clef in [ClefType::Treble, ClefType::Bass].iter() {
+        for nr in 0..=127 {
+            fits(&layout(*clef, nr, true), &format!("{:?} {}", clef, nr));
+        }
+    }
+    let f_major: Vec<SpelledPitch> = ["F1", "G1", "A1", "Bb1", "C2", "D2", "E2", "F2"]
+        .iter()
+        .map(|name| name.parse().unwrap())
+        .collect();
+    let sequence = StaffLayout::sequence_with_glyphs(
+        Glyphs::Assets,
+        size,
+        ClefType::Bass,
+        &Key::new(-1),
+        &f_major,
+        &limits,
+        true,
+    );
+    fits(&sequence, "F major in bass");
+}
+
+#[test]
+fn test_chord_range() {
+    let size = Size::new(700.0, 500.0);
+    let clear = |a: &Rect, b: &Rect| a.intersect(*b).area() < 1e-6;
+    let white: Vec<u8> = (0..=127)
+        .filter(|nr| Key::new(0).contains(&Note::new(*nr)))
+        .collect();
+    // Clusters of seconds and triads with accidentals, over the whole
+    // range and in both spellings.
+    let clusters = white.windows(4).map(|notes| notes.to_vec());
+    let triads = (0..=120).flat_map(|root| {
+        vec![
+            vec![root, root + 4, root + 7],
+            vec![root, root + 3, root + 6],
+        ]
+    });
+    for notes in clusters.chain(triads) {
+        let notes: Vec<Note> = notes.iter().map(|nr| Note::new(*nr)).collect();
+        for alteration in [NoteAlt::Sharp, NoteAlt::Flat].iter() {
+            let context = format!("{:?} {:?}", notes, alteration);
+            let layout = StaffLayout::with_glyphs(
+                Glyphs::Assets,
+                size,
+                ClefType::Auto,
+                &Key::new(0),
+                alteration.clone(),
+                &notes,
+                &OttavaLimits::off(),
+                false,
+            );
+            let heads: Vec<Rect> = layout.notes.iter().map(|placed| placed.rect).collect();
+            let accidentals: Vec<Rect> = layout
+                .accidentals
+                .iter()
+                .map(|placed| placed.rect)
+                .collect();
+            assert_eq!(heads.len(), notes.len(), "{}", context);
+            let left = heads.iter().map(|head| head.x0).fold(f64::MAX, f64::min);
+            let right = heads.iter().map(|head| head.x1).fold(f64::MIN, f64::max);
+            for (i, head) in heads.iter().enumerate() {
+                assert!(
+                    heads[i + 1..].iter().all(|other| clear(head, other)),
+                    "{}",
+                    context
+                );
+            }
+            for (i, accidental) in accidentals.iter().enumerate() {
+                assert!(accidental.x1 < left, "{}", context);
+                assert!(
+                    accidentals[i + 1..]
+                        .iter()
+                        .all(|other| clear(accidental, other)),
+                    "{}",
+                    context
+                );
+            }
+            for ledger in layout.ledgers.iter() {
+                assert!(ledger.p0.x < left && ledger.p1.x > right, "{}", context);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_font_glyphs() {
+    let font = smufl::test_font();
+    let glyphs = Glyphs::Font(&font);
+    let size = Size::new(700.0, 500.0);
+    let space = 50.0;
+    let layout = StaffLayout::with_glyphs(
+        glyphs,
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &[Note::new(60), Note::new(78)],
+        &OttavaLimits::default(),
+        false,
+    );
+    // The G clef origin is on the G line, the heads centre on theirs.
+    let g_line = 300.0;
+    assert!((layout.clef.rect.y0 - (g_line - space * 4.392)).abs() < 1e-9);
+    assert_eq!(layout.notes[0].rect.center().y, 400.0);
+    // Line thicknesses and the ledger length follow the metadata.
+    assert!((layout.line_width - space * 0.13).abs() < 1e-9);
+    assert!((layout.ledger_width - space * 0.16).abs() < 1e-9);
+    assert_eq!(layout.ledgers.len(), 1);
+    let head = layout.notes[0].rect;
+    assert!((layout.ledgers[0].length() - (head.width() + space * 0.8)).abs() < 1e-9);
+    let sharp = layout.accidentals[0].rect;
+    assert!((head.x0 - sharp.x1 - space * ACCIDENTAL_GAP).abs() < 1e-9);
+    // Outlines are scaled into their boxes.
+    let path = glyphs.path(&layout.notes[0]).unwrap();
+    let bounds = druid::kurbo::Shape::bounding_box(&path);
+    assert!((bounds.x0 - head.x0).abs() < 1e-6 && (bounds.y1 - head.y1).abs() < 1e-6);
+    assert!(Glyphs::Assets.path(&layout.notes[0]).is_none());
+}
+
+// Symbols on the staff, drawn from the SVG files in assets or from a
+// SMuFL font.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Glyph {
+    TrebleClef,
+    BassClef,
+    Notehead,
+    Sharp,
+    Flat,
+    Natural,
+    DoubleSharp,
+    DoubleFlat,
+}
+impl Glyph {
+    pub const ALL: [Glyph; 8] = [
+        Glyph::TrebleClef,
+        Glyph::BassClef,
+        Glyph::Notehead,
+        Glyph::Sharp,
+        Glyph::Flat,
+        Glyph::Natural,
+        Glyph::DoubleSharp,
+        Glyph::DoubleFlat,
+    ];
+    pub fn source(&self) -> &'static str {
+        match self {
+            Glyph::TrebleClef => include_str!("../assets/treble clef.svg"),
+            Glyph::BassClef => include_str!("../assets/bass clef.svg"),
+            Glyph::Notehead => include_str!("../assets/note.svg"),
+            Glyph::Sharp => include_str!("../assets/sharp.svg"),
+            Glyph::Flat => include_str!("../assets/flat.svg"),
+            Glyph::Natural => include_str!("../assets/natural.svg"),
+            Glyph::DoubleSharp => include_str!("../assets/double sharp.svg"),
+            Glyph::DoubleFlat => include_str!("../assets/double flat.svg"),
+        }
+    }
+    // Source with its black parts in the given colour.
+    pub fn tinted(&self, color: &Color) -> String {
+        let (r, g, b, _) = color.as_rgba8();
+        let hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
+        self.source()
+            .replace("#000000", &hex)
+            .replace("#1a1a1a", &hex)
+    }
+    // SMuFL code point and glyph name.
+    pub fn codepoint(&self) -> u32 {
+        match self {
+            Glyph::TrebleClef => 0xE050,
+            Glyph::BassClef => 0xE062,
+            Glyph::Notehead => 0xE0A2,
+            Glyph::Flat => 0xE260,
+            Glyph::Natural => 0xE261,
+            Glyph::Sharp => 0xE262,
+            Glyph::DoubleSharp => 0xE263,
+            Glyph::DoubleFlat => 0xE264,
+        }
+    }
+    pub fn smufl_name(&self) -> &'static str {
+        match self {
+            Glyph::TrebleClef => "gClef",
+            Glyph::BassClef => "fClef",
+            Glyph::Notehead => "noteheadWhole",
+            Glyph::Flat => "accidentalFlat",
+            Glyph::Natural => "accidentalNatural",
+            Glyph::Sharp => "accidentalSharp",
+            Glyph::DoubleSharp => "accidentalDoubleSharp",
+            Glyph::DoubleFlat => "accidentalDoubleFlat",
+        }
+    }
+    // Width over height of the view box.
+    pub fn aspect(&self) -> f64 {
+        let source = self.source();
+        let view_box = source
+            .find("viewBox=\"")
+            .map(|start| &source[start + 9..])
+            .and_then(|rest| rest.split('"').next())
+            .map(|values| {
+                values
+                    .split_whitespace()
+                    .filter_map(|value| value.parse::<f64>().ok())
+                    .collect::<Vec<f64>>()
+            });
+        match view_box.as_deref() {
+            Some([_, _, width, height]) if *height > 0.0 => width / height,
+            _ => 1.0,
+        }
+    }
+    // Top and height of the SVG file around the line the glyph sits on,
+    // in staff spaces.
+    fn span(&self) -> (f64, f64) {
+        match self {
+            Glyph::TrebleClef => (-4.0, 6.0),
+            Glyph::BassClef => (-1.0, 3.2),
+            Glyph::Notehead => (-0.5, 1.0),
+            Glyph::Flat | Glyph::DoubleFlat => (-7.0 / 6.0, 1.5),
+            Glyph::Sharp | Glyph::Natural => (-0.75, 1.5),
+            Glyph::DoubleSharp => (-0.5, 1.0),
+        }
+    }
+    fn accidental(alt: &NoteAlt) -> Glyph {
+        match alt {
+            NoteAlt::Sharp => Glyph::Sharp,
+            NoteAlt::Flat => Glyph::Flat,
+            NoteAlt::White => Glyph::Natural,
+        }
+    }
+    // Accidental raising or lowering by the given semitones.
+    fn alter(alter: i8) -> Glyph {
+        match alter {
+            i8::MIN..=-2 => Glyph::DoubleFlat,
+            -1 => Glyph::Flat,
+            0 => Glyph::Natural,
+            1 => Glyph::Sharp,
+            _ => Glyph::DoubleSharp,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Glyphs<'a> {
+    Assets,
+    Font(&'a Font),
+}
+impl Glyphs<'static> {
+    // The music font loaded at startup, the SVG files without one.
+    pub fn current() -> Self {
+        match smufl::font() {
+            Some(font) => Glyphs::Font(font),
+            None => Glyphs::Assets,
+        }
+    }
+}
+impl<'a> Glyphs<'a> {
+    // Extent around the glyph origin in staff spaces, y pointing down.
+    pub fn bbox(&self, glyph: Glyph) -> Rect {
+        match self {
+            Glyphs::Font(font) => font.outline(glyph).bbox,
+            Glyphs::Assets => {
+                let (top, height) = glyph.span();
+                Rect::new(0.0, top, height * glyph.aspect(), top + height)
+            }
+        }
+    }
+    // Font outline moved into the box of a placed glyph, none for the
+    // SVG files.
+    pub fn path(&self, placed: &Placed) -> Option<BezPath> {
+        let outline = match self {
+            Glyphs::Font(font) => font.outline(placed.glyph),
+            Glyphs::Assets => return None,
+        };
+        let scale = placed.rect.height() / outline.bbox.height();
+        let transform = Affine::translate(placed.rect.origin().to_vec2())
+            * Affine::scale(scale)
+            * Affine::translate(-outline.bbox.origin().to_vec2());
+        Some(transform * outline.path.clone())
+    }
+    // Glyph with its left edge at x and its origin at height y.
+    fn place(&self, glyph: Glyph, x: f64, y: f64, space: f64) -> Placed {
+        let bbox = self.bbox(glyph);
+        Placed {
+            glyph,
+            rect: Rect::new(
+                x,
+                y + bbox.y0 * space,
+                x + bbox.width() * space,
+                y + bbox.y1 * space,
+            ),
+        }
+    }
+}
+
+// Line over or under notes written one or two octaves from where they
+// sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ottava {
+    Alta,
+    QuindicesimaAlta,
+    Bassa,
+    QuindicesimaBassa,
+}
+impl Ottava {
+    const ALL: [Ottava; 4] = [
+        Ottava::Alta,
+        Ottava::QuindicesimaAlta,
+        Ottava::Bassa,
+        Ottava::QuindicesimaBassa,
+    ];
+    // Octaves the notes sound above the written ones.
+    pub fn octaves(&self) -> i32 {
+        match self {
+            Ottava::Alta => 1,
+            Ottava::QuindicesimaAlta => 2,
+            Ottava::Bassa => -1,
+            Ottava::QuindicesimaBassa => -2,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Ottava::Alta => "8va",
+            Ottava::QuindicesimaAlta => "15ma",
+            Ottava::Bassa => "8vb",
+            Ottava::QuindicesimaBassa => "15mb",
+        }
+    }
+    pub fn is_above(&self) -> bool {
+        self.octaves() > 0
+    }
+    // Staff spaces the written notes are moved down by.
+    pub fn shift(&self) -> f64 {
+        self.octaves() as f64 * OCTAVE
+    }
+    // Line for notes at the given places in spaces above the bottom staff
+    // line, when they need more ledger lines than the limits allow. The
+    // one leaving the fewest extra ledger lines wins, none on a tie.
+    pub fn pick(lines: &[f64], limits: &OttavaLimits) -> Option<Ottava> {
+        if !limits.enabled || lines.is_empty() {
+            return None;
+        }
+        let low = lines.iter().cloned().fold(f64::MAX, f64::min);
+        let high = lines.iter().cloned().fold(f64::MIN, f64::max);
+        let excess = |ottava: &Option<Ottava>| {
+            let shift = ottava.map_or(0.0, |ottava| ottava.shift());
+            let (below, above) = (ledgers(low - shift).0, ledgers(high - shift).1);
+            below.saturating_sub(limits.below as usize)
+                + above.saturating_sub(limits.above as usize)
+        };
+        std::iter::once(None)
+            .chain(Ottava::ALL.iter().map(|ottava| Some(*ottava)))
+            .min_by_key(excess)
+            .flatten()
+    }
+}
+
+// Ledger lines below and above the staff for a note this many spaces
+// above the bottom line.
+pub fn ledgers(line: f64) -> (usize, usize) {
+    let top = (STAFF_LINES - 1) as f64;
+    (
+        (-line).floor().max(0.0) as usize,
+        (line - top).floor().max(0.0) as usize,
+    )
+}
+
+// Ottava label with its baseline on the bottom of the box and as tall as
+// it, then a dashed line past the notes ending in a hook toward the
+// staff.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bracket {
+    pub ottava: Ottava,
+    pub label: Rect,
+    pub dashes: Vec<Line>,
+}
+impl Bracket {
+    // The label box gets its width here, end is where the line stops.
+    pub fn new(ottava: Ottava, label: Rect, end: f64, space: f64) -> Self {
+        let width = ottava.label().len() as f64 * label.height() * LABEL_ADVANCE;
+        let label = Rect::new(label.x0, label.y0, label.x0 + width, label.y1);
+        // Halfway up the small letters.
+        let y = label.y1 - label.height() * 0.3;
+        let mut dashes = Vec::new();
+        let mut x = label.x1 + space * DASH_GAP;
+        let end = end.max(x + space * DASH);
+        while x < end {
+            dashes.push(Line::new((x, y), ((x + space * DASH).min(end), y)));
+            x += space * (DASH + DASH_GAP);
+        }
+        let hook = if ottava.is_above() {
+            label.y1
+        } else {
+            label.y0
+        };
+        dashes.push(Line::new((end, y), (end, hook)));
+        Bracket {
+            ottava,
+            label,
+            dashes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placed {
+    pub glyph: Glyph,
+    pub rect: Rect,
+}
+
+// Everything the staff draws, positioned for one size. The widget and
+// the render command both paint from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaffLayout {
+    pub line_width: f64,
+    pub lines: Vec<Line>,
+    pub ledger_width: f64,
+    pub ledgers: Vec<Line>,
+    pub clef: Placed,
+    pub signature: Vec<Placed>,
+    // Lowest note first.
+    pub notes: Vec<Placed>,
+    // Highest note first.
+    pub accidentals: Vec<Placed>,
+    pub bracket: Option<Bracket>,
+    // Top of the line of degree labels, when there is room for one.
+    pub degrees: Option<f64>,
+}
+impl StaffLayout {
+    pub fn new(
+        size: Size,
+        clef: ClefType,
+        key: &Key,
+        alteration: NoteAlt,
+        notes: &[Note],
+        limits: &OttavaLimits,
+    ) -> Self {
+        Self::with_glyphs(
+            Glyphs::current(),
+            size,
+            clef,
+            key,
+            alteration,
+            notes,
+            limits,
+            false,
+        )
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_glyphs(
+        glyphs: Glyphs,
+        size: Size,
+        clef: ClefType,
+        key: &Key,
+        alteration: NoteAlt,
+        notes: &[Note],
+        limits: &OttavaLimits,
+        degrees: bool,
+    ) -> Self {
+        let notes = chord_notes(notes);
+        // Auto picks the clef for the middle note of a chord.
+        let clef = clef.resolve(notes.get(notes.len() / 2));
+        let written = chord_written(&notes, &alteration, key);
+        let frame = Frame::new(glyphs, size, clef, key, &written, 0.0, limits, degrees);
+        let line_h = frame.line_h;
+
+        let head_w = glyphs.bbox(Glyph::Notehead).width() * line_h;
+        let column_x = (frame.lines_rect.center().x - head_w / 2.0)
+            .max(frame.signature_end + line_h * NOTE_CLEARANCE);
+        let mut heads: Vec<Placed> = Vec::new();
+        let mut accidentals: Vec<Placed> = Vec::new();
+        let mut ledger = (0, 0);
+        let mut shifted = false;
+        let mut previous: Option<(f64, bool)> = None;
+        for line in frame.lines.iter() {
+            let diff = line - frame.shift;
+            // The upper note of a second goes to the right of the lower.
+            let beside = match previous {
+                Some((previous, false)) => line - previous < SECOND,
+                _ => false,
+            };
+            previous = Some((*line, beside));
+            shifted |= beside;
+            let x = if beside { column_x + head_w } else { column_x };
+            heads.push(glyphs.place(Glyph::Notehead, x, frame.y(diff), line_h));
+            ledger = (ledger.0.min(diff as i32), ledger.1.max(diff as i32));
+        }
+        for ((_, glyph), head) in written.iter().zip(heads.iter()).rev() {
+            let glyph = match glyph {
+                Some(glyph) => *glyph,
+                None => continue,
+            };
+            let alt_w = glyphs.bbox(glyph).width() * line_h;
+            let mut placed = glyphs.place(
+                glyph,
+                column_x - line_h * ACCIDENTAL_GAP - alt_w,
+                head.rect.center().y,
+                line_h,
+            );
+            // Accidentals of close notes step to the left until they
+            // are clear of the ones above.
+            while accidentals
+                .iter()
+                .any(|other| other.rect.intersect(placed.rect).area() > 0.0)
+            {
+                placed.rect = placed
+                    .rect
+                    .with_origin((placed.rect.x0 - alt_w * ACCIDENTAL_STEP, placed.rect.y0));
+            }
+            accidentals.push(placed);
+        }
+
+        // Ledger lines reach past the heads by the font's extension, the
+        // SVG glyphs keep their fixed width.
+        let (ledger_x0, ledger_x1) = match glyphs {
+            Glyphs::Font(font) => {
+                let extension = line_h * font.engraving.leger_line_extension;
+                (column_x - extension, column_x + head_w + extension)
+            }
+            Glyphs::Assets => {
+                let x0 = column_x + head_w / 2.0 - size.width * ASSET_LEDGER / 2.0;
+                (x0, x0 + size.width * ASSET_LEDGER)
+            }
+        };
+        let ledger_x1 = ledger_x1 + if shifted { head_w } else { 0.0 };
+        let ledgers = frame.ledgers(ledger, ledger_x0, ledger_x1);
+
+        // The label starts over the heads, its line ends past them.
+        let end = ledger_x1.max(column_x + head_w);
+        frame.finish(column_x, end, heads, accidentals, ledgers)
+    }
+    /// Notes one after the other, like the degrees of a scale, spread
+    /// over the width after the key signature. Accidentals hold for the
+    /// rest of the notes, as in a measure.
+    pub fn sequence(
+        size: Size,
+        clef: ClefType,
+        key: &Key,
+        pitches: &[SpelledPitch],
+        limits: &OttavaLimits,
+    ) -> Self {
+        Self::sequence_with_glyphs(Glyphs::current(), size, clef, key, pitches, limits, false)
+    }
+    pub fn sequence_with_glyphs(
+        glyphs: Glyphs,
+        size: Size,
+        clef: ClefType,
+        key: &Key,
+        pitches: &[SpelledPitch],
+        limits: &OttavaLimits,
+        degrees: bool,
+    ) -> Self {
+        let middle = pitches
+            .get(pitches.len() / 2)
+            .and_then(|pitch| pitch.note());
+        let clef = clef.resolve(middle.as_ref());
+        let written = sequence_written(pitches, key);
+        // Each note gets the same room, wide enough for the widest
+        // accidental before it.
+        let head_w = glyphs.bbox(Glyph::Notehead).width();
+        let extension = match glyphs {
+            Glyphs::Font(font) => font.engraving.leger_line_extension,
+            Glyphs::Assets => smufl::Engraving::default().leger_line_extension,
+        };
+        let accidental_w = written
+            .iter()
+            .filter_map(|(_, glyph)| *glyph)
+            .map(|glyph| glyphs.bbox(glyph).width() + ACCIDENTAL_GAP)
+            .fold(extension, f64::max);
+        let notes_width =
+            2.0 * SEQUENCE_GAP + (head_w + accidental_w) * written.len() as f64 + extension;
+        let frame = Frame::new(
+            glyphs,
+            size,
+            clef,
+            key,
+            &written,
+            notes_width,
+            limits,
+            degrees,
+        );
+        let line_h = frame.line_h;
+        let (head_w, extension, accidental_w) =
+            (head_w * line_h, extension * line_h, accidental_w * line_h);
+        let start = frame.signature_end + line_h * SEQUENCE_GAP + accidental_w;
+        let room = (frame.lines_rect.x1 - line_h * SEQUENCE_GAP - extension - head_w - start)
+            / (pitches.len().max(2) - 1) as f64;
+        let mut heads = Vec::new();
+        let mut accidentals = Vec::new();
+        let mut ledgers = Vec::new();
+        let written = written.iter().map(|(_, glyph)| glyph);
+        for (index, (line, glyph)) in frame.lines.iter().zip(written).enumerate() {
+            let diff = line - frame.shift;
+            let x = start + room * index as f64;
+            let head = glyphs.place(Glyph::Notehead, x, frame.y(diff), line_h);
+            if let Some(glyph) = glyph {
+                let alt_w = glyphs.bbox(*glyph).width() * line_h;
+                let alt_x = x - line_h * ACCIDENTAL_GAP - alt_w;
+                accidentals.push(glyphs.place(*glyph, alt_x, head.rect.center().y, line_h));
+            }
+            let ledger = (diff.min(0.0) as i32, diff.max(0.0) as i32);
+            ledgers.extend(frame.ledgers(ledger, x - extension, x + head_w + extension));
+            heads.push(head);
+        }
+        let end = heads.last().map_or(start, |head| head.rect.x1 + extension);
+        frame.finish(start, end, heads, accidentals, ledgers)
+    }
+    /// Glyphs of the heads and of the accidentals a chord layout places,
+    /// in the same order. They do not depend on the size.
+    pub fn chord_glyphs(
+        key: &Key,
+        alteration: NoteAlt,
+        notes: &[Note],
+    ) -> (Vec<Glyph>, Vec<Glyph>) {
+        let written = chord_written(&chord_notes(notes), &alteration, key);
+        let accidentals = written.iter().rev().filter_map(|(_, glyph)| *glyph);
+        (vec![Glyph::Notehead; written.len()], accidentals.collect())
+    }
+    /// Glyphs of the heads and of the accidentals a sequence layout
+    /// places, in the same order.
+    pub fn sequence_glyphs(key: &Key, pitches: &[SpelledPitch]) -> (Vec<Glyph>, Vec<Glyph>) {
+        let written = sequence_written(pitches, key);
+        let accidentals = written.iter().filter_map(|(_, glyph)| *glyph);
+        (vec![Glyph::Notehead; written.len()], accidentals.collect())
+    }
+}
+
+// Notes of a chord lowest first, without doubles.
+fn chord_notes(notes: &[Note]) -> Vec<Note> {
+    let mut notes = notes.to_vec();
+    notes.sort_by_key(|note| note.midi_nr());
+    notes.dedup();
+    notes
+}
+
+// Line and accidental of each note of a chord.
+fn chord_written(notes: &[Note], alteration: &NoteAlt, key: &Key) -> Vec<(f64, Option<Glyph>)> {
+    notes
+        .iter()
+        .map(|note| {
+            (
+                note.line(alteration.clone()),
+                accidental(note, alteration, key),
+            )
+        })
+        .collect()
+}
+
+// Line and accidental of each note of a sequence, accidentals holding
+// for the rest of it.
+fn sequence_written(pitches: &[SpelledPitch], key: &Key) -> Vec<(f64, Option<Glyph>)> {
+    let mut carried = HashMap::new();
+    pitches
+        .iter()
+        .map(|pitch| (pitch.line(), spelled_accidental(pitch, key, &mut carried)))
+        .collect()
+}
+
+// Staff lines, clef and key signature, scaled to keep the written notes
+// and their ottava label in view.
+struct Frame {
+    line_h: f64,
+    lines_rect: Rect,
+    line_width: f64,
+    ledger_width: f64,
+    staff_lines: Vec<Line>,
+    clef: Placed,
+    signature: Vec<Placed>,
+    signature_end: f64,
+    // Notes in spaces above the bottom line, before the ottava shift.
+    lines: Vec<f64>,
+    ottava: Option<Ottava>,
+    shift: f64,
+    // The label edge nearest the staff.
+    near: Option<f64>,
+    // Top of the degree labels, in spaces above the bottom line.
+    degrees: Option<f64>,
+}
+impl Frame {
+    // Notes as lines above the lowest C with the accidental drawn
+    // before them. The staff gets smaller when the notes need more than
+    // the given staff spaces after the key signature, or a line of
+    // degree labels does not fit below.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        glyphs: Glyphs,
+        size: Size,
+        clef: ClefType,
+        key: &Key,
+        written: &[(f64, Option<Glyph>)],
+        notes_width: f64,
+        limits: &OttavaLimits,
+        degrees: bool,
+    ) -> Self {
+        let staff_line = clef.bottom_note().line(NoteAlt::White);
+        let lines: Vec<f64> = written.iter().map(|(line, _)| line - staff_line).collect();
+        let ottava = Ottava::pick(&lines, limits);
+        let shift = ottava.map_or(0.0, |ottava| ottava.shift());
+        // Reach of the written heads and their accidentals, in spaces
+        // above the bottom line.
+        let reach = written.iter().zip(lines.iter()).fold(
+            (f64::MAX, f64::MIN),
+            |(low, high), ((_, accidental), line)| {
+                let diff = line - shift;
+                let drawn = std::iter::once(Glyph::Notehead).chain(*accidental);
+                let (above, below) = drawn.fold((0.0f64, 0.0f64), |(above, below), glyph| {
+                    let bbox = glyphs.bbox(glyph);
+                    (above.max(-bbox.y0), below.max(bbox.y1))
+                });
+                (low.min(diff - below), high.max(diff + above))
+            },
+        );
+        // Spaces shown below the bottom line and above it.
+        let top = (STAFF_LINES - 1) as f64;
+        let pad = ((MAX_LINES - STAFF_LINES) / 2) as f64;
+        // The label edge nearest the staff, clear of it and of the notes.
+        let near = ottava.map(|ottava| {
+            if ottava.is_above() {
+                (top + BRACKET_GAP).max(reach.1 + BRACKET_GAP)
+            } else {
+                (-BRACKET_GAP).min(reach.0 - BRACKET_GAP)
+            }
+        });
+        let (mut low, mut high) = if written.is_empty() {
+            (-pad, top + pad)
+        } else {
+            (
+                (-pad).min(reach.0 - EDGE_MARGIN),
+                (top + pad).max(reach.1 + EDGE_MARGIN),
+            )
+        };
+        match (ottava, near) {
+            (Some(ottava), Some(near)) if ottava.is_above() => {
+                high = high.max(near + BRACKET_HEIGHT + EDGE_MARGIN)
+            }
+            (Some(_), Some(near)) => low = low.min(near - BRACKET_HEIGHT - EDGE_MARGIN),
+            _ => {}
+        }
+        // Labels share a line under everything else.
+        let degrees = if degrees {
+            let bracket = match (ottava, near) {
+                (Some(ottava), Some(near)) if !ottava.is_above() => near - BRACKET_HEIGHT,
+                _ => 0.0,
+            };
+            let top = reach.0.min(bracket).min(0.0) - DEGREE_GAP;
+            low = low.min(top - DEGREE_HEIGHT);
+            Some(top)
+        } else {
+            None
+        };
+        let clef_glyph = match clef {
+            ClefType::Bass => Glyph::BassClef,
+            _ => Glyph::TrebleClef,
+        };
+        let signature_glyph = Glyph::accidental(&key.alteration());
+        let width = glyphs.bbox(clef_glyph).width()
+            + (SIGNATURE_GAP + glyphs.bbox(signature_glyph).width())
+                * key.altered_steps().len() as f64
+            + notes_width;
+        let fit = size.height / (high - low);
+        let line_h = fit.min(size.width / width);
+        // A staff narrowed to fit is centred.
+        let margin = if line_h < fit {
+            (size.height - line_h * (high - low)) / 2.0
+        } else {
+            0.0
+        };
+        let lines_rect = Rect::new(
+            0.0,
+            size.height - margin + line_h * (low - top),
+            size.width,
+            size.height - margin + line_h * low,
+        );
+        let line_y = |line: f64| lines_rect.y1 - line_h * line;
+        let (line_width, ledger_width) = match glyphs {
+            Glyphs::Font(font) => (
+                line_h * font.engraving.staff_line_thickness,
+                line_h * font.engraving.leger_line_thickness,
+            ),
+            Glyphs::Assets => (line_width(size.height), line_width(size.height)),
+        };
+        let staff_lines: Vec<Line> = (0..STAFF_LINES)
+            .map(|i| {
+                let y = line_y(i as f64);
+                Line::new((lines_rect.x0, y), (lines_rect.x1, y))
+            })
+            .collect();
+
+        let clef_line = clef.reference_note().line(NoteAlt::White) - staff_line;
+        let clef_placed = glyphs.place(clef_glyph, lines_rect.x0, line_y(clef_line), line_h);
+
+        let mut signature_end = clef_placed.rect.x1;
+        let signature = clef
+            .signature_notes(key)
+            .iter()
+            .map(|note| {
+                let diff = note.line(NoteAlt::White) - staff_line;
+                let placed = glyphs.place(
+                    signature_glyph,
+                    signature_end + line_h * SIGNATURE_GAP,
+                    line_y(diff),
+                    line_h,
+                );
+                signature_end = placed.rect.x1;
+                placed
+            })
+            .collect();
+
+        Frame {
+            line_h,
+            lines_rect,
+            line_width,
+            ledger_width,
+            staff_lines,
+            clef: clef_placed,
+            signature,
+            signature_end,
+            lines,
+            ottava,
+            shift,
+            near,
+            degrees,
+        }
+    }
+    fn y(&self, line: f64) -> f64 {
+        self.lines_rect.y1 - self.line_h * line
+    }
+    // Ledger lines between the staff and the lowest and highest line
+    // reached.
+    fn ledgers(&self, (low, high): (i32, i32), x0: f64, x1: f64) -> Vec<Line> {
+        let below = low..0;
+        let above = STAFF_LINES..(high + 1);
+        below
+            .chain(above)
+            .map(|i| {
+                let y = self.y(i as f64);
+                Line::new((x0, y), (x1, y))
+            })
+            .collect()
+    }
+    // The ottava label starts at x, its line ends at end.
+    fn finish(
+        self,
+        x: f64,
+        end: f64,
+        notes: Vec<Placed>,
+        accidentals: Vec<Placed>,
+        ledgers: Vec<Line>,
+    ) -> StaffLayout {
+        let bracket = match (self.ottava, self.near) {
+            (Some(ottava), Some(near)) => {
+                let (y0, y1) = if ottava.is_above() {
+                    (self.y(near + BRACKET_HEIGHT), self.y(near))
+                } else {
+                    (self.y(near), self.y(near - BRACKET_HEIGHT))
+                };
+                Some(Bracket::new(
+                    ottava,
+                    Rect::new(x, y0, x, y1),
+                    end + self.line_h * BRACKET_GAP,
+                    self.line_h,
+                ))
+            }
+            _ => None,
+        };
+        let degrees = self.degrees.map(|line| self.y(line));
+        StaffLayout {
+            line_width: self.line_width,
+            lines: self.staff_lines,
+            ledger_width: self.ledger_width,
+            ledgers,
+            clef: self.clef,
+            signature: self.signature,
+            notes,
+            accidentals,
+            bracket,
+            degrees,
+        }
+    }
+}
+
+pub fn line_width(staff_height: f64) -> f64 {
+    (staff_height * ASSET_LINE).max(MIN_LINE_WIDTH)
+}
+
+// Accidental printed before the note: none for notes on a line the key
+// signature already alters the same way, a natural for white notes on
+// such a line.
+fn accidental(note: &Note, alteration: &NoteAlt, key: &Key) -> Option<Glyph> {
+    let alt = note.alteration(alteration.clone());
+    let step = ((note.line(alteration.clone()) * 2.0) as i64).rem_euclid(7) as usize;
+    let in_signature = key.altered_steps().contains(&step);
+    match alt {
+        NoteAlt::White if in_signature => Some(Glyph::Natural),
+        NoteAlt::White => None,
+        alt if in_signature && alt == key.alteration() => None,
+        alt => Some(Glyph::accidental(&alt)),
+    }
+}
+
+// Accidental of a spelled pitch, none when the key signature or an
+// earlier note on the same line in the same octave already has it.
+fn spelled_accidental(
+    pitch: &SpelledPitch,
+    key: &Key,
+    carried: &mut HashMap<(i32, usize), i8>,
+) -> Option<Glyph> {
+    let signature = if key.altered_steps().contains(&pitch.step()) {
+        key.fifths().signum()
+    } else {
+        0
+    };
+    let line = (pitch.octave(), pitch.step());
+    let current = *carried.get(&line).unwrap_or(&signature);
+    carried.insert(line, pitch.alter());
+    if pitch.alter() == current {
+        None
+    } else {
+        Some(Glyph::alter(pitch.alter()))
+    }
+}
@@ -0,0 +1,97 @@
+//! Note reading on a five line staff, as used by the Rusted Big Note app.
+//!
+//! [`note`] spells MIDI numbers as notes in a key, [`staff`] has the
+//! [`Staff`](staff::Staff) druid widget and [`midi`] reads a MIDI keyboard.
+//!
+//! The staff takes its colours from the environment, keys the app does
+//! not set keep the light theme. Point it at the note with a lens:
+//!
+//! ```no_run
+//! use druid::{AppLauncher, Data, Lens, WindowDesc};
+//! use rusted_big_note::note::{Key, Note};
+//! use rusted_big_note::settings::Colors;
+//! use rusted_big_note::staff::{ClefType, Staff};
+//!
+//! #[derive(Clone, Data, Lens)]
+//! struct Practice {
+//!     note: Option<Note>,
+//!     score: u32,
+//! }
+//!
+//! let staff = Staff::of(Practice::note)
+//!     .with_clef(ClefType::Auto)
+//!     .with_key(Key::new(-2));
+//! AppLauncher::with_window(WindowDesc::new(staff))
+//!     .configure_env(|env, _| Colors::default().set_env(env))
+//!     .launch(Practice {
+//!         note: Some(Note::new(62)),
+//!         score: 0,
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! The default `app` feature builds the app itself. Without it only the
+//! staff and what it needs are built, and the `serialize` feature adds
+//! serde support for notes, keys, clefs, the [`StaffState`](staff::StaffState)
+//! and the settings.
+
+pub mod midi;
+pub mod note;
+pub mod settings;
+pub mod staff;
+
+// The rest is public for the app binary only, the modules the staff
+// does not need are left out without the app feature.
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod audio;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod caption;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod cli;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod dictation;
+#[doc(hidden)]
+pub mod i18n;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod keyboard;
+#[doc(hidden)]
+pub mod layout;
+#[doc(hidden)]
+pub mod naming;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod pitch;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod profile;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod render;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod rhythm;
+#[doc(hidden)]
+pub mod smufl;
+#[cfg(all(test, feature = "app"))]
+mod snapshot;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod srs;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod stats;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod trainer;
+#[doc(hidden)]
+pub mod transpose;
+#[doc(hidden)]
+pub mod tuner;
+#[cfg(feature = "app")]
+#[doc(hidden)]
+pub mod worksheet;
@@ -1,60 +1,378 @@
+use structopt::StructOpt;
 use tracing::error;
+use tracing_subscriber::EnvFilter;
 
 use druid::{
     kurbo::Line,
+    lens,
     widget::{
-        Button, Container, CrossAxisAlignment, FillStrat, Flex, Label, LabelText, Padding, Painter,
-        SizedBox, Svg, SvgData, WidgetExt, WidgetWrapper,
+        Button, Checkbox, Container, Controller, CrossAxisAlignment, FillStrat, Flex, Label,
+        LabelText, Padding, Painter, RadioGroup, SizedBox, Stepper, Svg, SvgData, TextBox,
+        ViewSwitcher, WidgetExt, WidgetWrapper,
     },
-    AppLauncher, BoxConstraints, Color, Data, Env, Event, EventCtx, Insets, LayoutCtx, LifeCycle,
-    LifeCycleCtx, LocalizedString, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
-    WidgetPod, WindowDesc,
+    AppDelegate, AppLauncher, BoxConstraints, Command, Data, DelegateCtx, Env, Event, EventCtx,
+    ExtEventSink, Handled, Insets, LayoutCtx, Lens, LensExt, LifeCycle, LifeCycleCtx, PaintCtx,
+    Point, Rect, RenderContext, Size, Target, TimerToken, UpdateCtx, Widget, WidgetPod, WindowDesc,
+    WindowState,
+};
+use rusted_big_note::{
+    audio, caption, cli, dictation, i18n, keyboard, layout, midi, naming, note, pitch, profile,
+    render, rhythm, settings, smufl, srs, staff, stats, trainer, transpose, tuner, worksheet,
 };
-mod midi;
-mod note;
-mod staff;
 
-struct Root {
-    staff: WidgetPod<Option<note::Note>, staff::Staff>,
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+enum Mode {
+    Profiles,
+    Note,
+    Scale,
+    Dictation,
+    Trainer,
+    Tuner,
+    Statistics,
+    Settings,
 }
-impl Widget<Option<note::Note>> for Root {
-    fn event(
+
+#[derive(Clone, Data, Lens)]
+struct AppState {
+    mode: Mode,
+    midi: midi::MidiData,
+    dictation: dictation::DictationState,
+    trainer: trainer::TrainerState,
+    profile: profile::ProfileState,
+    audio: audio::AudioState,
+    tuner: tuner::TunerState,
+    transposition: transpose::TranspositionState,
+    naming: naming::Naming,
+    language: i18n::Language,
+    colors: settings::Colors,
+    // Colours of the custom theme, read once from the theme file. They
+    // are not saved, the colors table keeps its own.
+    custom_colors: settings::Colors,
+    window: settings::Window,
+    // Staff of the note view.
+    clef: staff::ClefType,
+    spelling: staff::Spelling,
+    reference: staff::Reference,
+    degrees: staff::Degrees,
+    // Scale view, on the clef of the note view. The note view can check
+    // against it.
+    scale: note::Scale,
+    // Music font from the settings, kept to be saved again. It is loaded
+    // once at startup.
+    font: String,
+    theme: settings::Theme,
+    theme_file: String,
+    caption: settings::Caption,
+    ottava: settings::OttavaLimits,
+}
+impl AppState {
+    fn settings(&self) -> settings::Settings {
+        let pool = &self.trainer.pool;
+        settings::Settings {
+            language: self.language,
+            naming: self.naming.system,
+            octaves: self.naming.octaves,
+            midi_port: self.midi.selected.clone(),
+            clef: self.clef,
+            spelling: self.spelling,
+            reference: self.reference,
+            degrees: self.degrees,
+            font: self.font.clone(),
+            theme: self.theme,
+            theme_file: self.theme_file.clone(),
+            trainer: settings::Trainer {
+                clef: pool.clef,
+                low: pool.low.round() as u8,
+                high: pool.high.round() as u8,
+                key: pool.key.fifths(),
+                accidentals: pool.accidentals,
+                spaced: self.trainer.spaced,
+            },
+            transposition: settings::Transposition {
+                instrument: self.transposition.instrument,
+                key: self.transposition.key.fifths(),
+                from_written: self.transposition.from_written,
+                second_staff: self.transposition.second_staff,
+            },
+            tuner: settings::Tuner {
+                a4: self.tuner.a4,
+                temperament: self.tuner.temperament,
+                tonic: self.tuner.tonic,
+            },
+            caption: self.caption.clone(),
+            ottava: self.ottava.clone(),
+            colors: self.colors.clone(),
+            window: self.window.clone(),
+        }
+    }
+    fn apply(&mut self, settings: &settings::Settings) {
+        self.language = settings.language;
+        self.naming = naming::Naming::new(settings.naming, settings.octaves);
+        self.midi.selected = settings.midi_port.clone();
+        self.clef = settings.clef;
+        self.spelling = settings.spelling;
+        self.reference = settings.reference;
+        self.degrees = settings.degrees;
+        self.font = settings.font.clone();
+        self.theme = settings.theme;
+        self.theme_file = settings.theme_file.clone();
+        let trainer = &settings.trainer;
+        self.trainer.pool = trainer::Pool::new(
+            trainer.clef,
+            trainer.low.clamp(21, 108),
+            trainer.high.clamp(21, 108),
+            trainer.accidentals,
+            note::Key::new(trainer.key),
+        );
+        self.trainer.spaced = trainer.spaced;
+        self.transposition.instrument = settings.transposition.instrument;
+        self.transposition.key = note::Key::new(settings.transposition.key);
+        self.transposition.from_written = settings.transposition.from_written;
+        self.transposition.second_staff = settings.transposition.second_staff;
+        self.tuner.a4 = settings.tuner.a4;
+        self.tuner.temperament = settings.tuner.temperament;
+        self.tuner.tonic = settings.tuner.tonic % 12;
+        self.caption = settings.caption.clone();
+        self.ottava = settings.ottava.clone();
+        self.colors = settings.colors.clone();
+        self.custom_colors = settings.custom_colors();
+        self.window = settings.window.clone();
+    }
+    // Notes of the note view. Several keys held down are shown together,
+    // otherwise the last note is.
+    fn played(&self) -> Vec<note::Note> {
+        if self.midi.keys.len() > 1 {
+            self.midi
+                .keys
+                .iter()
+                .map(|key| note::Note::new(*key))
+                .collect()
+        } else {
+            self.midi.note.iter().cloned().collect()
+        }
+    }
+    // Staff of the note view as played, before any transposition. It is
+    // what a session file keeps.
+    fn staff_state(&self) -> staff::StaffState {
+        let key = self.transposition.key.clone();
+        staff::StaffState {
+            notes: self.played(),
+            clef: self.clef,
+            alteration: self.spelling.alteration(&key),
+            key,
+        }
+    }
+    // Played notes transposed for the main staff.
+    fn shown_notes(&self) -> Vec<note::Note> {
+        self.played()
+            .iter()
+            .filter_map(|note| self.transposition.shown(note))
+            .collect()
+    }
+    // Label of the note view, naming what the main staff shows.
+    fn shown(&self) -> caption::Shown {
+        let played = self.played();
+        let transposition = &self.transposition;
+        let notes = self.shown_notes();
+        // Written input sounds as shown.
+        let sounding = if transposition.from_written {
+            &notes
+        } else {
+            &played
+        };
+        let frequencies = sounding
+            .iter()
+            .map(|note| self.tuner.target_frequency(note.midi_nr()))
+            .collect();
+        caption::Shown {
+            keys: played.iter().map(|note| note.midi_nr()).collect(),
+            notes,
+            key: transposition.shown_key(),
+            spelling: self.spelling,
+            frequencies,
+            options: self.caption.clone(),
+        }
+    }
+}
+
+struct Delegate {
+    midi: midi::MidiHandler,
+    sink: ExtEventSink,
+    audio: audio::AudioInput,
+    onset: pitch::Onset,
+    trainer: trainer::Trainer,
+    schedule: Option<(String, srs::Scheduler)>,
+}
+impl Delegate {
+    fn load_schedule(&mut self, student: &str) {
+        if let Some((name, _)) = &self.schedule {
+            if name == student {
+                return;
+            }
+        }
+        srs::Scheduler::migrate(student);
+        let scheduler = match srs::Scheduler::path(student) {
+            Some(path) => srs::Scheduler::load(&path),
+            None => srs::Scheduler::new(),
+        };
+        self.schedule = Some((String::from(student), scheduler));
+    }
+    fn next_target(&mut self, data: &mut trainer::TrainerState, student: &str) {
+        if data.spaced {
+            self.load_schedule(student);
+            let (_, scheduler) = self.schedule.as_ref().unwrap();
+            data.target = self.trainer.next_scheduled(&data.pool, scheduler);
+        } else {
+            data.target = self.trainer.next(&data.pool);
+        }
+    }
+    fn record(&mut self, data: &mut AppState, answer: &trainer::Answer) {
+        let pool = &data.trainer.pool;
+        let stats = std::sync::Arc::make_mut(&mut data.profile.stats);
+        stats.record(pool.clef, answer, pool.key.alteration());
+        if let Some(path) = data.profile.stats_path() {
+            if let Err(err) = data.profile.stats.save(&path) {
+                error!("{}: {}", path.display(), err);
+            }
+        }
+        if !data.trainer.spaced {
+            return;
+        }
+        self.load_schedule(&data.profile.name);
+        let data = &data.trainer;
+        let (student, scheduler) = self.schedule.as_mut().unwrap();
+        scheduler.record(
+            data.pool.clef,
+            &answer.target,
+            data.pool.key.alteration(),
+            answer.correct,
+            answer.reaction,
+        );
+        if let Some(path) = srs::Scheduler::path(student) {
+            if let Err(err) = scheduler.save(&path) {
+                error!("{}: {}", path.display(), err);
+            }
+        }
+    }
+    fn play(&mut self, data: &mut AppState, key: u8) {
+        data.midi.note = Some(note::Note::new(key));
+        // Clicked and sung notes have no key to hold.
+        data.midi.held = false;
+        if data.mode == Mode::Trainer {
+            if let Some(answer) = self.trainer.answer(key) {
+                if !answer.retry {
+                    self.record(data, &answer);
+                    std::sync::Arc::make_mut(&mut data.trainer.answers).push(answer.clone());
+                }
+                // A missed target stays on the staff until it is played.
+                if answer.correct {
+                    self.next_target(&mut data.trainer, &data.profile.name);
+                }
+                data.trainer.last = Some(answer);
+            }
+        }
+    }
+}
+impl AppDelegate<AppState> for Delegate {
+    fn command(
         &mut self,
-        ctx: &mut EventCtx,
-        event: &Event,
-        data: &mut Option<note::Note>,
-        env: &Env,
-    ) {
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut AppState,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(port) = cmd.get(midi::SELECT_PORT) {
+            self.midi.selected_port = Some(port.clone());
+            match self.midi.connect(self.sink.clone()) {
+                Ok(()) => {
+                    data.midi.selected = port.name.to_string();
+                    data.midi.message = String::new();
+                }
+                Err(err) => {
+                    error!("{}", err);
+                    data.midi.message = err;
+                }
+            }
+            return Handled::Yes;
+        }
+        if let Some(event) = cmd.get(midi::MIDI_EVENT) {
+            match event.message {
+                midi::MidiMessage::NoteOn(key, _) => {
+                    self.play(data, key);
+                    data.midi.held = true;
+                    data.midi.press(key);
+                }
+                midi::MidiMessage::NoteOff(key) => {
+                    data.midi.release(key);
+                    if data.midi.note.as_ref().map(|note| note.midi_nr()) == Some(key) {
+                        data.midi.held = false;
+                    }
+                }
+                _ => {}
+            }
+            data.dictation.handle(event);
+            data.tuner.handle(&event.message);
+            return Handled::Yes;
+        }
+        if cmd.is(audio::TOGGLE) {
+            if self.audio.is_running() {
+                self.audio.stop();
+                data.audio.detected = None;
+                data.audio.message = String::new();
+            } else if let Err(err) = self.audio.start(self.sink.clone(), data.tuner.a4) {
+                error!("{}", err);
+                data.audio.message = err.to_string();
+            }
+            data.audio.running = self.audio.is_running();
+            return Handled::Yes;
+        }
+        if let Some(detected) = cmd.get(pitch::PITCH) {
+            data.audio.detected = detected.clone();
+            data.tuner
+                .set_frequency(detected.as_ref().map(|pitch| pitch.frequency));
+            let nr = detected.as_ref().map(|pitch| pitch.note.midi_nr());
+            if let Some(key) = self.onset.feed(nr) {
+                self.play(data, key);
+            }
+            return Handled::Yes;
+        }
+        if let Some(key) = cmd.get(keyboard::KEY_PRESSED) {
+            self.play(data, *key);
+            return Handled::Yes;
+        }
+        if cmd.is(trainer::NEXT) {
+            self.next_target(&mut data.trainer, &data.profile.name);
+            return Handled::Yes;
+        }
+        if let Some(name) = cmd.get(profile::SELECT) {
+            data.profile.select(name);
+            data.trainer.answers = std::sync::Arc::new(Vec::new());
+            data.trainer.last = None;
+            data.mode = Mode::Note;
+            return Handled::Yes;
+        }
+        Handled::No
+    }
+}
+
+struct Root<T, W> {
+    staff: WidgetPod<T, W>,
+}
+impl<T: Data, W: Widget<T>> Widget<T> for Root<T, W> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
         self.staff.event(ctx, event, data, env)
     }
 
-    fn lifecycle(
-        &mut self,
-        ctx: &mut LifeCycleCtx,
-        event: &LifeCycle,
-        data: &Option<note::Note>,
-        env: &Env,
-    ) {
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
         self.staff.lifecycle(ctx, event, data, env)
     }
 
-    fn update(
-        &mut self,
-        ctx: &mut UpdateCtx,
-        _old_data: &Option<note::Note>,
-        data: &Option<note::Note>,
-        env: &Env,
-    ) {
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
         self.staff.update(ctx, data, env);
     }
 
-    fn layout(
-        &mut self,
-        ctx: &mut LayoutCtx,
-        bc: &BoxConstraints,
-        data: &Option<note::Note>,
-        env: &Env,
-    ) -> Size {
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
         const PADDING: f64 = 20.0;
         let size = bc.max();
         let staff_origin = Point::new(PADDING, PADDING);
@@ -65,25 +383,1133 @@ impl Widget<Option<note::Note>> for Root {
         size
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &Option<note::Note>, env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
         let size = ctx.size().to_rect();
-        ctx.fill(size, &Color::WHITE);
+        ctx.fill(size, &env.get(settings::BACKGROUND));
         self.staff.paint(ctx, data, env);
     }
 }
 
-fn build_ui() -> impl Widget<Option<note::Note>> {
+// Saves the settings whenever they change and keeps track of the
+// window geometry, which druid does not store in the app data.
+// Settings are saved a moment after they change, so resizing the window
+// doesn't write the file on every frame. The window position has no event
+// of its own and is checked at the same time.
+const SAVE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+// Only what changes in the app is written back, so options given on the
+// command line don't end up in the settings file.
+struct Persist {
+    // Settings file as last written, or as loaded at startup.
+    saved: settings::Settings,
+    // Session file the staff of the note view is written to on close.
+    session: Option<std::path::PathBuf>,
+    // Settings of the app when last compared.
+    shown: Option<settings::Settings>,
+    timer: TimerToken,
+}
+impl Persist {
+    fn new(saved: settings::Settings, session: Option<std::path::PathBuf>) -> Self {
+        Persist {
+            saved,
+            session,
+            shown: None,
+            timer: TimerToken::INVALID,
+        }
+    }
+    fn save(&mut self, data: &AppState) {
+        let shown = data.settings();
+        let saved = match &self.shown {
+            Some(before) if *before != shown => self.saved.with_changes(before, &shown),
+            _ => return,
+        };
+        self.shown = Some(shown);
+        if saved == self.saved {
+            return;
+        }
+        if let Some(path) = settings::Settings::path() {
+            if let Err(err) = saved.save(&path) {
+                error!("{}: {}", path.display(), err);
+            }
+        }
+        self.saved = saved;
+    }
+}
+fn window_position(ctx: &EventCtx, window: &mut settings::Window) {
+    let position = ctx.window().get_position();
+    window.x = Some(position.x);
+    window.y = Some(position.y);
+}
+impl<W: Widget<AppState>> Controller<AppState, W> for Persist {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            Event::WindowSize(size) => {
+                data.window.width = size.width;
+                data.window.height = size.height;
+            }
+            Event::Timer(token) if *token == self.timer => {
+                window_position(ctx, &mut data.window);
+                self.save(data);
+                self.timer = ctx.request_timer(SAVE_DELAY);
+                return;
+            }
+            Event::WindowCloseRequested => {
+                window_position(ctx, &mut data.window);
+                self.save(data);
+                if let Some(path) = &self.session {
+                    if let Err(err) = data.staff_state().save(path) {
+                        error!("{}", err);
+                    }
+                }
+            }
+            _ => (),
+        }
+        child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &AppState,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.shown = Some(data.settings());
+            self.timer = ctx.request_timer(SAVE_DELAY);
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+}
+
+fn build_staff<T: Data>(staff: impl Widget<T>) -> impl Widget<T> {
     Root {
-        staff: WidgetPod::new(
-            // staff::Staff::new(),
-            staff::Staff::from_note(note::Note::new(73)),
+        staff: WidgetPod::new(staff),
+    }
+}
+fn build_trainer_controls() -> impl Widget<trainer::TrainerState> {
+    let note_label = |nr: f64, key: &note::Key, env: &Env| {
+        naming::Naming::from_env(env).name(&note::Note::new(nr as u8), key.alteration(), key)
+    };
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            RadioGroup::new(vec![
+                (i18n::label("clef-treble"), staff::ClefType::Treble),
+                (i18n::label("clef-bass"), staff::ClefType::Bass),
+                (i18n::label("clef-auto"), staff::ClefType::Auto),
+            ])
+            .lens(trainer::TrainerState::pool.then(trainer::Pool::clef)),
+        )
+        .with_spacer(10.0)
+        .with_child(Label::new(
+            move |data: &trainer::TrainerState, env: &Env| {
+                let note = note_label(data.pool.low, &data.pool.key, env);
+                i18n::tr_args(env, "trainer-from", &[("note", note)])
+            },
+        ))
+        .with_child(
+            Stepper::new()
+                .with_range(21.0, 108.0)
+                .with_step(1.0)
+                .lens(trainer::TrainerState::pool.then(trainer::Pool::low)),
+        )
+        .with_child(Label::new(
+            move |data: &trainer::TrainerState, env: &Env| {
+                let note = note_label(data.pool.high, &data.pool.key, env);
+                i18n::tr_args(env, "trainer-to", &[("note", note)])
+            },
+        ))
+        .with_child(
+            Stepper::new()
+                .with_range(21.0, 108.0)
+                .with_step(1.0)
+                .lens(trainer::TrainerState::pool.then(trainer::Pool::high)),
+        )
+        .with_child(Label::new(|data: &trainer::TrainerState, env: &Env| {
+            let key = naming::Naming::from_env(env).key_name(&data.pool.key);
+            i18n::tr_args(env, "trainer-key", &[("key", key)])
+        }))
+        .with_child(Stepper::new().with_range(-7.0, 7.0).with_step(1.0).lens(
+            trainer::TrainerState::pool.then(trainer::Pool::key).map(
+                |key: &note::Key| key.fifths() as f64,
+                |key: &mut note::Key, fifths: f64| *key = note::Key::new(fifths as i8),
+            ),
+        ))
+        .with_child(
+            Checkbox::new(i18n::label("trainer-accidentals"))
+                .lens(trainer::TrainerState::pool.then(trainer::Pool::accidentals)),
+        )
+        .with_spacer(10.0)
+        .with_child(
+            Checkbox::new(i18n::label("trainer-spaced")).lens(trainer::TrainerState::spaced),
+        )
+        .with_spacer(10.0)
+        .with_child(Button::new(i18n::label("trainer-next")).on_click(
+            |ctx, _data: &mut trainer::TrainerState, _env| ctx.submit_command(trainer::NEXT),
+        ))
+}
+fn build_trainer() -> impl Widget<trainer::TrainerState> {
+    let staff = ViewSwitcher::new(
+        |data: &trainer::TrainerState, _env| (data.pool.clef, data.pool.key.clone()),
+        |(clef, key), _data, _env| {
+            Box::new(
+                build_staff(staff::Staff::new().with_clef(*clef).with_key(key.clone()))
+                    .lens(trainer::TrainerState::target),
+            )
+        },
+    );
+    let feedback = Label::new(|data: &trainer::TrainerState, env: &Env| {
+        let naming = naming::Naming::from_env(env);
+        let key = &data.pool.key;
+        let name = |note: &note::Note| naming.name(note, key.alteration(), key);
+        match &data.last {
+            Some(answer) if answer.correct => i18n::tr_args(
+                env,
+                "trainer-right",
+                &[
+                    ("note", name(&answer.target)),
+                    ("seconds", format!("{:.2}", answer.reaction as f64 / 1000.0)),
+                ],
+            ),
+            Some(answer) => i18n::tr_args(
+                env,
+                "trainer-wrong",
+                &[
+                    ("played", name(&answer.played)),
+                    ("expected", name(&answer.target)),
+                ],
+            ),
+            None => i18n::tr(env, "trainer-start"),
+        }
+    })
+    .with_text_size(24.0);
+    let score = Label::new(|data: &trainer::TrainerState, env: &Env| {
+        i18n::tr_args(
+            env,
+            "trainer-score",
+            &[
+                ("correct", data.correct().to_string()),
+                ("total", data.answers.len().to_string()),
+            ],
+        )
+    });
+    Flex::column()
+        .with_flex_child(staff, 1.0)
+        .with_child(feedback)
+        .with_child(score)
+}
+fn build_note_controls() -> impl Widget<AppState> {
+    let spellings: Vec<(LabelText<_>, staff::Spelling)> = staff::Spelling::ALL
+        .iter()
+        .map(|spelling| (i18n::label(spelling.id()), *spelling))
+        .collect();
+    let references: Vec<(LabelText<_>, staff::Reference)> = staff::Reference::ALL
+        .iter()
+        .map(|reference| (i18n::label(reference.id()), *reference))
+        .collect();
+    let degrees: Vec<(LabelText<_>, staff::Degrees)> = staff::Degrees::ALL
+        .iter()
+        .map(|degrees| (i18n::label(degrees.id()), *degrees))
+        .collect();
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(i18n::label("note-clef")))
+        .with_child(
+            RadioGroup::new(vec![
+                (i18n::label("clef-treble"), staff::ClefType::Treble),
+                (i18n::label("clef-bass"), staff::ClefType::Bass),
+                (i18n::label("clef-auto"), staff::ClefType::Auto),
+            ])
+            .lens(AppState::clef),
+        )
+        .with_child(Label::new(i18n::label("note-spelling")))
+        .with_child(RadioGroup::new(spellings).lens(AppState::spelling))
+        .with_child(Label::new(i18n::label("note-reference")))
+        .with_child(RadioGroup::new(references).lens(AppState::reference))
+        .with_child(Label::new(i18n::label("note-degrees")))
+        .with_child(RadioGroup::new(degrees).lens(AppState::degrees))
+}
+fn build_scale_controls() -> impl Widget<AppState> {
+    let kinds: Vec<(LabelText<_>, note::ScaleType)> = note::ScaleType::ALL
+        .iter()
+        .map(|kind| (i18n::label(kind.id()), *kind))
+        .collect();
+    let degrees: Vec<(LabelText<_>, staff::Degrees)> = staff::Degrees::ALL
+        .iter()
+        .map(|degrees| (i18n::label(degrees.id()), *degrees))
+        .collect();
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(|data: &AppState, env: &Env| {
+            let tonic = naming::Naming::from_env(env).tonic_name(&data.scale);
+            i18n::tr_args(env, "scale-tonic", &[("tonic", tonic)])
+        }))
+        .with_child(
+            Stepper::new()
+                .with_range(-7.0, 10.0)
+                .with_step(1.0)
+                .lens(AppState::scale.map(
+                    |scale: &note::Scale| scale.fifths() as f64,
+                    |scale: &mut note::Scale, fifths: f64| {
+                        *scale = note::Scale::new(fifths as i8, scale.kind())
+                    },
+                )),
+        )
+        .with_child(RadioGroup::new(kinds).lens(AppState::scale.map(
+            |scale: &note::Scale| scale.kind(),
+            |scale: &mut note::Scale, kind: note::ScaleType| {
+                *scale = note::Scale::new(scale.fifths(), kind)
+            },
+        )))
+        .with_spacer(10.0)
+        .with_child(Label::new(i18n::label("note-clef")))
+        .with_child(
+            RadioGroup::new(vec![
+                (i18n::label("clef-treble"), staff::ClefType::Treble),
+                (i18n::label("clef-bass"), staff::ClefType::Bass),
+            ])
+            .lens(AppState::clef),
+        )
+        .with_child(Label::new(i18n::label("note-degrees")))
+        .with_child(RadioGroup::new(degrees).lens(AppState::degrees))
+}
+// The scale on the clef of the note view, the degree played lit up.
+fn build_scale() -> impl Widget<AppState> {
+    ViewSwitcher::new(
+        |data: &AppState, _env| (data.scale.clone(), data.clef, data.degrees),
+        |(scale, clef, degrees), _data, _env| {
+            Box::new(
+                build_staff(
+                    staff::Staff::new()
+                        .with_clef(*clef)
+                        .with_scale(scale.clone())
+                        .with_degrees(*degrees),
+                )
+                .padding(20.0)
+                .background(settings::BACKGROUND)
+                .lens(AppState::midi.then(midi::MidiData::note)),
+            )
+        },
+    )
+}
+fn build_transposition_controls() -> impl Widget<transpose::TranspositionState> {
+    let instruments: Vec<(LabelText<_>, transpose::Instrument)> = transpose::Instrument::ALL
+        .iter()
+        .map(|instrument| (i18n::label(instrument.id()), *instrument))
+        .collect();
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(RadioGroup::new(instruments).lens(transpose::TranspositionState::instrument))
+        .with_spacer(10.0)
+        .with_child(
+            Checkbox::new(i18n::label("transpose-from-written"))
+                .lens(transpose::TranspositionState::from_written),
+        )
+        .with_child(Label::new(
+            |data: &transpose::TranspositionState, env: &Env| {
+                let key = naming::Naming::from_env(env).key_name(&data.key);
+                i18n::tr_args(env, "transpose-input-key", &[("key", key)])
+            },
+        ))
+        .with_child(Stepper::new().with_range(-7.0, 7.0).with_step(1.0).lens(
+            transpose::TranspositionState::key.map(
+                |key: &note::Key| key.fifths() as f64,
+                |key: &mut note::Key, fifths: f64| *key = note::Key::new(fifths as i8),
+            ),
+        ))
+        .with_child(
+            Checkbox::new(i18n::label("transpose-second-staff"))
+                .lens(transpose::TranspositionState::second_staff),
+        )
+}
+// Main staff shows the input transposed for the chosen instrument,
+// the optional small staff shows the input itself.
+fn build_note() -> impl Widget<AppState> {
+    ViewSwitcher::new(
+        |data: &AppState, _env| {
+            (
+                data.transposition.clone(),
+                data.clef,
+                data.spelling,
+                data.caption.clone(),
+                (data.reference, data.degrees, data.scale.clone()),
+            )
+        },
+        |(state, clef, spelling, options, (reference, degrees, scale)), _data, _env| {
+            let (shown_title, input_title) = if state.from_written {
+                ("transpose-concert", "transpose-written")
+            } else {
+                ("transpose-written", "transpose-concert")
+            };
+            let staff = |key: note::Key, scale: note::Scale| {
+                let alteration = spelling.alteration(&key);
+                let reference = reference.scale(&key, &scale);
+                let staff = staff::Staff::chord()
+                    .with_state(&staff::StaffState {
+                        notes: Vec::new(),
+                        clef: *clef,
+                        key,
+                        alteration,
+                    })
+                    .with_degrees(*degrees);
+                build_staff(match reference {
+                    Some(reference) => staff.with_reference(reference),
+                    None => staff,
+                })
+            };
+            let shown =
+                staff(state.shown_key(), state.shown_scale(scale)).lens(lens::Identity.map(
+                    |data: &AppState| std::sync::Arc::new(data.shown_notes()),
+                    |_data: &mut AppState, _notes: std::sync::Arc<Vec<note::Note>>| {},
+                ));
+            let mut column = Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(Label::new(move |data: &AppState, env: &Env| {
+                    let naming = naming::Naming::from_env(env);
+                    let key = naming.key_name(&data.transposition.shown_key());
+                    i18n::tr_args(env, shown_title, &[("key", key)])
+                }))
+                .with_flex_child(shown, 3.0);
+            if options.is_shown() {
+                let label = caption::BigNote.lens(lens::Identity.map(
+                    |data: &AppState| data.shown(),
+                    |_data: &mut AppState, _shown: caption::Shown| {},
+                ));
+                column.add_flex_child(label, 3.0 * options.size);
+            }
+            if state.second_staff && state.instrument != transpose::Instrument::Concert {
+                column.add_child(Label::new(move |data: &AppState, env: &Env| {
+                    let naming = naming::Naming::from_env(env);
+                    let key = naming.key_name(&data.transposition.key);
+                    i18n::tr_args(env, input_title, &[("key", key)])
+                }));
+                column.add_flex_child(
+                    staff(state.key.clone(), scale.clone()).lens(lens::Identity.map(
+                        |data: &AppState| std::sync::Arc::new(data.played()),
+                        |_data: &mut AppState, _notes: std::sync::Arc<Vec<note::Note>>| {},
+                    )),
+                    1.0,
+                );
+            }
+            Box::new(column)
+        },
+    )
+}
+fn build_tuner_controls() -> impl Widget<tuner::TunerState> {
+    let references: Vec<(LabelText<_>, f64)> = tuner::REFERENCES
+        .iter()
+        .map(|&a4| {
+            let label = LabelText::from(move |_data: &f64, env: &Env| {
+                i18n::tr_args(env, "tuner-reference", &[("hz", a4.to_string())])
+            });
+            (label, a4)
+        })
+        .collect();
+    let temperaments: Vec<(LabelText<_>, tuner::Temperament)> = tuner::Temperament::ALL
+        .iter()
+        .map(|temperament| (i18n::label(temperament.id()), *temperament))
+        .collect();
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(RadioGroup::new(references).lens(tuner::TunerState::a4))
+        .with_spacer(10.0)
+        .with_child(RadioGroup::new(temperaments).lens(tuner::TunerState::temperament))
+        .with_child(Label::new(|data: &tuner::TunerState, env: &Env| {
+            let (tonic, alteration) = data.tonic_note();
+            let naming = naming::Naming::from_env(env);
+            let tonic = naming.pitch_class(&tonic, alteration, &note::Key::new(0));
+            i18n::tr_args(env, "tuner-tonic", &[("tonic", tonic)])
+        }))
+        .with_child(Stepper::new().with_range(0.0, 11.0).with_step(1.0).lens(
+            tuner::TunerState::tonic.map(
+                |tonic: &u8| *tonic as f64,
+                |tonic: &mut u8, value: f64| *tonic = value as u8,
+            ),
+        ))
+}
+fn build_tuner() -> impl Widget<tuner::TunerState> {
+    let staff = build_staff(staff::Staff::new()).lens(tuner::TunerState::reading.map(
+        |reading: &Option<tuner::Reading>| reading.as_ref().map(|r| r.note.clone()),
+        |_reading: &mut Option<tuner::Reading>, _note: Option<note::Note>| {},
+    ));
+    let needle = tuner::Needle.lens(tuner::TunerState::reading);
+    let frequency = Label::new(|data: &tuner::TunerState, env: &Env| match &data.reading {
+        Some(reading) => i18n::tr_args(
+            env,
+            "tuner-reading",
+            &[
+                (
+                    "note",
+                    naming::Naming::from_env(env).name(
+                        &reading.note,
+                        note::NoteAlt::Sharp,
+                        &note::Key::new(0),
+                    ),
+                ),
+                ("cents", format!("{:+.1}", reading.cents)),
+                ("hz", format!("{:.2}", reading.frequency)),
+                (
+                    "target",
+                    format!("{:.2}", data.target_frequency(reading.note.midi_nr())),
+                ),
+            ],
         ),
+        None => i18n::tr(env, "tuner-play"),
+    })
+    .with_text_size(24.0);
+    Flex::column()
+        .with_flex_child(
+            Flex::row()
+                .with_flex_child(staff, 1.0)
+                .with_flex_child(needle, 1.0),
+            1.0,
+        )
+        .with_child(frequency)
+}
+fn build_dictation_controls() -> impl Widget<dictation::DictationState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(|data: &dictation::DictationState, env: &Env| {
+            i18n::tr_args(
+                env,
+                "dictation-tempo",
+                &[("bpm", data.tempo.bpm.to_string())],
+            )
+        }))
+        .with_child(
+            Stepper::new()
+                .with_range(20.0, 300.0)
+                .with_step(1.0)
+                .lens(dictation::DictationState::tempo.then(rhythm::Tempo::bpm)),
+        )
+        .with_child(
+            Checkbox::new(i18n::label("dictation-follow-clock"))
+                .lens(dictation::DictationState::follow_clock),
+        )
+        .with_spacer(10.0)
+        .with_child(
+            RadioGroup::new(vec![
+                ("2/4", rhythm::TimeSignature::new(2, 4)),
+                ("3/4", rhythm::TimeSignature::new(3, 4)),
+                ("4/4", rhythm::TimeSignature::new(4, 4)),
+                ("6/8", rhythm::TimeSignature::new(6, 8)),
+            ])
+            .lens(dictation::DictationState::time),
+        )
+        .with_spacer(10.0)
+        .with_child(
+            Button::new(i18n::label("dictation-clear"))
+                .on_click(|_ctx, data: &mut dictation::DictationState, _env| data.clear()),
+        )
+}
+fn build_profiles(available: &[String]) -> impl Widget<profile::ProfileState> {
+    let mut profiles = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(i18n::label("profiles-choose")).with_text_size(24.0))
+        .with_spacer(10.0);
+    for name in available {
+        let name = name.clone();
+        profiles.add_child(Button::new(name.clone()).on_click(
+            move |ctx, _data: &mut profile::ProfileState, _env| {
+                ctx.submit_command(profile::SELECT.with(name.clone()))
+            },
+        ));
+    }
+    profiles.add_spacer(10.0);
+    profiles.add_child(
+        Flex::row()
+            .with_child(TextBox::new().lens(profile::ProfileState::new_name))
+            .with_child(Button::new(i18n::label("profiles-create")).on_click(
+                |ctx, data: &mut profile::ProfileState, _env| {
+                    if !profile::sanitize(&data.new_name).is_empty() {
+                        ctx.submit_command(profile::SELECT.with(data.new_name.clone()))
+                    }
+                },
+            )),
+    );
+    profiles.padding(20.0)
+}
+fn build_statistics() -> impl Widget<profile::ProfileState> {
+    fn lines(
+        env: &Env,
+        title: &str,
+        tallies: impl IntoIterator<Item = (String, stats::Tally)>,
+    ) -> String {
+        let mut text = format!("{}:\n", i18n::tr(env, title));
+        for (value, tally) in tallies {
+            let line = i18n::tr_args(
+                env,
+                "stats-line",
+                &[
+                    ("value", value),
+                    ("accuracy", format!("{:.0}", tally.accuracy() * 100.0)),
+                    ("answers", tally.answers.to_string()),
+                    (
+                        "seconds",
+                        format!("{:.2}", tally.mean_reaction() as f64 / 1000.0),
+                    ),
+                ],
+            );
+            text.push_str(&format!("  {}\n", line));
+        }
+        text
+    }
+    let breakdown = Label::new(|data: &profile::ProfileState, env: &Env| {
+        let stats = &data.stats;
+        let naming = naming::Naming::from_env(env);
+        // Registers are named after their C.
+        let registers = stats.by_register().into_iter().map(|(register, tally)| {
+            let c = note::Note::new(((register + 2) * 12).clamp(0, 127) as u8);
+            (
+                naming.name(&c, note::NoteAlt::Sharp, &note::Key::new(0)),
+                tally,
+            )
+        });
+        // Clefs and accidentals are stored in English, see stats::Record.
+        let clefs = stats.by_clef().into_iter().map(|(clef, tally)| {
+            let id = format!("clef-{}", clef.to_lowercase());
+            (i18n::tr(env, &id), tally)
+        });
+        let accidentals = stats
+            .by_accidental()
+            .into_iter()
+            .map(|(accidental, tally)| {
+                (i18n::tr(env, &format!("accidental-{}", accidental)), tally)
+            });
+        format!(
+            "{}{}{}",
+            lines(env, "stats-clef", clefs),
+            lines(env, "stats-accidental", accidentals),
+            lines(env, "stats-register", registers)
+        )
+    });
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(|data: &profile::ProfileState, env: &Env| {
+            i18n::tr_args(env, "stats-profile", &[("name", data.name.clone())])
+        }))
+        .with_spacer(10.0)
+        .with_child(stats::HeatMap::new(21, 108).lens(profile::ProfileState::stats))
+        .with_spacer(10.0)
+        .with_child(breakdown)
+        .with_spacer(10.0)
+        .with_child(Button::new(i18n::label("stats-export")).on_click(
+            |_ctx, data: &mut profile::ProfileState, env| {
+                data.message = match data.export(&naming::Naming::from_env(env)) {
+                    Ok(paths) => {
+                        let paths: Vec<String> =
+                            paths.iter().map(|p| p.display().to_string()).collect();
+                        i18n::tr_args(env, "stats-saved", &[("paths", paths.join(", "))])
+                    }
+                    Err(err) => {
+                        error!("{}", err);
+                        i18n::tr_args(env, "stats-export-failed", &[("error", err.to_string())])
+                    }
+                }
+            },
+        ))
+        .with_child(Label::new(|data: &profile::ProfileState, _env: &Env| {
+            data.message.clone()
+        }))
+        .padding(20.0)
+}
+fn build_settings() -> impl Widget<AppState> {
+    let languages: Vec<(LabelText<_>, i18n::Language)> = i18n::Language::ALL
+        .iter()
+        .map(|language| (i18n::label(language.id()), *language))
+        .collect();
+    let systems: Vec<(LabelText<_>, naming::System)> = naming::System::ALL
+        .iter()
+        .map(|system| (i18n::label(system.id()), *system))
+        .collect();
+    let octaves: Vec<(LabelText<_>, naming::Octaves)> = naming::Octaves::ALL
+        .iter()
+        .map(|octaves| (i18n::label(octaves.id()), *octaves))
+        .collect();
+    let themes: Vec<(LabelText<_>, settings::Theme)> = settings::Theme::ALL
+        .iter()
+        .map(|theme| (i18n::label(theme.id()), *theme))
+        .collect();
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(i18n::label("settings-language")).with_text_size(24.0))
+        .with_child(RadioGroup::new(languages).lens(AppState::language))
+        .with_spacer(10.0)
+        .with_child(Label::new(i18n::label("settings-names")).with_text_size(24.0))
+        .with_child(RadioGroup::new(systems).lens(AppState::naming.then(naming::Naming::system)))
+        .with_spacer(10.0)
+        .with_child(Label::new(i18n::label("settings-octaves")).with_text_size(24.0))
+        .with_child(RadioGroup::new(octaves).lens(AppState::naming.then(naming::Naming::octaves)))
+        .with_spacer(10.0)
+        .with_child(Label::new(|_data: &AppState, env: &Env| {
+            let naming = naming::Naming::from_env(env);
+            let key = note::Key::new(0);
+            let names: Vec<String> = [48, 60, 61, 70, 71, 72]
+                .iter()
+                .map(|nr| naming.name(&note::Note::new(*nr), note::NoteAlt::Sharp, &key))
+                .collect();
+            i18n::tr_args(env, "settings-example", &[("names", names.join(" "))])
+        }))
+        .with_spacer(10.0)
+        .with_child(Label::new(i18n::label("settings-theme")).with_text_size(24.0))
+        .with_child(RadioGroup::new(themes).lens(AppState::theme))
+        .with_spacer(10.0)
+        .with_child(Label::new(i18n::label("settings-caption")).with_text_size(24.0))
+        .with_child(
+            Checkbox::new(i18n::label("caption-show-name"))
+                .lens(AppState::caption.then(settings::Caption::name)),
+        )
+        .with_child(
+            Checkbox::new(i18n::label("caption-show-frequency"))
+                .lens(AppState::caption.then(settings::Caption::frequency)),
+        )
+        .with_child(
+            Checkbox::new(i18n::label("caption-show-midi"))
+                .lens(AppState::caption.then(settings::Caption::midi_number)),
+        )
+        .with_child(Label::new(i18n::label("caption-font")))
+        .with_child(TextBox::new().lens(AppState::caption.then(settings::Caption::font)))
+        .with_child(Label::new(|data: &AppState, env: &Env| {
+            let percent = format!("{:.0}", data.caption.size * 100.0);
+            i18n::tr_args(env, "caption-size", &[("percent", percent)])
+        }))
+        .with_child(
+            Stepper::new()
+                .with_range(0.1, 1.0)
+                .with_step(0.1)
+                .lens(AppState::caption.then(settings::Caption::size)),
+        )
+        .with_spacer(10.0)
+        .with_child(Label::new(i18n::label("settings-ottava")).with_text_size(24.0))
+        .with_child(
+            Checkbox::new(i18n::label("ottava-enabled"))
+                .lens(AppState::ottava.then(settings::OttavaLimits::enabled)),
+        )
+        .with_child(Label::new(|data: &AppState, env: &Env| {
+            let count = data.ottava.above.to_string();
+            i18n::tr_args(env, "ottava-above", &[("count", count)])
+        }))
+        .with_child(Stepper::new().with_range(0.0, 8.0).with_step(1.0).lens(
+            AppState::ottava.then(settings::OttavaLimits::above).map(
+                |above: &u8| *above as f64,
+                |above: &mut u8, value: f64| *above = value as u8,
+            ),
+        ))
+        .with_child(Label::new(|data: &AppState, env: &Env| {
+            let count = data.ottava.below.to_string();
+            i18n::tr_args(env, "ottava-below", &[("count", count)])
+        }))
+        .with_child(Stepper::new().with_range(0.0, 8.0).with_step(1.0).lens(
+            AppState::ottava.then(settings::OttavaLimits::below).map(
+                |below: &u8| *below as f64,
+                |below: &mut u8, value: f64| *below = value as u8,
+            ),
+        ))
+        .padding(20.0)
+}
+fn build_ui(ports: Vec<midi::Port>) -> impl Widget<AppState> {
+    let mut controls = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            RadioGroup::new(vec![
+                (i18n::label("mode-profiles"), Mode::Profiles),
+                (i18n::label("mode-note"), Mode::Note),
+                (i18n::label("mode-scale"), Mode::Scale),
+                (i18n::label("mode-dictation"), Mode::Dictation),
+                (i18n::label("mode-trainer"), Mode::Trainer),
+                (i18n::label("mode-tuner"), Mode::Tuner),
+                (i18n::label("mode-statistics"), Mode::Statistics),
+                (i18n::label("mode-settings"), Mode::Settings),
+            ])
+            .lens(AppState::mode),
+        )
+        .with_spacer(10.0)
+        .with_child(Label::new(i18n::label("midi-input")));
+    for port in ports {
+        let label = port.name.to_string();
+        controls.add_child(
+            Button::new(label).on_click(move |ctx, _data: &mut AppState, _env| {
+                ctx.submit_command(midi::SELECT_PORT.with(port.clone()))
+            }),
+        );
+    }
+    controls.add_child(Label::new(|data: &AppState, env: &Env| {
+        if data.midi.message.is_empty() {
+            String::new()
+        } else {
+            i18n::tr_args(env, "midi-failed", &[("error", data.midi.message.clone())])
+        }
+    }));
+    controls.add_spacer(10.0);
+    controls.add_child(Label::new(i18n::label("audio-input")));
+    controls.add_child(
+        Button::dynamic(|data: &AppState, env| {
+            i18n::tr(
+                env,
+                if data.audio.running {
+                    "audio-stop"
+                } else {
+                    "audio-start"
+                },
+            )
+        })
+        .on_click(|ctx, _data: &mut AppState, _env| ctx.submit_command(audio::TOGGLE)),
+    );
+    controls.add_child(Label::new(|data: &AppState, env: &Env| {
+        match &data.audio.detected {
+            Some(pitch) => i18n::tr_args(
+                env,
+                "audio-detected",
+                &[
+                    (
+                        "note",
+                        naming::Naming::from_env(env).name(
+                            &pitch.note,
+                            note::NoteAlt::Sharp,
+                            &note::Key::new(0),
+                        ),
+                    ),
+                    ("cents", format!("{:+.0}", pitch.cents)),
+                    ("hz", format!("{:.1}", pitch.frequency)),
+                ],
+            ),
+            None if data.audio.message.is_empty() => String::new(),
+            None => i18n::tr_args(
+                env,
+                "audio-failed",
+                &[("error", data.audio.message.clone())],
+            ),
+        }
+    }));
+    controls.add_spacer(10.0);
+    controls.add_child(ViewSwitcher::new(
+        |data: &AppState, _env| data.mode,
+        |mode, _data, _env| match mode {
+            Mode::Dictation => Box::new(build_dictation_controls().lens(AppState::dictation)),
+            Mode::Trainer => Box::new(build_trainer_controls().lens(AppState::trainer)),
+            Mode::Tuner => Box::new(build_tuner_controls().lens(AppState::tuner)),
+            Mode::Note => Box::new(
+                Flex::column()
+                    .cross_axis_alignment(CrossAxisAlignment::Start)
+                    .with_child(build_note_controls())
+                    .with_spacer(10.0)
+                    .with_child(build_transposition_controls().lens(AppState::transposition)),
+            ),
+            Mode::Scale => Box::new(build_scale_controls()),
+            Mode::Profiles | Mode::Statistics | Mode::Settings => Box::new(SizedBox::empty()),
+        },
+    ));
+    let view = ViewSwitcher::new(
+        |data: &AppState, _env| data.mode,
+        |mode, data, _env| match mode {
+            Mode::Note => Box::new(build_note()),
+            Mode::Scale => Box::new(build_scale()),
+            Mode::Trainer => Box::new(build_trainer().lens(AppState::trainer)),
+            Mode::Tuner => Box::new(build_tuner().lens(AppState::tuner)),
+            Mode::Profiles => {
+                Box::new(build_profiles(&data.profile.available).lens(AppState::profile))
+            }
+            Mode::Statistics => Box::new(build_statistics().lens(AppState::profile)),
+            Mode::Settings => Box::new(build_settings()),
+            Mode::Dictation => Box::new(
+                dictation::Dictation::new()
+                    .padding(20.0)
+                    .background(settings::BACKGROUND)
+                    .lens(AppState::dictation),
+            ),
+        },
+    );
+    let main = Flex::column()
+        .with_flex_child(view, 1.0)
+        .with_child(keyboard::Keyboard::new(21, 108).lens(AppState::midi));
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(controls.padding(10.0))
+        .with_flex_child(main, 1.0)
+        .env_scope(|env, data: &AppState| {
+            env.set(naming::NAMING, std::sync::Arc::new(data.naming));
+            env.set(i18n::LANGUAGE, data.language.code());
+            let colors = data.theme.colors();
+            colors.as_ref().unwrap_or(&data.custom_colors).set_env(env);
+            data.ottava.set_env(env);
+        })
+}
+// Staff of a session file, none for a new session.
+fn load_session(path: &std::path::Path) -> Option<staff::StaffState> {
+    if !path.exists() {
+        return None;
+    }
+    staff::StaffState::load(path)
+        .map_err(|err| error!("{}", err))
+        .ok()
+}
+// Clef, key and spelling of a saved staff, taken like command line
+// overrides of the settings.
+fn apply_session(settings: &mut settings::Settings, state: &staff::StaffState) {
+    settings.clef = state.clef;
+    settings.transposition.key = state.key.fifths();
+    settings.spelling = if state.alteration == state.key.alteration() {
+        staff::Spelling::Key
+    } else if state.alteration == note::NoteAlt::Sharp {
+        staff::Spelling::Sharps
+    } else {
+        staff::Spelling::Flats
+    };
+}
+// Prints the detected notes of a WAV file, one line per change.
+fn detect_wav(path: &std::path::Path, naming: &naming::Naming) {
+    let detected = match pitch::detect_wav(path, pitch::A4) {
+        Ok(detected) => detected,
+        Err(err) => {
+            eprintln!("{}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    };
+    let mut last = None;
+    for (time, pitch) in detected {
+        let nr = pitch.as_ref().map(|pitch| pitch.note.midi_nr());
+        if nr == last {
+            continue;
+        }
+        last = nr;
+        match pitch {
+            Some(pitch) => println!(
+                "{:.3}\t{}\t{:+.1}\t{:.2}",
+                time,
+                naming.name(&pitch.note, note::NoteAlt::Sharp, &note::Key::new(0)),
+                pitch.cents,
+                pitch.frequency
+            ),
+            None => println!("{:.3}\t-", time),
+        }
+    }
+}
+fn render_staff(render: &cli::Render, settings: &settings::Settings) {
+    let key = note::Key::new(settings.transposition.key);
+    let size = Size::new(render.width as f64, render.height as f64);
+    let layout = layout::StaffLayout::new(
+        size,
+        settings.clef,
+        &key,
+        settings.spelling.alteration(&key),
+        &render.notes,
+        &settings.ottava,
+    );
+    let result = render::write(&render.output, size, &layout, &settings.theme_colors());
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+fn write_worksheet(options: &cli::Worksheet, settings: &settings::Settings) {
+    let key = note::Key::new(settings.transposition.key);
+    let low = options
+        .low
+        .clone()
+        .map_or(settings.trainer.low, |note| note.midi_nr());
+    let high = options
+        .high
+        .clone()
+        .map_or(settings.trainer.high, |note| note.midi_nr());
+    let accidentals = options.accidentals || settings.trainer.accidentals;
+    let pool = trainer::Pool::new(settings.clef, low, high, accidentals, key.clone());
+    let seed = options.seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default()
+    });
+    let result = worksheet::items(&pool, options.count, options.chord, seed).and_then(|items| {
+        let sheet = worksheet::Worksheet {
+            clef: settings.clef,
+            alteration: settings.spelling.alteration(&key),
+            key,
+            naming: naming::Naming::new(settings.naming, settings.octaves),
+            language: settings.language,
+            columns: options.columns,
+            ottava: settings.ottava.clone(),
+            seed,
+            items,
+        };
+        worksheet::write(&options.output, &sheet)
+    });
+    match result {
+        Ok(written) => {
+            for path in written {
+                println!("{}", path.display());
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
     }
 }
 fn main() {
-    let data = None;
-    let window = WindowDesc::new(build_ui())
-    // .window_size(Size::new(100.0, 100.0)) 
-    ;
-    AppLauncher::with_window(window).launch(data);
+    // Broken settings and other problems found before the window opens
+    // are logged to stderr. RUST_LOG picks another level.
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+    let options = cli::Options::from_args();
+    let mut settings = match settings::Settings::path() {
+        Some(path) => settings::Settings::load(&path),
+        None => settings::Settings::default(),
+    };
+    let saved = settings.clone();
+    let session = options.state.as_deref().and_then(load_session);
+    if let Some(state) = &session {
+        apply_session(&mut settings, state);
+    }
+    options.apply(&mut settings);
+    if let Some(path) = &options.detect_wav {
+        detect_wav(
+            path,
+            &naming::Naming::new(settings.naming, settings.octaves),
+        );
+        return;
+    }
+    smufl::install(smufl::load(&settings.font));
+    // Rendering needs neither MIDI nor a display.
+    match &options.command {
+        Some(cli::Command::Render(render)) => return render_staff(render, &settings),
+        Some(cli::Command::Worksheet(worksheet)) => return write_worksheet(worksheet, &settings),
+        None => {}
+    }
+    let mut midi = midi::MidiHandler::new(String::from("RustedBigNote"));
+    let ports = midi.port_names();
+    if options.list_ports {
+        for port in ports {
+            println!("{}\t{}", port.index, port.name);
+        }
+        return;
+    }
+    let mut data = AppState {
+        mode: Mode::Profiles,
+        midi: midi::MidiData {
+            note: None,
+            held: false,
+            keys: Vec::new(),
+            ports_list: ports.clone(),
+            selected: String::new(),
+            message: String::new(),
+        },
+        dictation: dictation::DictationState::new(),
+        trainer: trainer::TrainerState::new(),
+        profile: profile::ProfileState::new(),
+        audio: audio::AudioState::new(),
+        tuner: tuner::TunerState::new(),
+        transposition: transpose::TranspositionState::new(),
+        naming: naming::Naming::default(),
+        language: i18n::Language::System,
+        colors: settings::Colors::default(),
+        custom_colors: settings::Colors::default(),
+        window: settings::Window::default(),
+        clef: staff::ClefType::Treble,
+        spelling: staff::Spelling::Key,
+        reference: staff::Reference::Off,
+        degrees: staff::Degrees::Off,
+        scale: note::Scale::new(0, note::ScaleType::Major),
+        font: String::new(),
+        theme: settings::Theme::Light,
+        theme_file: String::new(),
+        caption: settings::Caption::default(),
+        ottava: settings::OttavaLimits::default(),
+    };
+    data.apply(&settings);
+    // Launchers with display options go straight to the staff.
+    if options.is_display() {
+        data.mode = Mode::Note;
+        // A chord of the session comes back as its top note, held keys
+        // only come from the keyboard.
+        data.midi.note = options.note.clone().or_else(|| {
+            session
+                .as_ref()
+                .and_then(|state| state.notes.last().cloned())
+        });
+    }
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default();
+    let saved_port = match &options.port {
+        Some(pattern) => cli::find_port(&ports, pattern),
+        None => ports
+            .iter()
+            .find(|port| *port.name == settings.midi_port)
+            .cloned(),
+    };
+    // A launcher with a mistyped port stops with the inputs there are.
+    if let (Some(pattern), None) = (&options.port, &saved_port) {
+        eprintln!("No MIDI input matches {}.", pattern);
+        if ports.is_empty() {
+            eprintln!("There are no MIDI inputs.");
+        } else {
+            eprintln!("The MIDI inputs are:");
+            for port in ports.iter() {
+                eprintln!("{}\t{}", port.index, port.name);
+            }
+        }
+        std::process::exit(1);
+    }
+    if let Some(port) = &saved_port {
+        data.midi.selected = port.name.to_string();
+    }
+    let mut window =
+        WindowDesc::new(build_ui(ports).controller(Persist::new(saved, options.state.clone())))
+            .title(i18n::label("window-title"))
+            .window_size(Size::new(settings.window.width, settings.window.height));
+    if let (Some(x), Some(y)) = (settings.window.x, settings.window.y) {
+        window = window.set_position(Point::new(x, y));
+    }
+    if options.fullscreen {
+        window = window
+            .set_window_state(WindowState::Maximized)
+            .show_titlebar(false);
+    }
+    let launcher = AppLauncher::with_window(window);
+    let sink = launcher.get_external_handle();
+    // A port that can't be opened leaves the app without MIDI, unless
+    // the command line asked for it.
+    if let Some(port) = saved_port {
+        midi.selected_port = Some(port);
+        if let Err(err) = midi.connect(sink.clone()) {
+            eprintln!("{}", err);
+            if options.port.is_some() {
+                std::process::exit(1);
+            }
+            data.midi.message = err;
+        }
+    }
+    launcher
+        .configure_env(|env, _data| {
+            env.set(
+                naming::NAMING,
+                std::sync::Arc::new(naming::Naming::default()),
+            );
+            env.set(i18n::LANGUAGE, i18n::Language::System.code());
+            settings::Colors::default().set_env(env);
+            settings::OttavaLimits::default().set_env(env);
+        })
+        .delegate(Delegate {
+            midi,
+            sink,
+            audio: audio::AudioInput::new(),
+            onset: pitch::Onset::new(),
+            trainer: trainer::Trainer::new(seed),
+            schedule: None,
+        })
+        .launch(data);
 }
@@ -1,10 +1,97 @@
 use super::note::Note;
-use druid::Data;
+use druid::{Data, ExtEventSink, Lens, Selector, Target};
 use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort, MidiInputPorts};
-use std::sync::mpsc::{channel, Receiver, Sender};
+use tracing::{debug, error};
 
+/// Command carrying every message read from the connected port.
+pub const MIDI_EVENT: Selector<MidiEvent> = Selector::new("rusted_big_note.midi-event");
+/// Command asking to connect to another port.
+pub const SELECT_PORT: Selector<Port> = Selector::new("rusted_big_note.select-port");
+
+#[test]
+fn test_parse_message() {
+    assert_eq!(
+        MidiMessage::parse(&[0x90, 60, 100]),
+        MidiMessage::NoteOn(60, 100)
+    );
+    assert_eq!(MidiMessage::parse(&[0x93, 60, 0]), MidiMessage::NoteOff(60));
+    assert_eq!(
+        MidiMessage::parse(&[0x80, 61, 64]),
+        MidiMessage::NoteOff(61)
+    );
+    assert_eq!(MidiMessage::parse(&[0xF8]), MidiMessage::Clock);
+    assert_eq!(MidiMessage::parse(&[0xFA]), MidiMessage::Start);
+    assert_eq!(
+        MidiMessage::parse(&[0xE0, 0x00, 0x40]),
+        MidiMessage::PitchBend(0)
+    );
+    assert_eq!(
+        MidiMessage::parse(&[0xE1, 0x7F, 0x7F]),
+        MidiMessage::PitchBend(8191)
+    );
+    assert_eq!(
+        MidiMessage::parse(&[0xE0, 0x00, 0x00]),
+        MidiMessage::PitchBend(-8192)
+    );
+    assert_eq!(MidiMessage::parse(&[0xB0, 64, 127]), MidiMessage::Other);
+    assert_eq!(MidiMessage::parse(&[]), MidiMessage::Other);
+}
+
+#[test]
+fn test_held_keys() {
+    let mut data = MidiData::new();
+    data.press(64);
+    data.press(60);
+    data.press(64);
+    assert_eq!(data.keys, vec![60, 64]);
+    data.release(60);
+    data.release(72);
+    assert_eq!(data.keys, vec![64]);
+}
+
+/// Channel messages the app reacts to, the rest are `Other`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum MidiMessage {
+    /// Key and velocity.
+    NoteOn(u8, u8),
+    NoteOff(u8),
+    /// Offset from the centre, -8192..8191.
+    PitchBend(i16),
+    Clock,
+    Start,
+    Stop,
+    Other,
+}
+impl MidiMessage {
+    /// Reads a raw message, NoteOn with velocity 0 is a NoteOff.
+    pub fn parse(message: &[u8]) -> Self {
+        match message {
+            [status, key, velocity] if status & 0xF0 == 0x90 => match velocity {
+                0 => MidiMessage::NoteOff(*key),
+                _ => MidiMessage::NoteOn(*key, *velocity),
+            },
+            [status, key, _] if status & 0xF0 == 0x80 => MidiMessage::NoteOff(*key),
+            [status, lsb, msb] if status & 0xF0 == 0xE0 => {
+                MidiMessage::PitchBend(((*msb as i16) << 7 | *lsb as i16) - 8192)
+            }
+            [0xF8] => MidiMessage::Clock,
+            [0xFA] | [0xFB] => MidiMessage::Start,
+            [0xFC] => MidiMessage::Stop,
+            _ => MidiMessage::Other,
+        }
+    }
+}
+
+/// Message with its stamp in microseconds, as given by midir.
+#[derive(Debug, Clone)]
+pub struct MidiEvent {
+    pub stamp: u64,
+    pub message: MidiMessage,
+}
+
+/// Input port, by its index in the list midir gives.
 #[derive(Debug, PartialEq, Eq, Clone)]
-struct Port {
+pub struct Port {
     pub index: usize,
     pub name: Box<String>,
 }
@@ -23,22 +110,52 @@ impl std::fmt::Display for Port {
     }
 }
 
-#[derive(Clone)]
-struct MidiData {
-    note: Option<Note>,
-    ports_list: Vec<Port>,
+/// Keyboard state shown by the app.
+#[derive(Clone, Lens)]
+pub struct MidiData {
+    /// Last note played.
+    pub note: Option<Note>,
+    /// The note stays shown after its key is let go.
+    pub held: bool,
+    /// Keys held down, lowest first, named as an interval or chord.
+    pub keys: Vec<u8>,
+    pub ports_list: Vec<Port>,
+    /// Name of the connected port, kept in the settings.
+    pub selected: String,
+    /// Why the last connection failed, empty when it worked.
+    pub message: String,
 }
 impl MidiData {
-    fn new() -> Self {
+    pub fn new() -> Self {
         MidiData {
             note: None,
+            held: false,
+            keys: Vec::new(),
             ports_list: Vec::new(),
+            selected: String::new(),
+            message: String::new(),
         }
     }
+    /// Adds a key to the held ones.
+    pub fn press(&mut self, key: u8) {
+        if let Err(index) = self.keys.binary_search(&key) {
+            self.keys.insert(index, key);
+        }
+    }
+    /// Lets a key go.
+    pub fn release(&mut self, key: u8) {
+        self.keys.retain(|held| *held != key);
+    }
 }
 impl Data for MidiData {
     fn same(&self, other: &Self) -> bool {
-        if self.note == other.note && self.ports_list == other.ports_list {
+        if self.note == other.note
+            && self.held == other.held
+            && self.keys == other.keys
+            && self.ports_list == other.ports_list
+            && self.selected == other.selected
+            && self.message == other.message
+        {
             return true;
         } else {
             return false;
@@ -46,25 +163,27 @@ impl Data for MidiData {
     }
 }
 
-struct MidiHandler {
+/// Connection to a MIDI input, forwarding its messages as
+/// [`MIDI_EVENT`] commands.
+pub struct MidiHandler {
     client_name: String,
-    selected_port: Option<Port>,
+    pub selected_port: Option<Port>,
     midi_in: MidiInput,
 
-    connection: Option<MidiInputConnection<Sender<Vec<u8>>>>,
-    reciever: Option<Receiver<Vec<u8>>>,
+    connection: Option<MidiInputConnection<ExtEventSink>>,
 }
 impl MidiHandler {
-    fn new(name: String) -> Self {
+    /// Client named `name`, not connected yet.
+    pub fn new(name: String) -> Self {
         MidiHandler {
             client_name: name.clone(),
             selected_port: None,
             midi_in: MidiInput::new(&name).unwrap(),
             connection: None,
-            reciever: None,
         }
     }
-    fn port_names(&self) -> Vec<Port> {
+    /// Input ports present now.
+    pub fn port_names(&self) -> Vec<Port> {
         let mut names: Vec<Port> = Vec::new();
         let midi_in = &self.midi_in;
         let ports_amount = midi_in.port_count();
@@ -80,28 +199,47 @@ impl MidiHandler {
         }
         names.into()
     }
-    fn connect(&mut self) {
-        println!("init:");
-        let ports = &self.midi_in.ports();
-        let selected_port = self.selected_port.as_ref().unwrap();
-        let port = ports.get(selected_port.index).unwrap();
-        println!("in port name: {}", &self.midi_in.port_name(&port).unwrap());
-        let (sender, reciever) = channel();
-        self.reciever = Some(reciever);
-        let midi_in = MidiInput::new(&self.client_name).unwrap();
-        self.connection = Some(
-            midi_in
-                .connect(
-                    &port,
-                    "name",
-                    |_stamp, message, sender| {
-                        let mut packet = Vec::new();
-                        packet.extend_from_slice(message);
-                        sender.send(packet).unwrap();
-                    },
-                    sender,
-                )
-                .unwrap(),
-        );
+    /// Connects to `selected_port`, closing the previous connection.
+    /// Fails when the port was unplugged since it was listed or can't
+    /// be opened, for example when another app holds it.
+    pub fn connect(&mut self, sink: ExtEventSink) -> Result<(), String> {
+        // Dropping the old connection closes it.
+        self.connection = None;
+        let selected_port = self
+            .selected_port
+            .as_ref()
+            .ok_or_else(|| String::from("No MIDI input selected"))?;
+        // The index may belong to another device after a replug.
+        let ports = self.midi_in.ports();
+        let port = ports
+            .get(selected_port.index)
+            .filter(|port| {
+                self.midi_in.port_name(port).ok().as_deref() == Some(selected_port.name.as_str())
+            })
+            .ok_or_else(|| format!("MIDI input {} is gone", selected_port.name))?;
+        debug!("Connecting to MIDI input {}", selected_port.name);
+        let mut midi_in = MidiInput::new(&self.client_name).map_err(|err| err.to_string())?;
+        midi_in.ignore(Ignore::SysexAndActiveSense);
+        let connection = midi_in
+            .connect(
+                port,
+                "name",
+                |stamp, message, sink| {
+                    let event = MidiEvent {
+                        stamp,
+                        message: MidiMessage::parse(message),
+                    };
+                    if event.message == MidiMessage::Other {
+                        return;
+                    }
+                    if let Err(err) = sink.submit_command(MIDI_EVENT, event, Target::Auto) {
+                        error!("{}", err);
+                    }
+                },
+                sink,
+            )
+            .map_err(|err| format!("{}: {}", selected_port.name, err))?;
+        self.connection = Some(connection);
+        Ok(())
     }
 }
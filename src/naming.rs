@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use druid::{Data, Env, Key as EnvKey, Lens};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use super::note::ScaleType;
+use super::note::{Key, Note, NoteAlt, Scale};
+
+// Current naming settings.
+pub const NAMING: EnvKey<Arc<Naming>> = EnvKey::new("rusted_big_note.naming");
+
+const ENGLISH: [&str; 7] = ["C", "D", "E", "F", "G", "A", "B"];
+const GERMAN: [&str; 7] = ["C", "D", "E", "F", "G", "A", "H"];
+const ITALIAN: [&str; 7] = ["Do", "Re", "Mi", "Fa", "Sol", "La", "Si"];
+const FRENCH: [&str; 7] = ["Do", "Ré", "Mi", "Fa", "Sol", "La", "Si"];
+const RUSSIAN: [&str; 7] = ["До", "Ре", "Ми", "Фа", "Соль", "Ля", "Си"];
+const JAPANESE: [&str; 7] = ["ド", "レ", "ミ", "ファ", "ソ", "ラ", "シ"];
+// Chromatic movable-do syllables from the tonic, raised and lowered.
+const DO_SHARP: [&str; 12] = [
+    "Do", "Di", "Re", "Ri", "Mi", "Fa", "Fi", "Sol", "Si", "La", "Li", "Ti",
+];
+const DO_FLAT: [&str; 12] = [
+    "Do", "Ra", "Re", "Me", "Mi", "Fa", "Se", "Sol", "Le", "La", "Te", "Ti",
+];
+
+#[test]
+fn test_letter_systems() {
+    let c4 = Note::new(60);
+    let fis = Note::new(66);
+    let b = Note::new(70);
+    let c = Key::new(0);
+    let english = Naming::new(System::English, Octaves::MiddleC3);
+    assert_eq!(english.name(&c4, NoteAlt::Sharp, &c), "C3");
+    assert_eq!(english.name(&fis, NoteAlt::Flat, &c), "Gb3");
+    let german = Naming::new(System::German, Octaves::MiddleC4);
+    assert_eq!(german.name(&c4, NoteAlt::Sharp, &c), "C4");
+    assert_eq!(german.name(&fis, NoteAlt::Sharp, &c), "Fis4");
+    assert_eq!(german.name(&b, NoteAlt::Flat, &c), "B4");
+    assert_eq!(german.name(&Note::new(71), NoteAlt::Flat, &c), "H4");
+    assert_eq!(german.name(&Note::new(63), NoteAlt::Flat, &c), "Es4");
+    assert_eq!(german.name(&Note::new(68), NoteAlt::Flat, &c), "As4");
+    assert_eq!(german.key_name(&Key::new(-3)), "Es");
+    assert_eq!(english.key_name(&Key::new(-7)), "Cb");
+    let d_sharp = Scale::new(9, ScaleType::NaturalMinor);
+    assert_eq!(english.tonic_name(&d_sharp), "D#");
+    assert_eq!(german.tonic_name(&d_sharp), "Dis");
+    let movable = Naming::new(System::MovableDo, Octaves::MiddleC4);
+    assert_eq!(movable.tonic_name(&Scale::new(-2, ScaleType::Dorian)), "Bb");
+}
+
+#[test]
+fn test_octaves() {
+    let c = Key::new(0);
+    let helmholtz = Naming::new(System::English, Octaves::Helmholtz);
+    assert_eq!(helmholtz.name(&Note::new(60), NoteAlt::Sharp, &c), "c′");
+    assert_eq!(helmholtz.name(&Note::new(74), NoteAlt::Sharp, &c), "d′′");
+    assert_eq!(helmholtz.name(&Note::new(57), NoteAlt::Sharp, &c), "a");
+    assert_eq!(helmholtz.name(&Note::new(36), NoteAlt::Sharp, &c), "C");
+    assert_eq!(helmholtz.name(&Note::new(24), NoteAlt::Sharp, &c), "C,");
+    assert_eq!(helmholtz.name(&Note::new(1), NoteAlt::Flat, &c), "Db,,,");
+    let german = Naming::new(System::German, Octaves::Helmholtz);
+    assert_eq!(german.name(&Note::new(61), NoteAlt::Sharp, &c), "cis′");
+    let c5 = Naming::new(System::English, Octaves::MiddleC5);
+    assert_eq!(c5.name(&Note::new(60), NoteAlt::Sharp, &c), "C5");
+    // No underflow at the bottom of the MIDI range.
+    let c3 = Naming::new(System::English, Octaves::MiddleC3);
+    assert_eq!(c3.name(&Note::new(0), NoteAlt::Sharp, &c), "C-2");
+}
+
+#[test]
+fn test_solfege() {
+    let c = Key::new(0);
+    let italian = Naming::new(System::Italian, Octaves::MiddleC4);
+    assert_eq!(italian.name(&Note::new(67), NoteAlt::Sharp, &c), "Sol4");
+    assert_eq!(italian.name(&Note::new(70), NoteAlt::Flat, &c), "Si♭4");
+    let russian = Naming::new(System::Russian, Octaves::MiddleC4);
+    assert_eq!(russian.name(&Note::new(66), NoteAlt::Sharp, &c), "Фа♯4");
+    let japanese = Naming::new(System::Japanese, Octaves::MiddleC4);
+    assert_eq!(
+        japanese.pitch_class(&Note::new(65), NoteAlt::Sharp, &c),
+        "ファ"
+    );
+    let french = Naming::new(System::French, Octaves::MiddleC4);
+    assert_eq!(french.pitch_class(&Note::new(62), NoteAlt::Sharp, &c), "Ré");
+    // Movable do follows the key: in G major G is Do and F# is Ti.
+    let movable = Naming::new(System::MovableDo, Octaves::MiddleC4);
+    let g = Key::new(1);
+    assert_eq!(
+        movable.pitch_class(&Note::new(67), NoteAlt::Sharp, &g),
+        "Do"
+    );
+    assert_eq!(
+        movable.pitch_class(&Note::new(66), NoteAlt::Sharp, &g),
+        "Ti"
+    );
+    assert_eq!(movable.pitch_class(&Note::new(70), NoteAlt::Flat, &g), "Me");
+    assert_eq!(
+        movable.pitch_class(&Note::new(68), NoteAlt::Sharp, &g),
+        "Di"
+    );
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum System {
+    English,
+    German,
+    Italian,
+    French,
+    Russian,
+    Japanese,
+    MovableDo,
+}
+impl System {
+    pub const ALL: [System; 7] = [
+        System::English,
+        System::German,
+        System::Italian,
+        System::French,
+        System::Russian,
+        System::Japanese,
+        System::MovableDo,
+    ];
+    // Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            System::English => "naming-english",
+            System::German => "naming-german",
+            System::Italian => "naming-italian",
+            System::French => "naming-french",
+            System::Russian => "naming-russian",
+            System::Japanese => "naming-japanese",
+            System::MovableDo => "naming-movable-do",
+        }
+    }
+    fn letters(&self) -> Option<&'static [&'static str; 7]> {
+        match self {
+            System::English => Some(&ENGLISH),
+            System::German => Some(&GERMAN),
+            System::Italian => Some(&ITALIAN),
+            System::French => Some(&FRENCH),
+            System::Russian => Some(&RUSSIAN),
+            System::Japanese => Some(&JAPANESE),
+            System::MovableDo => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Octaves {
+    MiddleC3,
+    MiddleC4,
+    MiddleC5,
+    // Letter case and marks: C, C c c′ c′′
+    Helmholtz,
+}
+impl Octaves {
+    pub const ALL: [Octaves; 4] = [
+        Octaves::MiddleC3,
+        Octaves::MiddleC4,
+        Octaves::MiddleC5,
+        Octaves::Helmholtz,
+    ];
+    pub fn id(&self) -> &'static str {
+        match self {
+            Octaves::MiddleC3 => "octaves-c3",
+            Octaves::MiddleC4 => "octaves-c4",
+            Octaves::MiddleC5 => "octaves-c5",
+            Octaves::Helmholtz => "octaves-helmholtz",
+        }
+    }
+}
+
+// How note names are written everywhere in the app. Widgets read it
+// from the environment, see NAMING.
+#[derive(Clone, Copy, Data, Lens, PartialEq, Debug)]
+pub struct Naming {
+    pub system: System,
+    pub octaves: Octaves,
+}
+impl Default for Naming {
+    fn default() -> Self {
+        Naming::new(System::English, Octaves::MiddleC3)
+    }
+}
+impl Naming {
+    pub fn new(system: System, octaves: Octaves) -> Self {
+        Naming { system, octaves }
+    }
+    pub fn from_env(env: &Env) -> Self {
+        *env.get(NAMING)
+    }
+
+    fn spell(&self, step: usize, alt: NoteAlt) -> String {
+        let letters = self.system.letters().unwrap_or(&ENGLISH);
+        match (self.system, alt) {
+            (System::German, NoteAlt::White) => String::from(letters[step]),
+            (System::German, NoteAlt::Sharp) => format!("{}is", letters[step]),
+            // B is the German B flat, H the natural.
+            (System::German, NoteAlt::Flat) if step == 6 => String::from("B"),
+            (System::German, NoteAlt::Flat) if step == 2 || step == 5 => {
+                format!("{}s", letters[step])
+            }
+            (System::German, NoteAlt::Flat) => format!("{}es", letters[step]),
+            (System::English, alt) | (System::MovableDo, alt) => {
+                format!("{}{}", letters[step], alt)
+            }
+            (_, NoteAlt::White) => String::from(letters[step]),
+            (_, NoteAlt::Sharp) => format!("{}♯", letters[step]),
+            (_, NoteAlt::Flat) => format!("{}♭", letters[step]),
+        }
+    }
+    // Name without octave. Movable do counts from the tonic of the key.
+    pub fn pitch_class(&self, note: &Note, alteration: NoteAlt, key: &Key) -> String {
+        let (step, alt) = note.step(alteration);
+        if self.system == System::MovableDo {
+            let degree = (note.midi_nr() as usize + 12 - key.tonic()) % 12;
+            let syllables = match alt {
+                NoteAlt::Flat => &DO_FLAT,
+                _ => &DO_SHARP,
+            };
+            return String::from(syllables[degree]);
+        }
+        self.spell(step, alt)
+    }
+    pub fn name(&self, note: &Note, alteration: NoteAlt, key: &Key) -> String {
+        let pitch_class = self.pitch_class(note, alteration, key);
+        // Octave of middle C counted from zero.
+        let octave = note.midi_nr() as i32 / 12 - 5;
+        match self.octaves {
+            Octaves::MiddleC3 => format!("{}{}", pitch_class, octave + 3),
+            Octaves::MiddleC4 => format!("{}{}", pitch_class, octave + 4),
+            Octaves::MiddleC5 => format!("{}{}", pitch_class, octave + 5),
+            Octaves::Helmholtz if octave >= -1 => format!(
+                "{}{}",
+                pitch_class.to_lowercase(),
+                "′".repeat((octave + 1) as usize)
+            ),
+            Octaves::Helmholtz => {
+                format!("{}{}", pitch_class, ",".repeat((-octave - 2) as usize))
+            }
+        }
+    }
+    // Key names use letters for movable do, where the tonic is always Do.
+    pub fn key_name(&self, key: &Key) -> String {
+        let name = key.name();
+        let step = ENGLISH
+            .iter()
+            .position(|l| name.starts_with(l))
+            .unwrap_or(0);
+        let alt = match &name[1..] {
+            "#" => NoteAlt::Sharp,
+            "b" => NoteAlt::Flat,
+            _ => NoteAlt::White,
+        };
+        self.letter_name(step, alt)
+    }
+    // Tonic of a scale without octave, named like a key.
+    pub fn tonic_name(&self, scale: &Scale) -> String {
+        let tonic = scale.tonic(0);
+        let alt = match tonic.alter() {
+            alter if alter > 0 => NoteAlt::Sharp,
+            alter if alter < 0 => NoteAlt::Flat,
+            _ => NoteAlt::White,
+        };
+        self.letter_name(tonic.step(), alt)
+    }
+    fn letter_name(&self, step: usize, alt: NoteAlt) -> String {
+        let naming = match self.system {
+            System::MovableDo => Naming::new(System::English, self.octaves),
+            _ => *self,
+        };
+        naming.spell(step, alt)
+    }
+}
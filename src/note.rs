@@ -1,11 +1,74 @@
-use druid::{widget::SvgData, Data};
-
-use tracing::error;
-
-// mod note;
+use druid::Data;
+#[cfg(feature = "serialize")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 const LINES_AMOUTN: usize = 24;
+const MAJOR_STEPS: [usize; 7] = [0, 2, 4, 5, 7, 9, 11];
+const KEY_NAMES: [&str; 15] = [
+    "Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#",
+];
+// Staff steps (C is 0) altered by each added sharp or flat.
+const SHARP_STEPS: [usize; 7] = [3, 0, 4, 1, 5, 2, 6];
+const FLAT_STEPS: [usize; 7] = [6, 2, 5, 1, 4, 0, 3];
 const NOTE_NAMES: [&str; 12] = ["C", "C", "D", "D", "E", "F", "F", "G", "G", "A", "A", "B"];
+// Lower case solfège syllables and letters read from text, with their
+// staff step. Longer syllables come first, "sol" before "so".
+const SYLLABLES: [(&str, usize); 24] = [
+    ("sol", 4),
+    ("do", 0),
+    ("re", 1),
+    ("ré", 1),
+    ("mi", 2),
+    ("fa", 3),
+    ("so", 4),
+    ("la", 5),
+    ("si", 6),
+    ("ti", 6),
+    ("соль", 4),
+    ("до", 0),
+    ("ре", 1),
+    ("ми", 2),
+    ("фа", 3),
+    ("ля", 5),
+    ("си", 6),
+    ("ファ", 3),
+    ("ド", 0),
+    ("レ", 1),
+    ("ミ", 2),
+    ("ソ", 4),
+    ("ラ", 5),
+    ("シ", 6),
+];
+// H is the German B, the German B flat has to be written as one.
+const LETTERS: [(&str, usize); 8] = [
+    ("c", 0),
+    ("d", 1),
+    ("e", 2),
+    ("f", 3),
+    ("g", 4),
+    ("a", 5),
+    ("b", 6),
+    ("h", 6),
+];
+// German endings after a letter, "s" only for As and Es.
+const GERMAN_ENDINGS: [(&str, i8); 6] = [
+    ("isis", 2),
+    ("is", 1),
+    ("eses", -2),
+    ("es", -1),
+    ("ses", -2),
+    ("s", -1),
+];
+const ACCIDENTALS: [(&str, i8); 8] = [
+    ("#", 1),
+    ("♯", 1),
+    ("x", 2),
+    ("𝄪", 2),
+    ("b", -1),
+    ("♭", -1),
+    ("𝄫", -2),
+    ("♮", 0),
+];
 const NOTE_LINES: [NoteLine; 24] = [
     NoteLine::new(0.0, false), // C
     NoteLine::new(0.0, true),  // C#
@@ -71,32 +134,239 @@ fn test_note() {
     assert_eq!(fis3.name(NoteAlt::Flat), String::from("Gb3"));
     assert_eq!(fis3.alteration(NoteAlt::Flat), NoteAlt::Flat);
     assert_eq!(fis3.line(NoteAlt::Flat), 19.5);
+    assert_eq!(fis3.step(NoteAlt::Sharp), (3, NoteAlt::Sharp));
+    assert_eq!(fis3.step(NoteAlt::Flat), (4, NoteAlt::Flat));
+    assert_eq!(Note::new(71).step(NoteAlt::Flat), (6, NoteAlt::White));
+    assert_eq!(Note::new(0).line(NoteAlt::Sharp), 0.0);
+    assert_eq!(Note::new(1).alteration(NoteAlt::Flat), NoteAlt::Flat);
+    assert_eq!(Note::new(0).name(NoteAlt::Sharp), "C-2");
+    assert_eq!(Note::new(13).name(NoteAlt::Flat), "Db-1");
+    assert_eq!(Note::new(23).name(NoteAlt::Sharp), "B-1");
+    assert_eq!(Note::new(24).name(NoteAlt::Sharp), "C0");
+    assert_eq!(Note::new(127).name(NoteAlt::Sharp), "G8");
+}
+
+#[test]
+fn test_full_range() {
+    for nr in 0..=127 {
+        let note = Note::new(nr);
+        for alteration in [NoteAlt::Sharp, NoteAlt::Flat].iter() {
+            let name = note.name(alteration.clone());
+            let letter = name.chars().next().unwrap();
+            assert!(('A'..='G').contains(&letter), "{}", name);
+            let octave = name.trim_start_matches(|c: char| !c.is_ascii_digit() && c != '-');
+            assert_eq!(
+                octave.parse::<i32>().unwrap(),
+                nr as i32 / 12 - 2,
+                "{}",
+                name
+            );
+            if nr > 0 {
+                let below = Note::new(nr - 1).line(alteration.clone());
+                assert!(note.line(alteration.clone()) >= below, "{}", name);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_key() {
+    let c = Key::new(0);
+    assert_eq!(c.name(), "C");
+    assert_eq!(c.alteration(), NoteAlt::Sharp);
+    assert!(c.contains(&Note::new(60)));
+    assert!(!c.contains(&Note::new(61)));
+    let es = Key::new(-3);
+    assert_eq!(es.name(), "Eb");
+    assert_eq!(es.alteration(), NoteAlt::Flat);
+    assert!(es.contains(&Note::new(63)));
+    assert!(es.contains(&Note::new(68)));
+    assert!(!es.contains(&Note::new(64)));
+    let fis = Key::new(6);
+    assert_eq!(fis.name(), "F#");
+    assert!(fis.contains(&Note::new(66)));
+    assert!(!fis.contains(&Note::new(67)));
+    assert_eq!(Key::new(-2).altered_steps(), &[6, 2]);
+    assert_eq!(Key::new(1).altered_steps(), &[3]);
+    // D major up a major second is E major, C# up a second wraps to Eb.
+    assert_eq!(Key::new(2).transpose(2), Key::new(4));
+    assert_eq!(Key::new(7).transpose(2), Key::new(-3));
+    assert_eq!(Key::new(-6).transpose(-3), Key::new(3));
+}
+
+#[test]
+fn test_parse() {
+    for nr in 0..=127 {
+        for alteration in [NoteAlt::Sharp, NoteAlt::Flat].iter() {
+            let note = Note::new(nr);
+            let name = note.name(alteration.clone());
+            assert_eq!(name.parse::<Note>(), Ok(note.clone()), "{}", name);
+            let spelled = note.spelled(alteration.clone());
+            assert_eq!(spelled.to_string(), name);
+            assert_eq!(name.parse::<SpelledPitch>(), Ok(spelled));
+        }
+    }
+    let parse = |text: &str| text.parse::<Note>().map(|note| note.midi_nr());
+    assert_eq!(parse("60"), Ok(60));
+    assert_eq!(parse(" c3 "), Ok(60));
+    assert_eq!(parse("bb2"), Ok(58));
+    assert_eq!(parse("Cb3"), Ok(59));
+    assert_eq!(parse("B#2"), Ok(60));
+    assert_eq!(parse("F♯3"), Ok(66));
+    assert_eq!(parse("Fx3"), Ok(67));
+    assert_eq!(parse("E𝄫3"), Ok(62));
+    assert_eq!(parse("Ebb3"), Ok(62));
+    assert_eq!(parse("Fis3"), Ok(66));
+    assert_eq!(parse("Es3"), Ok(63));
+    assert_eq!(parse("As3"), Ok(68));
+    assert_eq!(parse("Heses2"), Ok(57));
+    assert_eq!(parse("H2"), Ok(59));
+    assert_eq!(parse("Sol3"), Ok(67));
+    assert_eq!(parse("Si♭3"), Ok(70));
+    assert_eq!(parse("Ré3"), Ok(62));
+    assert_eq!(parse("Фа♯3"), Ok(66));
+    assert_eq!(parse("ソ3"), Ok(67));
+    assert_eq!(parse("C-2"), Ok(0));
+    assert_eq!(
+        parse("128"),
+        Err(String::from("128 is not a MIDI note number 0-127"))
+    );
+    assert_eq!(parse(""), Err(String::from("empty note name")));
+    assert_eq!(parse("Q3"), Err(String::from("unknown note name Q3")));
+    assert_eq!(parse("C"), Err(String::from("missing octave in C")));
+    assert_eq!(
+        parse("C#b3"),
+        Err(String::from("mixed or more than two accidentals in C#b3"))
+    );
+    assert_eq!(
+        parse("C###3"),
+        Err(String::from("mixed or more than two accidentals in C###3"))
+    );
+    assert_eq!(parse("Cs3"), Err(String::from("bad octave s3 in Cs3")));
+    assert_eq!(
+        parse("G#8"),
+        Err(String::from("G#8 is outside the MIDI range C-2 to G8"))
+    );
+    assert_eq!(
+        "Cx3".parse::<SpelledPitch>(),
+        Ok(SpelledPitch::new(0, 2, 3))
+    );
+    assert_eq!(SpelledPitch::new(6, -2, 2).to_string(), "Bbb2");
+    assert_eq!(SpelledPitch::new(0, -1, -2).note(), None);
+}
+
+#[test]
+fn test_scale() {
+    let names = |tonic: i8, kind: ScaleType, octave: i32| {
+        let pitches = Scale::new(tonic, kind).pitches(octave);
+        let names: Vec<String> = pitches.iter().map(|pitch| pitch.to_string()).collect();
+        names.join(" ")
+    };
+    assert_eq!(names(0, ScaleType::Major, 3), "C3 D3 E3 F3 G3 A3 B3 C4");
+    assert_eq!(
+        names(3, ScaleType::NaturalMinor, 2),
+        "A2 B2 C3 D3 E3 F3 G3 A3"
+    );
+    assert_eq!(
+        names(8, ScaleType::HarmonicMinor, 3),
+        "G#3 A#3 B3 C#4 D#4 E4 F##4 G#4"
+    );
+    assert_eq!(
+        names(-7, ScaleType::NaturalMinor, 3),
+        "Cb3 Db3 Ebb3 Fb3 Gb3 Abb3 Bbb3 Cb4"
+    );
+    assert_eq!(
+        names(-2, ScaleType::MelodicMinor, 3),
+        "Bb3 C4 Db4 Eb4 F4 G4 A4 Bb4"
+    );
+    assert_eq!(names(2, ScaleType::Dorian, 3), "D3 E3 F3 G3 A3 B3 C4 D4");
+    assert_eq!(names(5, ScaleType::Locrian, 2), "B2 C3 D3 E3 F3 G3 A3 B3");
+    assert_eq!(names(-1, ScaleType::Lydian, 3), "F3 G3 A3 B3 C4 D4 E4 F4");
+    assert_eq!(names(1, ScaleType::MajorPentatonic, 3), "G3 A3 B3 D4 E4 G4");
+    assert_eq!(names(4, ScaleType::MinorPentatonic, 3), "E3 G3 A3 B3 D4 E4");
+    assert_eq!(names(0, ScaleType::Blues, 3), "C3 Eb3 F3 F#3 G3 Bb3 C4");
+    assert_eq!(names(0, ScaleType::WholeTone, 3), "C3 D3 E3 F#3 G#3 A#3 C4");
+    assert_eq!(
+        names(10, ScaleType::WholeTone, 3),
+        "A#3 B#3 C##4 D##4 E##4 G#4 A#4"
+    );
+    assert_eq!(
+        names(0, ScaleType::WholeHalf, 3),
+        "C3 D3 Eb3 F3 F#3 G#3 A3 B3 C4"
+    );
+    assert_eq!(
+        names(0, ScaleType::HalfWhole, 3),
+        "C3 Db3 Eb3 E3 F#3 G3 A3 Bb3 C4"
+    );
+    for kind in ScaleType::ALL.iter() {
+        for tonic in -7..=10 {
+            let scale = Scale::new(tonic, *kind);
+            let pitches = scale.pitches(3);
+            let notes: Vec<u8> = pitches.iter().map(|p| p.note().unwrap().midi_nr).collect();
+            assert_eq!(notes[notes.len() - 1], notes[0] + 12, "{:?}", kind);
+            assert!(notes.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", kind);
+            assert!(pitches
+                .windows(2)
+                .all(|pair| pair[0].line() <= pair[1].line()));
+            for (degree, nr) in notes[..notes.len() - 1].iter().enumerate() {
+                assert_eq!(scale.degree(&Note::new(nr + 24)), Some(degree));
+            }
+        }
+    }
+    let d_sharp = Scale::new(9, ScaleType::NaturalMinor);
+    assert_eq!(d_sharp.tonic(3).to_string(), "D#3");
+    assert_eq!(d_sharp.degree(&Note::new(63)), Some(0));
+    assert_eq!(d_sharp.degree(&Note::new(64)), None);
+    let numbers = |kind: ScaleType| {
+        let scale = Scale::new(0, kind);
+        let numbers: Vec<String> = (0..kind.degrees().len())
+            .map(|degree| scale.degree_number(degree).unwrap())
+            .collect();
+        numbers.join(" ")
+    };
+    assert_eq!(numbers(ScaleType::Major), "1 2 3 4 5 6 7");
+    assert_eq!(numbers(ScaleType::NaturalMinor), "1 2 ♭3 4 5 ♭6 ♭7");
+    assert_eq!(numbers(ScaleType::Blues), "1 ♭3 4 ♯4 5 ♭7");
+    assert_eq!(Scale::new(0, ScaleType::Major).degree_number(7), None);
+    assert_eq!(Scale::new(9, ScaleType::Major).transpose(2).fifths(), -1);
+    assert_eq!(Scale::new(-6, ScaleType::Major).transpose(-3).fifths(), 3);
+    assert_eq!(Scale::new(20, ScaleType::Major).fifths(), 10);
+    assert_eq!(
+        Note::new(66).spelled(NoteAlt::Sharp).line(),
+        Note::new(66).line(NoteAlt::Sharp)
+    );
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn test_serde() {
+    let notes = vec![Note::new(78), Note::new(61)];
+    let json = serde_json::to_string(&notes).unwrap();
+    assert_eq!(json, r#"["F#4","C#3"]"#);
+    let read: Vec<Note> = serde_json::from_str(r#"["F#4","Db3",60]"#).unwrap();
+    assert_eq!(read, vec![Note::new(78), Note::new(61), Note::new(60)]);
+    assert!(serde_json::from_str::<Note>(r#""X4""#).is_err());
+    assert!(serde_json::from_str::<Note>("128").is_err());
+    assert_eq!(serde_json::to_string(&NoteAlt::Flat).unwrap(), r#""flat""#);
+    assert_eq!(serde_json::to_string(&Key::new(-2)).unwrap(), r#""Bb""#);
+    assert_eq!(serde_json::from_str::<Key>(r#""F#""#).unwrap(), Key::new(6));
+    assert!(serde_json::from_str::<Key>(r#""H""#).is_err());
+    let pitch: SpelledPitch = serde_json::from_str(r#""Fisis3""#).unwrap();
+    assert_eq!(serde_json::to_string(&pitch).unwrap(), r#""F##3""#);
 }
 
+/// Accidental of a note, or the spelling asked for black keys.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum NoteAlt {
     White,
     Sharp,
     Flat,
 }
-impl NoteAlt {
-    pub fn svgdata(&self) -> SvgData {
-        let file: Result<SvgData, Box<dyn std::error::Error + 'static>>;
-        match *self {
-            NoteAlt::White => return SvgData::default(),
-            NoteAlt::Sharp => file = include_str!("../assets/sharp.svg").parse::<SvgData>(),
-            NoteAlt::Flat => file = include_str!("../assets/flat.svg").parse::<SvgData>(),
-        }
-        match file {
-            Ok(svg) => svg,
-            Err(err) => {
-                error!("{}", err);
-                error!("Using an empty SVG instead.");
-                SvgData::default()
-            }
-        }
-    }
-}
 impl std::fmt::Display for NoteAlt {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
@@ -108,7 +378,7 @@ impl std::fmt::Display for NoteAlt {
 }
 
 #[derive(Debug)]
-pub struct NoteLine {
+struct NoteLine {
     root: f64,
     alterated: bool,
 }
@@ -130,6 +400,7 @@ impl NoteLine {
     }
 }
 
+/// Pitch given by its MIDI number, middle C is 60.
 #[derive(Data, Clone, PartialEq, Debug)]
 pub struct Note {
     midi_nr: u8,
@@ -138,32 +409,532 @@ impl Note {
     pub fn new(midi_nr: u8) -> Self {
         Note { midi_nr: midi_nr }
     }
-    pub fn spec(&self, alteration: NoteAlt) -> (f64, NoteAlt, String) {
+    pub fn midi_nr(&self) -> u8 {
+        self.midi_nr
+    }
+    // Line counted from the lowest C and the accidental, without the
+    // name, for the staff layout.
+    fn position(&self, alteration: NoteAlt) -> (f64, NoteAlt) {
         let midi_nr = self.midi_nr as usize;
         let modulo = midi_nr / LINES_AMOUTN;
         let remainder = midi_nr % LINES_AMOUTN;
-        let (line, alt) = &NOTE_LINES[remainder].from_alteration(alteration);
-        let line_full = line + (modulo * 7) as f64;
-        let octave = (midi_nr as usize / 12) - 2;
+        let (line, alt) = NOTE_LINES[remainder].from_alteration(alteration);
+        (line + (modulo * 7) as f64, alt)
+    }
+    /// Staff line, accidental and name, see [`Note::line`] and
+    /// [`Note::name`].
+    pub fn spec(&self, alteration: NoteAlt) -> (f64, NoteAlt, String) {
+        let midi_nr = self.midi_nr as usize;
+        let (line_full, alt) = self.position(alteration);
+        // Octaves below the one of MIDI 24 are negative.
+        let octave = midi_nr as i32 / 12 - 2;
         let mut note_name: &str;
-        if alt == &NoteAlt::Sharp || alt == &NoteAlt::White {
+        if alt == NoteAlt::Sharp || alt == NoteAlt::White {
             note_name = &NOTE_NAMES[midi_nr % 12];
         } else {
             note_name = &NOTE_NAMES[(midi_nr % 12) + 1];
         }
         let name = format!("{}{}{}", note_name, alt, octave);
-        (line_full, alt.clone(), name)
+        (line_full, alt, name)
     }
+    /// Place on the staff in lines, half a line per step, counted
+    /// from the lowest C. Black keys take the sharp or the flat spelling.
     pub fn line(&self, alteration: NoteAlt) -> f64 {
-        let (line, _alt, _name) = self.spec(alteration);
+        let (line, _alt) = self.position(alteration);
         line
     }
+    /// Accidental written before the note, `White` for white keys.
     pub fn alteration(&self, alteration: NoteAlt) -> NoteAlt {
-        let (_line, alt, _name) = self.spec(alteration);
+        let (_line, alt) = self.position(alteration);
         alt
     }
+    /// English name with the octave, middle C is "C3" and MIDI 0
+    /// is "C-2".
     pub fn name(&self, alteration: NoteAlt) -> String {
         let (_line, _alt, name) = self.spec(alteration);
         return name;
     }
+    /// Letter as a staff step (C is 0, B is 6) and its accidental.
+    pub fn step(&self, alteration: NoteAlt) -> (usize, NoteAlt) {
+        let (line, alt) =
+            NOTE_LINES[self.midi_nr as usize % LINES_AMOUTN].from_alteration(alteration);
+        ((line * 2.0) as usize % 7, alt)
+    }
+    /// Spelling with the letter of [`Note::name`].
+    pub fn spelled(&self, alteration: NoteAlt) -> SpelledPitch {
+        let (step, alt) = self.step(alteration);
+        let alter = match alt {
+            NoteAlt::White => 0,
+            NoteAlt::Sharp => 1,
+            NoteAlt::Flat => -1,
+        };
+        SpelledPitch::new(step, alter, self.midi_nr as i32 / 12 - 2)
+    }
+}
+/// MIDI numbers or names read by [`SpelledPitch`], like "60", "F#3",
+/// "Fis3" or "Fa♯3".
+impl std::str::FromStr for Note {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if let Ok(midi_nr) = text.parse::<i64>() {
+            return match midi_nr {
+                0..=127 => Ok(Note::new(midi_nr as u8)),
+                _ => Err(format!("{} is not a MIDI note number 0-127", midi_nr)),
+            };
+        }
+        let pitch = text.parse::<SpelledPitch>()?;
+        pitch
+            .note()
+            .ok_or_else(|| format!("{} is outside the MIDI range C-2 to G8", text))
+    }
+}
+
+// Notes are written by name, sharps for black keys, and read from a
+// name or a MIDI number.
+#[cfg(feature = "serialize")]
+impl Serialize for Note {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name(NoteAlt::Sharp))
+    }
+}
+#[cfg(feature = "serialize")]
+impl<'de> Deserialize<'de> for Note {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Note;
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a note name like \"F#4\" or a MIDI number")
+            }
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Note, E> {
+                text.parse().map_err(E::custom)
+            }
+            fn visit_u64<E: de::Error>(self, nr: u64) -> Result<Note, E> {
+                match nr {
+                    0..=127 => Ok(Note::new(nr as u8)),
+                    _ => Err(E::custom(format!("bad MIDI number {}", nr))),
+                }
+            }
+            fn visit_i64<E: de::Error>(self, nr: i64) -> Result<Note, E> {
+                match nr {
+                    0..=127 => Ok(Note::new(nr as u8)),
+                    _ => Err(E::custom(format!("bad MIDI number {}", nr))),
+                }
+            }
+        }
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// Note with its letter, accidentals and octave, so that F# and Gb or
+/// the double sharps of a scale stay apart. Middle C is C3, as in
+/// [`Note::name`].
+#[derive(Data, Clone, PartialEq, Debug)]
+pub struct SpelledPitch {
+    step: usize,
+    alter: i8,
+    octave: i32,
+}
+impl SpelledPitch {
+    /// Staff step of the letter (C is 0, B is 6) and the accidentals,
+    /// clamped to double flats and sharps.
+    pub fn new(step: usize, alter: i8, octave: i32) -> Self {
+        SpelledPitch {
+            step: step % 7,
+            alter: alter.clamp(-2, 2),
+            octave,
+        }
+    }
+    pub fn step(&self) -> usize {
+        self.step
+    }
+    /// Sharps (positive) or flats (negative), up to two.
+    pub fn alter(&self) -> i8 {
+        self.alter
+    }
+    pub fn octave(&self) -> i32 {
+        self.octave
+    }
+    /// Place on the staff in lines, as [`Note::line`] gives it for
+    /// single accidentals.
+    pub fn line(&self) -> f64 {
+        (self.octave + 2) as f64 * 3.5 + self.step as f64 * 0.5
+    }
+    // MIDI number, also outside the MIDI range.
+    fn midi_nr(&self) -> i32 {
+        (self.octave + 2) * 12 + MAJOR_STEPS[self.step] as i32 + self.alter as i32
+    }
+    /// The sounding note, none outside the MIDI range.
+    pub fn note(&self) -> Option<Note> {
+        let midi_nr = self.midi_nr();
+        if (0..=127).contains(&midi_nr) {
+            Some(Note::new(midi_nr as u8))
+        } else {
+            None
+        }
+    }
+}
+/// English name with the octave, "##" and "bb" for double accidentals.
+impl std::fmt::Display for SpelledPitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let accidental = if self.alter < 0 { "b" } else { "#" };
+        write!(
+            f,
+            "{}{}{}",
+            NOTE_NAMES[MAJOR_STEPS[self.step]],
+            accidental.repeat(self.alter.unsigned_abs() as usize),
+            self.octave
+        )
+    }
+}
+/// English letters with #, b, x or ♯, ♭, 𝄪, 𝄫, German letters with
+/// their endings like "Fis" or "Heses", or solfège syllables in Latin,
+/// Cyrillic or katakana, followed by the octave. Case is ignored, so
+/// "bb3" is B flat.
+impl std::str::FromStr for SpelledPitch {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let lower = text.trim().to_lowercase();
+        if lower.is_empty() {
+            return Err(String::from("empty note name"));
+        }
+        let prefix = |table: &[(&'static str, usize)]| {
+            table
+                .iter()
+                .find(|(name, _)| lower.starts_with(name))
+                .map(|(name, step)| (name.len(), *step))
+        };
+        let (mut rest, step, letter) = match prefix(&SYLLABLES) {
+            Some((len, step)) => (&lower[len..], step, false),
+            None => match prefix(&LETTERS) {
+                Some((len, step)) => (&lower[len..], step, true),
+                None => return Err(format!("unknown note name {}", text)),
+            },
+        };
+        let mut alter = 0;
+        let german = GERMAN_ENDINGS.iter().find(|(ending, _)| {
+            rest.starts_with(ending) && (!ending.starts_with('s') || step == 2 || step == 5)
+        });
+        match german {
+            Some((ending, shift)) if letter => {
+                rest = &rest[ending.len()..];
+                alter = *shift;
+            }
+            _ => {
+                while let Some((symbol, shift)) = ACCIDENTALS
+                    .iter()
+                    .find(|(symbol, _)| rest.starts_with(symbol))
+                {
+                    if alter * shift < 0 || (alter + shift).abs() > 2 {
+                        return Err(format!("mixed or more than two accidentals in {}", text));
+                    }
+                    rest = &rest[symbol.len()..];
+                    alter += shift;
+                }
+            }
+        }
+        match rest.parse::<i32>() {
+            Ok(octave) => Ok(SpelledPitch::new(step, alter, octave)),
+            Err(_) if rest.is_empty() => Err(format!("missing octave in {}", text)),
+            Err(_) => Err(format!("bad octave {} in {}", rest, text)),
+        }
+    }
+}
+
+// Spelled pitches are written and read by name.
+#[cfg(feature = "serialize")]
+impl Serialize for SpelledPitch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+#[cfg(feature = "serialize")]
+impl<'de> Deserialize<'de> for SpelledPitch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Scales and church modes a [`Scale`] can be built from.
+#[derive(Clone, Copy, Debug, Data, PartialEq)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ScaleType {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+    /// The ascending form.
+    MelodicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Blues,
+    WholeTone,
+    /// Octatonic starting with a whole step.
+    WholeHalf,
+    /// Octatonic starting with a half step.
+    HalfWhole,
+}
+impl ScaleType {
+    pub const ALL: [ScaleType; 15] = [
+        ScaleType::Major,
+        ScaleType::NaturalMinor,
+        ScaleType::HarmonicMinor,
+        ScaleType::MelodicMinor,
+        ScaleType::Dorian,
+        ScaleType::Phrygian,
+        ScaleType::Lydian,
+        ScaleType::Mixolydian,
+        ScaleType::Locrian,
+        ScaleType::MajorPentatonic,
+        ScaleType::MinorPentatonic,
+        ScaleType::Blues,
+        ScaleType::WholeTone,
+        ScaleType::WholeHalf,
+        ScaleType::HalfWhole,
+    ];
+    /// Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            ScaleType::Major => "scale-major",
+            ScaleType::NaturalMinor => "scale-natural-minor",
+            ScaleType::HarmonicMinor => "scale-harmonic-minor",
+            ScaleType::MelodicMinor => "scale-melodic-minor",
+            ScaleType::Dorian => "scale-dorian",
+            ScaleType::Phrygian => "scale-phrygian",
+            ScaleType::Lydian => "scale-lydian",
+            ScaleType::Mixolydian => "scale-mixolydian",
+            ScaleType::Locrian => "scale-locrian",
+            ScaleType::MajorPentatonic => "scale-major-pentatonic",
+            ScaleType::MinorPentatonic => "scale-minor-pentatonic",
+            ScaleType::Blues => "scale-blues",
+            ScaleType::WholeTone => "scale-whole-tone",
+            ScaleType::WholeHalf => "scale-whole-half",
+            ScaleType::HalfWhole => "scale-half-whole",
+        }
+    }
+    // Letters above the tonic letter and semitones above the tonic of
+    // each degree. Scales without seven notes skip or repeat letters.
+    fn degrees(&self) -> &'static [(usize, i32)] {
+        match self {
+            ScaleType::Major => &[(0, 0), (1, 2), (2, 4), (3, 5), (4, 7), (5, 9), (6, 11)],
+            ScaleType::NaturalMinor => &[(0, 0), (1, 2), (2, 3), (3, 5), (4, 7), (5, 8), (6, 10)],
+            ScaleType::HarmonicMinor => &[(0, 0), (1, 2), (2, 3), (3, 5), (4, 7), (5, 8), (6, 11)],
+            ScaleType::MelodicMinor => &[(0, 0), (1, 2), (2, 3), (3, 5), (4, 7), (5, 9), (6, 11)],
+            ScaleType::Dorian => &[(0, 0), (1, 2), (2, 3), (3, 5), (4, 7), (5, 9), (6, 10)],
+            ScaleType::Phrygian => &[(0, 0), (1, 1), (2, 3), (3, 5), (4, 7), (5, 8), (6, 10)],
+            ScaleType::Lydian => &[(0, 0), (1, 2), (2, 4), (3, 6), (4, 7), (5, 9), (6, 11)],
+            ScaleType::Mixolydian => &[(0, 0), (1, 2), (2, 4), (3, 5), (4, 7), (5, 9), (6, 10)],
+            ScaleType::Locrian => &[(0, 0), (1, 1), (2, 3), (3, 5), (4, 6), (5, 8), (6, 10)],
+            ScaleType::MajorPentatonic => &[(0, 0), (1, 2), (2, 4), (4, 7), (5, 9)],
+            ScaleType::MinorPentatonic => &[(0, 0), (2, 3), (3, 5), (4, 7), (6, 10)],
+            ScaleType::Blues => &[(0, 0), (2, 3), (3, 5), (3, 6), (4, 7), (6, 10)],
+            ScaleType::WholeTone => &[(0, 0), (1, 2), (2, 4), (3, 6), (4, 8), (5, 10)],
+            ScaleType::WholeHalf => &[
+                (0, 0),
+                (1, 2),
+                (2, 3),
+                (3, 5),
+                (3, 6),
+                (4, 8),
+                (5, 9),
+                (6, 11),
+            ],
+            ScaleType::HalfWhole => &[
+                (0, 0),
+                (1, 1),
+                (2, 3),
+                (2, 4),
+                (3, 6),
+                (4, 7),
+                (5, 9),
+                (6, 10),
+            ],
+        }
+    }
+}
+
+/// Scale or mode on a tonic given in fifths from C, from Cb (-7) to
+/// A# (10), which covers the tonics of all major and minor keys.
+#[derive(Data, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Scale {
+    tonic: i8,
+    kind: ScaleType,
+}
+impl Scale {
+    /// Clamped to the tonics from Cb to A#.
+    pub fn new(tonic: i8, kind: ScaleType) -> Self {
+        Scale {
+            tonic: tonic.clamp(-7, 10),
+            kind,
+        }
+    }
+    /// Tonic in fifths from C.
+    pub fn fifths(&self) -> i8 {
+        self.tonic
+    }
+    pub fn kind(&self) -> ScaleType {
+        self.kind
+    }
+    /// Tonic in the given octave.
+    pub fn tonic(&self, octave: i32) -> SpelledPitch {
+        // A fifth is four letters up, every seven fifths add a sharp.
+        let fifths = self.tonic as i32;
+        SpelledPitch::new(
+            (fifths * 4).rem_euclid(7) as usize,
+            (fifths + 1).div_euclid(7) as i8,
+            octave,
+        )
+    }
+    /// Degrees ascending from the tonic in the given octave, ending
+    /// on the tonic an octave higher. Degrees that would need more than
+    /// a double accidental take the next letter instead.
+    pub fn pitches(&self, octave: i32) -> Vec<SpelledPitch> {
+        let tonic = self.tonic(octave);
+        let degrees = self.kind.degrees().iter().chain(std::iter::once(&(7, 12)));
+        degrees
+            .map(|(letters, semitones)| {
+                let midi_nr = tonic.midi_nr() + semitones;
+                let spell = |step: i32| {
+                    let letter = SpelledPitch::new(
+                        step.rem_euclid(7) as usize,
+                        0,
+                        octave + step.div_euclid(7),
+                    );
+                    (letter.clone(), midi_nr - letter.midi_nr())
+                };
+                let step = (tonic.step + letters) as i32;
+                let (letter, alter) = match spell(step) {
+                    (_, alter) if alter > 2 => spell(step + 1),
+                    (_, alter) if alter < -2 => spell(step - 1),
+                    spelled => spelled,
+                };
+                SpelledPitch::new(letter.step, alter as i8, letter.octave)
+            })
+            .collect()
+    }
+    /// Moves the tonic by the given amount of fifths, falling back to the
+    /// enharmonic tonic past Cb or A#.
+    pub fn transpose(&self, fifths: i8) -> Scale {
+        let mut tonic = self.tonic + fifths;
+        if tonic > 10 {
+            tonic -= 12;
+        } else if tonic < -7 {
+            tonic += 12;
+        }
+        Scale::new(tonic, self.kind)
+    }
+    /// Letters and semitones from the tonic up to the degree.
+    pub fn interval(&self, degree: usize) -> Option<(usize, i32)> {
+        self.kind.degrees().get(degree).copied()
+    }
+    /// Number of the degree with a flat or sharp where it differs from
+    /// the major scale, "♭3" for the third of a minor scale.
+    pub fn degree_number(&self, degree: usize) -> Option<String> {
+        let (letters, semitones) = self.interval(degree)?;
+        let alter = semitones - MAJOR_STEPS[letters] as i32;
+        let sign = if alter < 0 { "♭" } else { "♯" };
+        Some(format!(
+            "{}{}",
+            sign.repeat(alter.unsigned_abs() as usize),
+            letters + 1
+        ))
+    }
+    /// Degree of the note counted from zero for the tonic, none for
+    /// notes outside the scale.
+    pub fn degree(&self, note: &Note) -> Option<usize> {
+        let tonic = self.tonic(0).midi_nr();
+        let semitones = (note.midi_nr as i32 - tonic).rem_euclid(12);
+        self.kind
+            .degrees()
+            .iter()
+            .position(|(_, degree)| *degree == semitones)
+    }
+}
+
+/// Major key (or its relative minor) given by the amount of sharps (positive)
+/// or flats (negative) in the key signature.
+#[derive(Data, Clone, PartialEq, Debug)]
+pub struct Key {
+    fifths: i8,
+}
+impl Key {
+    /// Clamped to seven accidentals.
+    pub fn new(fifths: i8) -> Self {
+        Key {
+            fifths: fifths.clamp(-7, 7),
+        }
+    }
+    pub fn fifths(&self) -> i8 {
+        self.fifths
+    }
+    /// Name of the major key, like "Bb".
+    pub fn name(&self) -> &'static str {
+        KEY_NAMES[(self.fifths + 7) as usize]
+    }
+    /// Pitch class of the tonic, C is 0.
+    pub fn tonic(&self) -> usize {
+        (self.fifths as i32 * 7).rem_euclid(12) as usize
+    }
+    /// Spelling of the key signature, sharps for C major.
+    pub fn alteration(&self) -> NoteAlt {
+        if self.fifths < 0 {
+            NoteAlt::Flat
+        } else {
+            NoteAlt::Sharp
+        }
+    }
+    /// Staff steps carrying an accidental in the key signature.
+    pub fn altered_steps(&self) -> &'static [usize] {
+        let amount = self.fifths.unsigned_abs() as usize;
+        if self.fifths < 0 {
+            &FLAT_STEPS[..amount]
+        } else {
+            &SHARP_STEPS[..amount]
+        }
+    }
+    /// Moves the key by the given amount of fifths, falling back to the
+    /// enharmonic key when that would need more than seven accidentals.
+    pub fn transpose(&self, fifths: i8) -> Key {
+        let mut fifths = self.fifths + fifths;
+        if fifths > 7 {
+            fifths -= 12;
+        } else if fifths < -7 {
+            fifths += 12;
+        }
+        Key::new(fifths)
+    }
+    /// Whether the note is on the major scale of the key.
+    pub fn contains(&self, note: &Note) -> bool {
+        let step = (note.midi_nr as usize + 12 - self.tonic()) % 12;
+        MAJOR_STEPS.contains(&step)
+    }
+}
+
+// Keys are written by the name of the major key.
+#[cfg(feature = "serialize")]
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+#[cfg(feature = "serialize")]
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        match KEY_NAMES.iter().position(|name| *name == text) {
+            Some(index) => Ok(Key::new(index as i8 - 7)),
+            None => Err(de::Error::custom(format!("bad key {}", text))),
+        }
+    }
 }
@@ -0,0 +1,304 @@
+use std::path::Path;
+
+use druid::{Data, Selector};
+
+use super::note::Note;
+
+pub const PITCH: Selector<Option<DetectedPitch>> = Selector::new("rusted_big_note.pitch");
+
+pub const A4: f64 = 440.0;
+const FRAME: usize = 2048;
+const HOP: usize = 512;
+// Frames quieter than this (RMS) are treated as silence.
+const SILENCE: f32 = 0.01;
+// Hops a detection has to stay the same to count as a note.
+const STABLE_HOPS: usize = 3;
+
+#[cfg(test)]
+fn sine(freq: f64, sample_rate: u32, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / sample_rate as f64;
+            (0.5 * (2.0 * std::f64::consts::PI * freq * t).sin()) as f32
+        })
+        .collect()
+}
+
+#[test]
+fn test_from_frequency() {
+    let a = DetectedPitch::from_frequency(440.0, A4).unwrap();
+    assert_eq!(a.note, Note::new(69));
+    assert!(a.cents.abs() < 1e-9);
+    let sharp_a = DetectedPitch::from_frequency(440.0 * 2f64.powf(10.0 / 1200.0), A4).unwrap();
+    assert_eq!(sharp_a.note, Note::new(69));
+    assert!((sharp_a.cents - 10.0).abs() < 1e-6);
+    let flat_c = DetectedPitch::from_frequency(261.626 * 2f64.powf(-30.0 / 1200.0), A4).unwrap();
+    assert_eq!(flat_c.note, Note::new(60));
+    assert!((flat_c.cents + 30.0).abs() < 0.1);
+    // Baroque pitch.
+    let a415 = DetectedPitch::from_frequency(415.0, 415.0).unwrap();
+    assert_eq!(a415.note, Note::new(69));
+    assert_eq!(DetectedPitch::from_frequency(0.0, A4), None);
+    assert_eq!(DetectedPitch::from_frequency(30000.0, A4), None);
+}
+
+#[test]
+fn test_yin() {
+    let yin = Yin::new(44100);
+    for freq in [82.41, 196.0, 440.0, 987.77].iter() {
+        let detected = yin.detect(&sine(*freq, 44100, FRAME)).unwrap();
+        assert!(
+            (detected - freq).abs() / freq < 0.005,
+            "{} != {}",
+            detected,
+            freq
+        );
+    }
+    assert_eq!(yin.detect(&vec![0.0; FRAME]), None);
+}
+
+#[test]
+fn test_tracker_and_wav() {
+    let path = std::env::temp_dir().join("rusted_big_note_test_pitch.wav");
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 22050,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    let samples = sine(329.63, 22050, 22050)
+        .into_iter()
+        .chain(std::iter::repeat(0.0).take(11025))
+        .chain(sine(392.0, 22050, 22050));
+    for sample in samples {
+        writer
+            .write_sample((sample * i16::MAX as f32) as i16)
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+
+    let detected = detect_wav(&path, A4).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let notes: Vec<u8> = detected
+        .iter()
+        .filter_map(|(_, pitch)| pitch.as_ref().map(|p| p.note.midi_nr()))
+        .collect();
+    assert!(notes.iter().all(|nr| *nr == 64 || *nr == 67));
+    assert!(notes.iter().filter(|nr| **nr == 64).count() > 30);
+    assert!(notes.iter().filter(|nr| **nr == 67).count() > 30);
+    assert!(detected.iter().any(|(_, pitch)| pitch.is_none()));
+    // Time stamps grow by one hop.
+    assert!((detected[1].0 - detected[0].0 - HOP as f64 / 22050.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_onset() {
+    let mut onset = Onset::new();
+    assert_eq!(onset.feed(Some(60)), None);
+    assert_eq!(onset.feed(Some(60)), None);
+    assert_eq!(onset.feed(Some(60)), Some(60));
+    assert_eq!(onset.feed(Some(60)), None);
+    // A short glitch doesn't retrigger.
+    assert_eq!(onset.feed(Some(61)), None);
+    assert_eq!(onset.feed(Some(60)), None);
+    assert_eq!(onset.feed(Some(60)), None);
+    assert_eq!(onset.feed(Some(60)), None);
+    // Repeating a note after silence does.
+    for _ in 0..STABLE_HOPS {
+        assert_eq!(onset.feed(None), None);
+    }
+    onset.feed(Some(60));
+    onset.feed(Some(60));
+    assert_eq!(onset.feed(Some(60)), Some(60));
+}
+
+#[derive(Clone, Data, Debug, PartialEq)]
+pub struct DetectedPitch {
+    pub note: Note,
+    // Deviation from the equal tempered note, -50..50.
+    pub cents: f64,
+    pub frequency: f64,
+}
+impl DetectedPitch {
+    pub fn from_frequency(frequency: f64, a4: f64) -> Option<Self> {
+        if frequency <= 0.0 {
+            return None;
+        }
+        let midi = 69.0 + 12.0 * (frequency / a4).log2();
+        let nearest = midi.round();
+        if !(0.0..=127.0).contains(&nearest) {
+            return None;
+        }
+        Some(DetectedPitch {
+            note: Note::new(nearest as u8),
+            cents: (midi - nearest) * 100.0,
+            frequency,
+        })
+    }
+}
+
+// YIN fundamental frequency estimator
+// (de Cheveigné & Kawahara, 2002).
+pub struct Yin {
+    sample_rate: u32,
+    threshold: f64,
+    min_freq: f64,
+    max_freq: f64,
+}
+impl Yin {
+    pub fn new(sample_rate: u32) -> Self {
+        Yin {
+            sample_rate,
+            threshold: 0.15,
+            min_freq: 40.0,
+            max_freq: 2000.0,
+        }
+    }
+    // Frequency in Hz, None for silence or unpitched frames.
+    pub fn detect(&self, frame: &[f32]) -> Option<f64> {
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+        if rms < SILENCE {
+            return None;
+        }
+        let half = frame.len() / 2;
+        let sample_rate = self.sample_rate as f64;
+        let tau_min = ((sample_rate / self.max_freq) as usize).max(2);
+        let tau_max = ((sample_rate / self.min_freq) as usize).min(half - 1);
+        if tau_min >= tau_max {
+            return None;
+        }
+        // Cumulative mean normalized difference function.
+        let mut cmnd = vec![1.0; tau_max + 1];
+        let mut running = 0.0;
+        for tau in 1..=tau_max {
+            let diff: f64 = (0..half)
+                .map(|j| {
+                    let delta = (frame[j] - frame[j + tau]) as f64;
+                    delta * delta
+                })
+                .sum();
+            running += diff;
+            cmnd[tau] = if running > 0.0 {
+                diff * tau as f64 / running
+            } else {
+                1.0
+            };
+        }
+        let mut tau = tau_min;
+        while tau < tau_max {
+            if cmnd[tau] < self.threshold {
+                while tau + 1 < tau_max && cmnd[tau + 1] < cmnd[tau] {
+                    tau += 1;
+                }
+                break;
+            }
+            tau += 1;
+        }
+        if tau == tau_max {
+            return None;
+        }
+        // Parabolic interpolation for sub-sample precision.
+        let (prev, here, next) = (cmnd[tau - 1], cmnd[tau], cmnd[tau + 1]);
+        let denom = prev + next - 2.0 * here;
+        let shift = if denom.abs() > f64::EPSILON {
+            (prev - next) / (2.0 * denom)
+        } else {
+            0.0
+        };
+        Some(sample_rate / (tau as f64 + shift))
+    }
+}
+
+// Turns a stream of per-hop detections into note starts: a note has to
+// stay for a few hops, and it only triggers again after a change.
+pub struct Onset {
+    candidate: Option<u8>,
+    count: usize,
+    sounding: Option<u8>,
+}
+impl Onset {
+    pub fn new() -> Self {
+        Onset {
+            candidate: None,
+            count: 0,
+            sounding: None,
+        }
+    }
+    pub fn feed(&mut self, note: Option<u8>) -> Option<u8> {
+        if note == self.candidate {
+            self.count += 1;
+        } else {
+            self.candidate = note;
+            self.count = 1;
+        }
+        if self.count < STABLE_HOPS || self.candidate == self.sounding {
+            return None;
+        }
+        self.sounding = self.candidate;
+        self.sounding
+    }
+}
+
+// Runs the detector over a continuous stream with overlapping frames.
+pub struct PitchTracker {
+    yin: Yin,
+    a4: f64,
+    buffer: Vec<f32>,
+    pending: usize,
+}
+impl PitchTracker {
+    pub fn new(sample_rate: u32, a4: f64) -> Self {
+        PitchTracker {
+            yin: Yin::new(sample_rate),
+            a4,
+            buffer: Vec::with_capacity(FRAME),
+            pending: 0,
+        }
+    }
+    // Returns one result per hop of new samples.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<Option<DetectedPitch>> {
+        let mut results = Vec::new();
+        for sample in samples {
+            self.buffer.push(*sample);
+            self.pending += 1;
+            if self.buffer.len() >= FRAME && self.pending >= HOP {
+                self.pending = 0;
+                let start = self.buffer.len() - FRAME;
+                self.buffer.drain(..start);
+                let frequency = self.yin.detect(&self.buffer);
+                results.push(frequency.and_then(|f| DetectedPitch::from_frequency(f, self.a4)));
+            }
+        }
+        results
+    }
+}
+
+// Detects the pitch of every hop in a WAV file (first channel only).
+// Returns the time of each frame end in seconds.
+pub fn detect_wav(path: &Path, a4: f64) -> Result<Vec<(f64, Option<DetectedPitch>)>, hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    let samples: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let mono: Vec<f32> = samples.into_iter().step_by(channels).collect();
+    let mut tracker = PitchTracker::new(spec.sample_rate, a4);
+    let mut detected = Vec::new();
+    let mut position = 0;
+    for chunk in mono.chunks(HOP) {
+        position += chunk.len();
+        for result in tracker.push(chunk) {
+            detected.push((position as f64 / spec.sample_rate as f64, result));
+        }
+    }
+    Ok(detected)
+}
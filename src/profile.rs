@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use druid::{Data, Lens, Selector};
+use tracing::error;
+
+use super::naming::Naming;
+use super::stats::Stats;
+
+pub const SELECT: Selector<String> = Selector::new("rusted_big_note.select-profile");
+
+#[test]
+fn test_sanitize() {
+    assert_eq!(sanitize("Anna Petrova"), "AnnaPetrova");
+    assert_eq!(sanitize("../etc"), "etc");
+    assert_eq!(sanitize("ученик_1"), "ученик_1");
+    assert_eq!(sanitize("  "), "");
+}
+
+pub fn sanitize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+// All profiles live in the user data directory, one folder each.
+pub fn root() -> Option<PathBuf> {
+    let mut path = dirs::data_dir()?;
+    path.push("rusted_big_note");
+    path.push("profiles");
+    Some(path)
+}
+
+pub fn dir(name: &str) -> Option<PathBuf> {
+    let name = sanitize(name);
+    if name.is_empty() {
+        return None;
+    }
+    Some(root()?.join(name))
+}
+
+pub fn list() -> Vec<String> {
+    let entries = match root().map(std::fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return Vec::new(),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    names
+}
+
+#[derive(Clone, Data, Lens)]
+pub struct ProfileState {
+    pub name: String,
+    pub stats: Arc<Stats>,
+    #[data(same_fn = "PartialEq::eq")]
+    pub available: Vec<String>,
+    // Name typed into the "new profile" box.
+    pub new_name: String,
+    // Result of the last export.
+    pub message: String,
+}
+impl ProfileState {
+    pub fn new() -> Self {
+        ProfileState {
+            name: String::new(),
+            stats: Arc::new(Stats::new()),
+            available: list(),
+            new_name: String::new(),
+            message: String::new(),
+        }
+    }
+    pub fn stats_path(&self) -> Option<PathBuf> {
+        Some(dir(&self.name)?.join("stats.json"))
+    }
+    pub fn select(&mut self, name: &str) {
+        self.name = sanitize(name);
+        if let Some(dir) = dir(&self.name) {
+            if let Err(err) = std::fs::create_dir_all(&dir) {
+                error!("{}: {}", dir.display(), err);
+            }
+        }
+        // A new profile shows up in the list right away.
+        self.available = list();
+        self.new_name = String::new();
+        self.stats = Arc::new(match self.stats_path() {
+            Some(path) => Stats::load(&path),
+            None => Stats::new(),
+        });
+        self.message = String::new();
+    }
+    // Writes CSV and JSON summaries next to the profile data.
+    pub fn export(&self, naming: &Naming) -> std::io::Result<Vec<PathBuf>> {
+        let dir = dir(&self.name).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no profile selected")
+        })?;
+        std::fs::create_dir_all(&dir)?;
+        let csv = dir.join("summary.csv");
+        std::fs::write(&csv, self.stats.to_csv(naming))?;
+        let json = dir.join("summary.json");
+        std::fs::write(&json, self.stats.to_json(naming)?)?;
+        Ok(vec![csv, json])
+    }
+}
@@ -0,0 +1,366 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use druid::{Color, Size};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect as PdfRect, Ref};
+use usvg::{fontdb, TreeParsing, TreePostProc};
+
+use super::layout::{Glyphs, Placed, StaffLayout};
+#[cfg(test)]
+use super::note::{Key, Note, NoteAlt};
+use super::settings::Colors;
+#[cfg(test)]
+use super::settings::OttavaLimits;
+#[cfg(test)]
+use super::staff::ClefType;
+
+#[test]
+fn test_svg() {
+    let layout = StaffLayout::new(
+        Size::new(400.0, 300.0),
+        ClefType::Treble,
+        &Key::new(-1),
+        NoteAlt::Flat,
+        &[Note::new(60), Note::new(64)],
+        &OttavaLimits::default(),
+    );
+    let dark = super::settings::Theme::Dark.colors().unwrap();
+    let themed = svg(&layout, Size::new(400.0, 300.0), &dark);
+    assert!(themed.contains(&format!("fill=\"{}\"", hex(&dark.background))));
+    assert!(themed.contains(&format!("stroke=\"{}\"", hex(&dark.staff))));
+    assert!(!themed.contains("#000000"));
+    let high = StaffLayout::new(
+        Size::new(400.0, 300.0),
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &[Note::new(96)],
+        &OttavaLimits::default(),
+    );
+    assert!(svg(&high, Size::new(400.0, 300.0), &Colors::default()).contains(">8va</text>"));
+    let svg = svg(&layout, Size::new(400.0, 300.0), &Colors::default());
+    assert!(svg.contains("width=\"400\" height=\"300\""));
+    assert!(svg.contains("fill=\"#ffffff\""));
+    // The document, the clef, the flat of the signature and two heads.
+    assert_eq!(svg.matches("<svg").count(), 5);
+    assert_eq!(svg.matches("<line").count(), 6);
+    assert_eq!(svg.matches("<?xml").count(), 1);
+    assert!(!svg.contains("width=\"16.847145mm\""));
+}
+
+#[test]
+fn test_font_paths() {
+    let font = super::smufl::test_font();
+    let size = Size::new(400.0, 300.0);
+    let layout = StaffLayout::with_glyphs(
+        Glyphs::Font(&font),
+        size,
+        ClefType::Treble,
+        &Key::new(0),
+        NoteAlt::Sharp,
+        &[Note::new(61)],
+        &OttavaLimits::default(),
+        false,
+    );
+    let staff = staff_with(&layout, Glyphs::Font(&font), &Colors::default());
+    // Clef, head and sharp as outlines, a ledger line below the staff.
+    assert_eq!(staff.matches("<path").count(), 3);
+    assert!(!staff.contains("<svg"));
+    assert_eq!(staff.matches("<line").count(), 6);
+    let mut svg = document(size);
+    svg.push_str(&staff);
+    svg.push_str("</svg>\n");
+    assert!(tree(&svg, &fontdb::Database::new()).is_ok());
+}
+
+#[test]
+fn test_raster_and_pdf() {
+    let size = Size::new(200.0, 150.0);
+    let layout = StaffLayout::new(
+        size,
+        ClefType::Bass,
+        &Key::new(2),
+        NoteAlt::Sharp,
+        &[Note::new(50)],
+        &OttavaLimits::default(),
+    );
+    let svg = svg(&layout, size, &Colors::default());
+    assert_eq!(&png(&svg, size).unwrap()[1..4], b"PNG");
+    let pdf = pdf(&[svg.clone(), svg], size).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+    let text = String::from_utf8_lossy(&pdf);
+    assert!(text.contains("/Count 2"));
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(escape("Do & Re <8va>"), "Do &amp; Re &lt;8va&gt;");
+    assert_eq!(escape("\"l'a\""), "&quot;l&apos;a&quot;");
+    assert_eq!(escape("C♯4"), "C♯4");
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Svg,
+    Png,
+    Pdf,
+}
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "svg" => Some(Format::Svg),
+            "png" => Some(Format::Png),
+            "pdf" => Some(Format::Pdf),
+            _ => None,
+        }
+    }
+}
+
+// Staff image as a standalone SVG document, drawn from the same layout
+// and glyphs as the Staff widget.
+pub fn svg(layout: &StaffLayout, size: Size, colors: &Colors) -> String {
+    let mut out = document(size);
+    let _ = writeln!(
+        out,
+        "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+        hex(&colors.background)
+    );
+    out.push_str(&staff(layout, colors));
+    out.push_str("</svg>\n");
+    out
+}
+
+// Opening of an SVG document, the caller closes the root element.
+pub fn document(size: Size) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = size.width,
+        h = size.height
+    )
+}
+
+fn hex(color: &Color) -> String {
+    let (r, g, b, _) = color.as_rgba8();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Lines and glyphs of a staff, without a background.
+pub fn staff(layout: &StaffLayout, colors: &Colors) -> String {
+    staff_with(layout, Glyphs::current(), colors)
+}
+
+fn staff_with(layout: &StaffLayout, glyphs: Glyphs, colors: &Colors) -> String {
+    let mut out = String::new();
+    let lines = layout
+        .lines
+        .iter()
+        .map(|line| (line, layout.line_width))
+        .chain(
+            layout
+                .ledgers
+                .iter()
+                .map(|line| (line, layout.ledger_width)),
+        )
+        .chain(
+            layout
+                .bracket
+                .iter()
+                .flat_map(|bracket| bracket.dashes.iter())
+                .map(|line| (line, layout.line_width)),
+        );
+    for (line, width) in lines {
+        let _ = writeln!(
+            out,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            line.p0.x,
+            line.p0.y,
+            line.p1.x,
+            line.p1.y,
+            hex(&colors.staff),
+            width
+        );
+    }
+    let placed = std::iter::once(&layout.clef)
+        .chain(layout.signature.iter())
+        .map(|placed| (placed, &colors.staff))
+        .chain(layout.notes.iter().map(|placed| (placed, &colors.notehead)))
+        .chain(
+            layout
+                .accidentals
+                .iter()
+                .map(|placed| (placed, &colors.accidental)),
+        );
+    for (placed, color) in placed {
+        match glyphs.path(placed) {
+            Some(path) => {
+                let _ = writeln!(
+                    out,
+                    "<path d=\"{}\" fill=\"{}\"/>",
+                    path.to_svg(),
+                    hex(color)
+                );
+            }
+            None => out.push_str(&nested(placed, color)),
+        }
+    }
+    if let Some(bracket) = &layout.bracket {
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" font-family=\"serif\" font-style=\"italic\" \
+             font-weight=\"bold\" font-size=\"{}\" fill=\"{}\">{}</text>",
+            bracket.label.x0,
+            bracket.label.y1,
+            bracket.label.height(),
+            hex(&colors.staff),
+            escape(bracket.ottava.label())
+        );
+    }
+    out
+}
+
+// Text content and attribute values with the XML special characters
+// replaced.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Glyph file as a nested svg element filling its box. The root element
+// keeps its namespaces, only the size attributes are replaced.
+fn nested(placed: &Placed, color: &Color) -> String {
+    let source = placed.glyph.tinted(color);
+    let source = &source[source.find("<svg").unwrap_or(0)..];
+    let tag_end = source.find('>').unwrap_or(0);
+    let mut tag = String::from(&source[..tag_end]);
+    for name in ["width", "height"].iter() {
+        tag = without_attribute(&tag, name);
+    }
+    let rect = placed.rect;
+    format!(
+        "<svg x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}{}\n",
+        rect.x0,
+        rect.y0,
+        rect.width(),
+        rect.height(),
+        &tag[4..],
+        source[tag_end..].trim_end()
+    )
+}
+
+fn without_attribute(tag: &str, name: &str) -> String {
+    let pattern = format!("{}=\"", name);
+    let mut search = 0;
+    while let Some(found) = tag[search..].find(&pattern) {
+        let start = search + found;
+        // Skip names that only end the same, like inkscape:window-width.
+        if !tag[..start].ends_with(char::is_whitespace) {
+            search = start + pattern.len();
+            continue;
+        }
+        let value_start = start + pattern.len();
+        let end = match tag[value_start..].find('"') {
+            Some(end) => value_start + end + 1,
+            None => break,
+        };
+        return format!("{}{}", tag[..start].trim_end(), &tag[end..]);
+    }
+    String::from(tag)
+}
+
+// Parses a document for rasterizing or PDF conversion. Text is turned
+// into paths with the given fonts.
+pub fn tree(svg: &str, fonts: &fontdb::Database) -> Result<usvg::Tree, String> {
+    let mut tree =
+        usvg::Tree::from_str(svg, &usvg::Options::default()).map_err(|err| err.to_string())?;
+    tree.postprocess(usvg::PostProcessingSteps::default(), fonts);
+    Ok(tree)
+}
+
+pub fn system_fonts() -> fontdb::Database {
+    let mut fonts = fontdb::Database::new();
+    fonts.load_system_fonts();
+    fonts
+}
+
+pub fn pixmap(
+    svg: &str,
+    size: Size,
+    fonts: &fontdb::Database,
+) -> Result<tiny_skia::Pixmap, String> {
+    let tree = tree(svg, fonts)?;
+    let mut pixmap = tiny_skia::Pixmap::new(size.width.ceil() as u32, size.height.ceil() as u32)
+        .ok_or_else(|| format!("cannot render an image of {}x{}", size.width, size.height))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+// Text needs the system fonts, as in PDF.
+pub fn png(svg: &str, size: Size) -> Result<Vec<u8>, String> {
+    pixmap(svg, size, &system_fonts())?
+        .encode_png()
+        .map_err(|err| err.to_string())
+}
+
+// Documents of the same size as the pages of one PDF. Text needs the
+// system fonts.
+pub fn pdf(pages: &[String], size: Size) -> Result<Vec<u8>, String> {
+    let fonts = system_fonts();
+    let catalog_id = Ref::new(1);
+    let tree_id = Ref::new(2);
+    let mut next_id = Ref::new(3);
+    let name = Name(b"S1");
+    let mut pdf = Pdf::new();
+    let mut page_ids = Vec::new();
+    for page in pages {
+        let tree = tree(page, &fonts)?;
+        let page_id = next_id;
+        let content_id = Ref::new(page_id.get() + 1);
+        let svg_id = Ref::new(page_id.get() + 2);
+        next_id = svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut pdf, svg_id);
+        let mut writer = pdf.page(page_id);
+        writer.media_box(PdfRect::new(
+            0.0,
+            0.0,
+            size.width as f32,
+            size.height as f32,
+        ));
+        writer.parent(tree_id);
+        writer.contents(content_id);
+        writer.resources().x_objects().pair(name, svg_id);
+        writer.finish();
+        let mut content = Content::new();
+        content.transform([size.width as f32, 0.0, 0.0, size.height as f32, 0.0, 0.0]);
+        content.x_object(name);
+        pdf.stream(content_id, &content.finish());
+        page_ids.push(page_id);
+    }
+    pdf.catalog(catalog_id).pages(tree_id);
+    let count = page_ids.len() as i32;
+    pdf.pages(tree_id).kids(page_ids).count(count);
+    Ok(pdf.finish())
+}
+
+// Writes the staff, the format follows the file extension.
+pub fn write(path: &Path, size: Size, layout: &StaffLayout, colors: &Colors) -> Result<(), String> {
+    let format = Format::from_path(path)
+        .ok_or_else(|| format!("{}: expected a .svg, .png or .pdf file", path.display()))?;
+    let svg = svg(layout, size, colors);
+    let bytes = match format {
+        Format::Svg => svg.into_bytes(),
+        Format::Png => png(&svg, size)?,
+        Format::Pdf => pdf(&[svg], size)?,
+    };
+    std::fs::write(path, bytes).map_err(|err| format!("{}: {}", path.display(), err))
+}
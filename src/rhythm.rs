@@ -0,0 +1,409 @@
+use druid::{Data, Lens};
+
+// Quantization grid: sixteenth notes. Every duration is expressed in ticks
+// of this grid, so a whole note is 16 ticks.
+pub const TICKS_PER_QUARTER: u32 = 4;
+const CLOCKS_PER_QUARTER: usize = 24;
+
+#[test]
+fn test_clock_tempo() {
+    let mut clock = ClockTempo::new();
+    assert_eq!(clock.bpm(), None);
+    // 120 bpm: a quarter lasts 500ms, one clock every 500_000 / 24 us.
+    for i in 0..(CLOCKS_PER_QUARTER * 2) as u64 {
+        clock.tick(i * 500_000 / 24);
+    }
+    let bpm = clock.bpm().unwrap();
+    assert!((bpm - 120.0).abs() < 0.5);
+    clock.reset();
+    assert_eq!(clock.bpm(), None);
+    // A reconnected port stamps from zero again, the clock now at
+    // 60 bpm.
+    for i in 0..(CLOCKS_PER_QUARTER * 2) as u64 {
+        clock.tick(10_000_000 + i * 500_000 / 24);
+    }
+    for i in 0..3 {
+        clock.tick(i * 1_000_000 / 24);
+    }
+    let bpm = clock.bpm().unwrap();
+    assert!((bpm - 60.0).abs() < 0.5);
+}
+
+#[test]
+fn test_split_ticks() {
+    assert_eq!(Value::split(16), vec![Value::new(Duration::Whole, false)]);
+    assert_eq!(Value::split(6), vec![Value::new(Duration::Quarter, true)]);
+    assert_eq!(
+        Value::split(5),
+        vec![
+            Value::new(Duration::Quarter, false),
+            Value::new(Duration::Sixteenth, false)
+        ]
+    );
+    for ticks in 1..=16 {
+        let sum: u32 = Value::split(ticks).iter().map(|v| v.ticks()).sum();
+        assert_eq!(sum, ticks);
+    }
+}
+
+#[test]
+fn test_transcribe() {
+    let tempo = Tempo::new(60.0);
+    let time = TimeSignature::new(4, 4);
+    // One second per quarter, slightly sloppy playing.
+    let notes = vec![
+        PlayedNote::new(60, 10_000, Some(980_000)),
+        PlayedNote::new(62, 1_020_000, Some(1_490_000)),
+        PlayedNote::new(64, 1_510_000, Some(1_990_000)),
+        PlayedNote::new(65, 3_000_000, Some(7_000_000)),
+    ];
+    let measures = transcribe(&notes, 0, 7_000_000, &tempo, &time);
+    assert_eq!(measures.len(), 2);
+    let first: Vec<(Vec<u8>, u32, bool)> = measures[0]
+        .events
+        .iter()
+        .map(|e| (e.keys.clone(), e.value.ticks(), e.tied))
+        .collect();
+    assert_eq!(
+        first,
+        vec![
+            (vec![60], 4, false),
+            (vec![62], 2, false),
+            (vec![64], 2, false),
+            (vec![], 4, false),
+            (vec![65], 4, true),
+        ]
+    );
+    assert_eq!(measures[1].events.len(), 2);
+    assert_eq!(measures[1].events[0].value.ticks(), 12);
+    assert_eq!(measures[1].events[0].keys, vec![65]);
+    assert!(measures[1].events[1].is_rest());
+}
+
+#[test]
+fn test_chords_and_beams() {
+    let tempo = Tempo::new(60.0);
+    let time = TimeSignature::new(2, 4);
+    let notes = vec![
+        PlayedNote::new(60, 0, Some(500_000)),
+        PlayedNote::new(64, 5_000, Some(500_000)),
+        PlayedNote::new(62, 500_000, Some(1_000_000)),
+        PlayedNote::new(64, 1_000_000, Some(2_000_000)),
+    ];
+    let measures = transcribe(&notes, 0, 2_000_000, &tempo, &time);
+    assert_eq!(measures.len(), 1);
+    assert_eq!(measures[0].events[0].keys, vec![60, 64]);
+    assert_eq!(measures[0].beams(&time), vec![0..2]);
+}
+
+#[test]
+fn test_compound_beats() {
+    let time = TimeSignature::new(6, 8);
+    assert_eq!(time.measure_ticks(), 12);
+    assert_eq!(time.beat_ticks(), 6);
+    let time = TimeSignature::new(3, 4);
+    assert_eq!(time.measure_ticks(), 12);
+    assert_eq!(time.beat_ticks(), 4);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Data)]
+pub enum Duration {
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+}
+impl Duration {
+    pub fn ticks(&self) -> u32 {
+        match *self {
+            Duration::Whole => TICKS_PER_QUARTER * 4,
+            Duration::Half => TICKS_PER_QUARTER * 2,
+            Duration::Quarter => TICKS_PER_QUARTER,
+            Duration::Eighth => TICKS_PER_QUARTER / 2,
+            Duration::Sixteenth => TICKS_PER_QUARTER / 4,
+        }
+    }
+    pub fn flags(&self) -> usize {
+        match *self {
+            Duration::Eighth => 1,
+            Duration::Sixteenth => 2,
+            _ => 0,
+        }
+    }
+    pub fn has_stem(&self) -> bool {
+        *self != Duration::Whole
+    }
+    pub fn is_hollow(&self) -> bool {
+        *self == Duration::Whole || *self == Duration::Half
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Data)]
+pub struct Value {
+    pub duration: Duration,
+    pub dotted: bool,
+}
+impl Value {
+    pub const fn new(duration: Duration, dotted: bool) -> Self {
+        Value { duration, dotted }
+    }
+    pub fn ticks(&self) -> u32 {
+        let ticks = self.duration.ticks();
+        if self.dotted {
+            ticks + ticks / 2
+        } else {
+            ticks
+        }
+    }
+    // Longest values first, so the greedy split below gives the shortest
+    // sequence of tied values.
+    const ALL: [Value; 8] = [
+        Value::new(Duration::Whole, false),
+        Value::new(Duration::Half, true),
+        Value::new(Duration::Half, false),
+        Value::new(Duration::Quarter, true),
+        Value::new(Duration::Quarter, false),
+        Value::new(Duration::Eighth, true),
+        Value::new(Duration::Eighth, false),
+        Value::new(Duration::Sixteenth, false),
+    ];
+    pub fn split(mut ticks: u32) -> Vec<Value> {
+        let mut values = Vec::new();
+        while ticks > 0 {
+            let value = *Self::ALL.iter().find(|v| v.ticks() <= ticks).unwrap();
+            ticks -= value.ticks();
+            values.push(value);
+        }
+        values
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Data, Lens)]
+pub struct Tempo {
+    pub bpm: f64,
+}
+impl Tempo {
+    pub fn new(bpm: f64) -> Self {
+        Tempo { bpm }
+    }
+    pub fn tick_us(&self) -> f64 {
+        60_000_000.0 / self.bpm / TICKS_PER_QUARTER as f64
+    }
+    pub fn ticks(&self, us: u64) -> u32 {
+        (us as f64 / self.tick_us()).round() as u32
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Data)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+}
+impl TimeSignature {
+    pub fn new(numerator: u8, denominator: u8) -> Self {
+        TimeSignature {
+            numerator,
+            denominator,
+        }
+    }
+    pub fn measure_ticks(&self) -> u32 {
+        self.numerator as u32 * TICKS_PER_QUARTER * 4 / self.denominator as u32
+    }
+    pub fn is_compound(&self) -> bool {
+        self.denominator == 8 && self.numerator % 3 == 0 && self.numerator > 3
+    }
+    pub fn beat_ticks(&self) -> u32 {
+        let ticks = TICKS_PER_QUARTER * 4 / self.denominator as u32;
+        if self.is_compound() {
+            ticks * 3
+        } else {
+            ticks
+        }
+    }
+}
+impl std::fmt::Display for TimeSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+// Estimates tempo from MIDI clock (24 pulses per quarter note),
+// averaging over the last quarter note.
+#[derive(Clone, Debug, PartialEq, Data)]
+pub struct ClockTempo {
+    #[data(same_fn = "PartialEq::eq")]
+    stamps: Vec<u64>,
+}
+impl ClockTempo {
+    pub fn new() -> Self {
+        ClockTempo { stamps: Vec::new() }
+    }
+    // Stamps start again from zero when the port is connected anew, the
+    // average starts over with them.
+    pub fn tick(&mut self, stamp: u64) {
+        if self.stamps.last().is_some_and(|last| stamp < *last) {
+            self.reset();
+        }
+        self.stamps.push(stamp);
+        if self.stamps.len() > CLOCKS_PER_QUARTER + 1 {
+            self.stamps.remove(0);
+        }
+    }
+    pub fn reset(&mut self) {
+        self.stamps.clear();
+    }
+    pub fn bpm(&self) -> Option<f64> {
+        if self.stamps.len() < 2 {
+            return None;
+        }
+        let first = self.stamps.first().unwrap();
+        let last = self.stamps.last().unwrap();
+        let pulse = last.checked_sub(*first)? as f64 / (self.stamps.len() - 1) as f64;
+        if pulse <= 0.0 {
+            return None;
+        }
+        Some(60_000_000.0 / (pulse * CLOCKS_PER_QUARTER as f64))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Data)]
+pub struct PlayedNote {
+    pub key: u8,
+    pub on: u64,
+    pub off: Option<u64>,
+}
+impl PlayedNote {
+    pub fn new(key: u8, on: u64, off: Option<u64>) -> Self {
+        PlayedNote { key, on, off }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Data)]
+pub struct RhythmEvent {
+    // Empty for rests.
+    #[data(same_fn = "PartialEq::eq")]
+    pub keys: Vec<u8>,
+    pub value: Value,
+    // Tied to the next event (a note crossing a barline or
+    // a length that has no single value).
+    pub tied: bool,
+}
+impl RhythmEvent {
+    pub fn is_rest(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measure {
+    pub events: Vec<RhythmEvent>,
+}
+impl Measure {
+    // Ranges of consecutive eighth or shorter notes inside one beat.
+    pub fn beams(&self, time: &TimeSignature) -> Vec<std::ops::Range<usize>> {
+        let beat = time.beat_ticks();
+        let mut groups = Vec::new();
+        let mut start: Option<usize> = None;
+        let mut pos = 0;
+        for (idx, event) in self.events.iter().enumerate() {
+            let beamable = !event.is_rest() && event.value.duration.flags() > 0;
+            if let Some(first) = start {
+                if !beamable || pos % beat == 0 {
+                    if idx - first > 1 {
+                        groups.push(first..idx);
+                    }
+                    start = None;
+                }
+            }
+            if beamable && start.is_none() {
+                start = Some(idx);
+            }
+            pos += event.value.ticks();
+        }
+        if let Some(first) = start {
+            if self.events.len() - first > 1 {
+                groups.push(first..self.events.len());
+            }
+        }
+        groups
+    }
+}
+
+// Turns raw note-on/off stamps (in microseconds) into measures of
+// quantized notes and rests. Notes starting on the same grid tick
+// form a chord, a new onset cuts the previous one.
+pub fn transcribe(
+    notes: &[PlayedNote],
+    start: u64,
+    now: u64,
+    tempo: &Tempo,
+    time: &TimeSignature,
+) -> Vec<Measure> {
+    let mut onsets: Vec<(u32, u32, Vec<u8>)> = Vec::new();
+    for note in notes {
+        let on = tempo.ticks(note.on.saturating_sub(start));
+        let mut off = tempo.ticks(note.off.unwrap_or(now).saturating_sub(start));
+        if off <= on {
+            off = on + 1;
+        }
+        match onsets.iter_mut().find(|(tick, _, _)| *tick == on) {
+            Some((_, end, keys)) => {
+                *end = (*end).max(off);
+                if !keys.contains(&note.key) {
+                    keys.push(note.key);
+                }
+            }
+            None => onsets.push((on, off, vec![note.key])),
+        }
+    }
+    onsets.sort_by_key(|(tick, _, _)| *tick);
+    for idx in 1..onsets.len() {
+        let next = onsets[idx].0;
+        let prev = &mut onsets[idx - 1];
+        prev.1 = prev.1.min(next);
+    }
+    for (_, _, keys) in onsets.iter_mut() {
+        keys.sort();
+    }
+
+    let measure_ticks = time.measure_ticks();
+    let mut measures: Vec<Measure> = Vec::new();
+    let mut pos = 0;
+    let push = |keys: Vec<u8>, from: u32, to: u32, measures: &mut Vec<Measure>| {
+        let mut from = from;
+        while from < to {
+            let measure_nr = (from / measure_ticks) as usize;
+            while measures.len() <= measure_nr {
+                measures.push(Measure { events: Vec::new() });
+            }
+            let bar_end = (measure_nr as u32 + 1) * measure_ticks;
+            let end = to.min(bar_end);
+            let values = Value::split(end - from);
+            let last = values.len() - 1;
+            for (idx, value) in values.into_iter().enumerate() {
+                let tied = !keys.is_empty() && (idx < last || end < to);
+                measures[measure_nr].events.push(RhythmEvent {
+                    keys: keys.clone(),
+                    value,
+                    tied,
+                });
+            }
+            from = end;
+        }
+    };
+    for (on, off, keys) in onsets {
+        if on > pos {
+            push(Vec::new(), pos, on, &mut measures);
+        }
+        push(keys, on, off, &mut measures);
+        pos = off;
+    }
+    // Rests up to now, padded to the end of the last measure.
+    let end = pos.max(tempo.ticks(now.saturating_sub(start)));
+    let end = end.div_ceil(measure_ticks) * measure_ticks;
+    if end > pos {
+        push(Vec::new(), pos, end, &mut measures);
+    }
+    measures
+}
@@ -0,0 +1,703 @@
+#[cfg(feature = "serialize")]
+use std::path::Path;
+use std::path::PathBuf;
+
+use druid::{Color, Data, Env, Key as EnvKey, Lens};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serialize")]
+use tracing::{error, warn};
+
+use super::i18n::Language;
+use super::naming::{Octaves, System};
+use super::staff::{ClefType, Degrees, Reference, Spelling};
+use super::transpose::Instrument;
+use super::tuner::Temperament;
+
+/// Theme colours. STAFF is for the lines, clefs and key signatures,
+/// HIGHLIGHT marks keys held down and SUSTAINED the shown key after it
+/// was let go. OUTSIDE is for played notes outside the key or scale the
+/// staff checks against.
+pub const BACKGROUND: EnvKey<Color> = EnvKey::new("rusted_big_note.background");
+pub const STAFF: EnvKey<Color> = EnvKey::new("rusted_big_note.staff");
+pub const NOTEHEAD: EnvKey<Color> = EnvKey::new("rusted_big_note.notehead");
+pub const ACCIDENTAL: EnvKey<Color> = EnvKey::new("rusted_big_note.accidental");
+pub const HIGHLIGHT: EnvKey<Color> = EnvKey::new("rusted_big_note.highlight");
+pub const SUSTAINED: EnvKey<Color> = EnvKey::new("rusted_big_note.sustained");
+pub const OUTSIDE: EnvKey<Color> = EnvKey::new("rusted_big_note.outside");
+// Ottava lines, see OttavaLimits.
+pub const OTTAVA: EnvKey<bool> = EnvKey::new("rusted_big_note.ottava");
+pub const OTTAVA_ABOVE: EnvKey<u64> = EnvKey::new("rusted_big_note.ottava-above");
+pub const OTTAVA_BELOW: EnvKey<u64> = EnvKey::new("rusted_big_note.ottava-below");
+
+#[cfg(feature = "serialize")]
+#[test]
+fn test_round_trip() {
+    let mut settings = Settings {
+        language: Language::Russian,
+        midi_port: String::from("Keystation 49"),
+        font: String::from("/usr/share/fonts/Bravura.otf"),
+        reference: Reference::Scale,
+        degrees: Degrees::Functions,
+        ..Settings::default()
+    };
+    settings.trainer.clef = ClefType::Bass;
+    settings.caption.frequency = true;
+    settings.ottava.above = 5;
+    settings.colors.highlight = Color::rgb8(0x12, 0x34, 0x56);
+    settings.window.x = Some(100.0);
+    let text = toml::to_string_pretty(&settings).unwrap();
+    assert!(text.contains("#123456"));
+    let value: toml::Value = text.parse().unwrap();
+    assert_eq!(value["trainer"]["low"].as_str(), Some("C3"));
+    let numbered = Settings::parse("[trainer]\nlow = 48\n", "test");
+    assert_eq!(numbered.trainer.low, 48);
+    assert_eq!(Settings::parse(&text, "test"), settings);
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn test_malformed_entries() {
+    let settings = Settings::parse(
+        "language = \"klingon\"\n\
+         naming = \"german\"\n\
+         unknown = 1\n\
+         [trainer]\n\
+         clef = \"bass\"\n\
+         low = \"C9\"\n\
+         high = \"G3\"\n\
+         [colors]\n\
+         background = \"#nothex\"\n",
+        "test",
+    );
+    let defaults = Settings::default();
+    assert_eq!(settings.language, defaults.language);
+    assert_eq!(settings.naming, System::German);
+    assert_eq!(settings.trainer.clef, ClefType::Bass);
+    assert_eq!(settings.trainer.low, defaults.trainer.low);
+    assert_eq!(settings.trainer.high, 67);
+    assert_eq!(settings.colors, defaults.colors);
+    assert_eq!(Settings::parse("not toml [", "test"), defaults);
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn test_themes() {
+    for theme in Theme::ALL.iter() {
+        match theme.colors() {
+            Some(colors) => assert_ne!(colors.background, colors.staff, "{:?}", theme),
+            None => assert_eq!(*theme, Theme::Custom),
+        }
+    }
+    assert_eq!(Theme::Light.colors(), Some(Colors::default()));
+    let path = std::env::temp_dir().join(format!(
+        "rusted_big_note_test_theme_{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(&path, "background = \"#000000\"\nnotehead = \"#ffff00\"\n").unwrap();
+    let settings = Settings {
+        theme: Theme::Custom,
+        theme_file: path.display().to_string(),
+        ..Settings::default()
+    };
+    let colors = settings.theme_colors();
+    assert_eq!(colors.background, Color::BLACK);
+    assert_eq!(colors.notehead, Color::rgb8(0xff, 0xff, 0x00));
+    assert_eq!(colors.staff, Colors::default().staff);
+    // The colors table stays as it was, for the other themes.
+    assert_eq!(settings.colors, Colors::default());
+    assert_eq!(settings.custom_colors(), colors);
+    std::fs::write(&path, "background = 12\n").unwrap();
+    assert!(Colors::load(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_colors_from_env() {
+    // An app embedding the staff may not set the colours.
+    let mut env = Env::empty();
+    assert_eq!(Colors::from_env(&env), Colors::default());
+    env.set(STAFF, Color::rgb8(0x20, 0x20, 0x20));
+    let colors = Colors::from_env(&env);
+    assert_eq!(colors.staff, Color::rgb8(0x20, 0x20, 0x20));
+    assert_eq!(colors.notehead, Colors::default().notehead);
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn test_changes() {
+    let file = Settings::default();
+    // The app was started with a clef and port on the command line.
+    let started = Settings {
+        clef: ClefType::Bass,
+        midi_port: String::from("Keystation 49"),
+        ..file.clone()
+    };
+    let mut changed = started.clone();
+    changed.language = Language::Russian;
+    changed.window.x = Some(10.0);
+    let saved = file.with_changes(&started, &changed);
+    assert_eq!(saved.clef, ClefType::Treble);
+    assert_eq!(saved.midi_port, "");
+    assert_eq!(saved.language, Language::Russian);
+    assert_eq!(saved.window.x, Some(10.0));
+    // A changed override is saved like any other setting.
+    let mut moved = changed.clone();
+    moved.clef = ClefType::Auto;
+    moved.window.x = None;
+    let saved = saved.with_changes(&changed, &moved);
+    assert_eq!(saved.clef, ClefType::Auto);
+    assert_eq!(saved.window.x, None);
+    assert_eq!(saved.window.width, file.window.width);
+}
+
+#[cfg(feature = "serialize")]
+mod hex {
+    use druid::Color;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        let (r, g, b, _) = color.as_rgba8();
+        serializer.serialize_str(&format!("#{:02x}{:02x}{:02x}", r, g, b))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Color::from_hex_str(&text).map_err(|_| D::Error::custom(format!("bad colour {}", text)))
+    }
+}
+
+// MIDI numbers are written as note names and read from either.
+#[cfg(feature = "serialize")]
+mod note_name {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use crate::note::{Note, NoteAlt};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NameOrNumber {
+        Name(String),
+        Number(i64),
+    }
+
+    pub fn serialize<S: Serializer>(midi_nr: &u8, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&Note::new(*midi_nr).name(NoteAlt::Sharp))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+        let text = match NameOrNumber::deserialize(deserializer)? {
+            NameOrNumber::Name(text) => text,
+            NameOrNumber::Number(nr) => nr.to_string(),
+        };
+        text.parse::<Note>()
+            .map(|note| note.midi_nr())
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct Trainer {
+    pub clef: ClefType,
+    #[cfg_attr(feature = "serialize", serde(with = "note_name"))]
+    pub low: u8,
+    #[cfg_attr(feature = "serialize", serde(with = "note_name"))]
+    pub high: u8,
+    // Key signature in fifths, negative for flats.
+    pub key: i8,
+    pub accidentals: bool,
+    pub spaced: bool,
+}
+impl Default for Trainer {
+    fn default() -> Self {
+        Trainer {
+            clef: ClefType::Treble,
+            low: 60,
+            high: 79,
+            key: 0,
+            accidentals: false,
+            spaced: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct Transposition {
+    pub instrument: Instrument,
+    pub key: i8,
+    pub from_written: bool,
+    pub second_staff: bool,
+}
+impl Default for Transposition {
+    fn default() -> Self {
+        Transposition {
+            instrument: Instrument::Concert,
+            key: 0,
+            from_written: false,
+            second_staff: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct Tuner {
+    pub a4: f64,
+    pub temperament: Temperament,
+    pub tonic: u8,
+}
+impl Default for Tuner {
+    fn default() -> Self {
+        Tuner {
+            a4: 440.0,
+            temperament: Temperament::Equal,
+            tonic: 0,
+        }
+    }
+}
+
+// Large label under the staff of the note view.
+#[derive(Clone, Data, Lens, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct Caption {
+    pub name: bool,
+    pub frequency: bool,
+    pub midi_number: bool,
+    // Font family, empty for the default serif font.
+    pub font: String,
+    // Height of the label relative to the staff.
+    pub size: f64,
+}
+impl Default for Caption {
+    fn default() -> Self {
+        Caption {
+            name: true,
+            frequency: false,
+            midi_number: false,
+            font: String::new(),
+            size: 0.5,
+        }
+    }
+}
+impl Caption {
+    pub fn is_shown(&self) -> bool {
+        (self.name || self.frequency || self.midi_number) && self.size > 0.0
+    }
+}
+
+// Notes needing more ledger lines than these above or below the staff
+// are written an octave or two nearer to it under an 8va, 8vb, 15ma or
+// 15mb line.
+#[derive(Clone, Data, Lens, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct OttavaLimits {
+    pub enabled: bool,
+    pub above: u8,
+    pub below: u8,
+}
+impl Default for OttavaLimits {
+    fn default() -> Self {
+        OttavaLimits {
+            enabled: true,
+            above: 3,
+            below: 3,
+        }
+    }
+}
+impl OttavaLimits {
+    pub fn set_env(&self, env: &mut Env) {
+        env.set(OTTAVA, self.enabled);
+        env.set(OTTAVA_ABOVE, self.above as u64);
+        env.set(OTTAVA_BELOW, self.below as u64);
+    }
+    // The defaults for keys that were not set.
+    pub fn from_env(env: &Env) -> Self {
+        let default = Self::default();
+        let limit = |key: EnvKey<u64>, default: u8| env.try_get(key).map_or(default, |n| n as u8);
+        OttavaLimits {
+            enabled: env.try_get(OTTAVA).unwrap_or(default.enabled),
+            above: limit(OTTAVA_ABOVE, default.above),
+            below: limit(OTTAVA_BELOW, default.below),
+        }
+    }
+    // No ottava lines, every note on ledger lines.
+    pub fn off() -> Self {
+        OttavaLimits {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Theme {
+    Light,
+    Dark,
+    // Yellow on black, for projectors and bright rooms.
+    HighContrast,
+    // Okabe-Ito colours that stay apart with any colour vision.
+    ColorBlind,
+    // The colors table or the theme file.
+    Custom,
+}
+impl Theme {
+    pub const ALL: [Theme; 5] = [
+        Theme::Light,
+        Theme::Dark,
+        Theme::HighContrast,
+        Theme::ColorBlind,
+        Theme::Custom,
+    ];
+    // Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Theme::Light => "theme-light",
+            Theme::Dark => "theme-dark",
+            Theme::HighContrast => "theme-high-contrast",
+            Theme::ColorBlind => "theme-color-blind",
+            Theme::Custom => "theme-custom",
+        }
+    }
+    // Colours of the preset, none for a custom theme.
+    pub fn colors(&self) -> Option<Colors> {
+        let rgb = Color::rgb8;
+        match self {
+            Theme::Light => Some(Colors::default()),
+            Theme::Dark => Some(Colors {
+                background: rgb(0x1e, 0x1e, 0x1e),
+                staff: rgb(0xc8, 0xc8, 0xc8),
+                notehead: rgb(0xf0, 0xf0, 0xf0),
+                accidental: rgb(0xf0, 0xf0, 0xf0),
+                highlight: rgb(0x4a, 0x90, 0xd9),
+                sustained: rgb(0x2c, 0x55, 0x80),
+                outside: rgb(0xff, 0x6b, 0x6b),
+            }),
+            Theme::HighContrast => Some(Colors {
+                background: Color::BLACK,
+                staff: Color::WHITE,
+                notehead: rgb(0xff, 0xff, 0x00),
+                accidental: rgb(0xff, 0xff, 0x00),
+                highlight: rgb(0x00, 0xff, 0xff),
+                sustained: rgb(0x00, 0x80, 0x80),
+                outside: rgb(0xff, 0x00, 0xff),
+            }),
+            Theme::ColorBlind => Some(Colors {
+                background: Color::WHITE,
+                staff: Color::BLACK,
+                notehead: Color::BLACK,
+                accidental: rgb(0xd5, 0x5e, 0x00),
+                highlight: rgb(0x00, 0x72, 0xb2),
+                sustained: rgb(0xe6, 0x9f, 0x00),
+                outside: rgb(0xcc, 0x79, 0xa7),
+            }),
+            Theme::Custom => None,
+        }
+    }
+}
+
+/// A theme file has the entries of the colors table, missing ones keep
+/// the light colours.
+#[derive(Clone, Data, Lens, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct Colors {
+    #[cfg_attr(feature = "serialize", serde(with = "hex"))]
+    pub background: Color,
+    #[cfg_attr(feature = "serialize", serde(with = "hex"))]
+    pub staff: Color,
+    #[cfg_attr(feature = "serialize", serde(with = "hex"))]
+    pub notehead: Color,
+    #[cfg_attr(feature = "serialize", serde(with = "hex"))]
+    pub accidental: Color,
+    #[cfg_attr(feature = "serialize", serde(with = "hex"))]
+    pub highlight: Color,
+    #[cfg_attr(feature = "serialize", serde(with = "hex"))]
+    pub sustained: Color,
+    #[cfg_attr(feature = "serialize", serde(with = "hex"))]
+    pub outside: Color,
+}
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            background: Color::WHITE,
+            staff: Color::BLACK,
+            notehead: Color::BLACK,
+            accidental: Color::BLACK,
+            highlight: Color::rgb8(0x4a, 0x90, 0xd9),
+            sustained: Color::rgb8(0xa6, 0xc8, 0xec),
+            outside: Color::rgb8(0xd9, 0x35, 0x35),
+        }
+    }
+}
+impl Colors {
+    #[cfg(feature = "serialize")]
+    pub fn load(path: &Path) -> Result<Colors, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+    /// Sets the colour keys the widgets paint with.
+    pub fn set_env(&self, env: &mut Env) {
+        env.set(BACKGROUND, self.background.clone());
+        env.set(STAFF, self.staff.clone());
+        env.set(NOTEHEAD, self.notehead.clone());
+        env.set(ACCIDENTAL, self.accidental.clone());
+        env.set(HIGHLIGHT, self.highlight.clone());
+        env.set(SUSTAINED, self.sustained.clone());
+        env.set(OUTSIDE, self.outside.clone());
+    }
+    /// Colours from the env, the light ones for keys that were not set.
+    pub fn from_env(env: &Env) -> Self {
+        let default = Self::default();
+        let color = |key: EnvKey<Color>, default: Color| env.try_get(key).unwrap_or(default);
+        Colors {
+            background: color(BACKGROUND, default.background),
+            staff: color(STAFF, default.staff),
+            notehead: color(NOTEHEAD, default.notehead),
+            accidental: color(ACCIDENTAL, default.accidental),
+            highlight: color(HIGHLIGHT, default.highlight),
+            sustained: color(SUSTAINED, default.sustained),
+            outside: color(OUTSIDE, default.outside),
+        }
+    }
+}
+
+// Position is left to the window manager until the window was moved.
+#[derive(Clone, Data, Lens, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct Window {
+    pub width: f64,
+    pub height: f64,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+}
+impl Default for Window {
+    fn default() -> Self {
+        Window {
+            width: 1000.0,
+            height: 700.0,
+            x: None,
+            y: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct Settings {
+    pub language: Language,
+    pub naming: System,
+    pub octaves: Octaves,
+    // Name of the last selected MIDI input, empty for none.
+    pub midi_port: String,
+    // Staff of the note view.
+    pub clef: ClefType,
+    pub spelling: Spelling,
+    pub reference: Reference,
+    pub degrees: Degrees,
+    // SMuFL font file for the staff symbols. Empty looks for an installed
+    // Bravura or Leland, without one the built-in drawings are used.
+    pub font: String,
+    pub theme: Theme,
+    // Colours of the custom theme, read at startup. Empty uses the
+    // colors table.
+    pub theme_file: String,
+    pub trainer: Trainer,
+    pub transposition: Transposition,
+    pub tuner: Tuner,
+    pub caption: Caption,
+    pub ottava: OttavaLimits,
+    pub colors: Colors,
+    pub window: Window,
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            language: Language::System,
+            naming: System::English,
+            octaves: Octaves::MiddleC3,
+            midi_port: String::new(),
+            clef: ClefType::Treble,
+            spelling: Spelling::Key,
+            reference: Reference::Off,
+            degrees: Degrees::Off,
+            font: String::new(),
+            theme: Theme::Light,
+            theme_file: String::new(),
+            trainer: Trainer::default(),
+            transposition: Transposition::default(),
+            tuner: Tuner::default(),
+            caption: Caption::default(),
+            ottava: OttavaLimits::default(),
+            colors: Colors::default(),
+            window: Window::default(),
+        }
+    }
+}
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        let mut path = dirs::config_dir()?;
+        path.push("rusted_big_note");
+        path.push("settings.toml");
+        Some(path)
+    }
+}
+// Reading and writing the file.
+#[cfg(feature = "serialize")]
+impl Settings {
+    // A missing file gives the defaults, broken entries are reported
+    // and replaced by their defaults one by one.
+    pub fn load(path: &Path) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text, &path.display().to_string()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                error!("{}: {}", path.display(), err);
+                error!("Starting with default settings.");
+                Self::default()
+            }
+        }
+    }
+    fn parse(text: &str, source: &str) -> Self {
+        let loaded: toml::Value = match text.parse() {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                error!("{}: {}", source, err);
+                error!("Starting with default settings.");
+                return Self::default();
+            }
+        };
+        let mut merged = toml::Value::try_from(Self::default()).unwrap();
+        let mut entries = Vec::new();
+        leaves(&loaded, &mut Vec::new(), &mut entries);
+        for (path, value) in entries {
+            let name = path.join(".");
+            let mut candidate = merged.clone();
+            if !replace(&mut candidate, &path, value) {
+                warn!("{}: unknown setting {}", source, name);
+                continue;
+            }
+            // Unknown keys are ignored by serde, so they are missing when
+            // the result is written back.
+            match candidate.clone().try_into::<Settings>() {
+                Ok(settings) if contains(&toml::Value::try_from(&settings).unwrap(), &path) => {
+                    merged = candidate
+                }
+                Ok(_) => warn!("{}: unknown setting {}", source, name),
+                Err(err) => error!("{}: invalid {}, using the default: {}", source, name, err),
+            }
+        }
+        merged.try_into().unwrap_or_default()
+    }
+    // Colours of the chosen theme.
+    pub fn theme_colors(&self) -> Colors {
+        self.theme.colors().unwrap_or_else(|| self.custom_colors())
+    }
+    // Colours of the theme file, or the colors table without one. A broken
+    // theme file is reported and the colors table used instead.
+    pub fn custom_colors(&self) -> Colors {
+        if self.theme_file.is_empty() {
+            return self.colors.clone();
+        }
+        Colors::load(Path::new(&self.theme_file)).unwrap_or_else(|err| {
+            error!("{}", err);
+            self.colors.clone()
+        })
+    }
+    // These settings with what changed from before to after. Command line
+    // overrides are part of both, so they are kept out of the file until
+    // they are changed in the app.
+    pub fn with_changes(&self, before: &Settings, after: &Settings) -> Settings {
+        let mut merged = toml::Value::try_from(self).unwrap();
+        changes(
+            &mut merged,
+            &toml::Value::try_from(before).unwrap(),
+            &toml::Value::try_from(after).unwrap(),
+        );
+        merged.try_into().unwrap_or_else(|_| after.clone())
+    }
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(path, text)
+    }
+}
+
+// Collects the non-table values with their key paths.
+#[cfg(feature = "serialize")]
+fn leaves(value: &toml::Value, path: &mut Vec<String>, out: &mut Vec<(Vec<String>, toml::Value)>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                path.push(key.clone());
+                leaves(value, path, out);
+                path.pop();
+            }
+        }
+        value => out.push((path.clone(), value.clone())),
+    }
+}
+
+// Copies the values that differ between before and after onto base.
+#[cfg(feature = "serialize")]
+fn changes(base: &mut toml::Value, before: &toml::Value, after: &toml::Value) {
+    match (base, before, after) {
+        (toml::Value::Table(base), toml::Value::Table(before), toml::Value::Table(after)) => {
+            for (key, value) in after {
+                match (before.get(key), base.get_mut(key)) {
+                    (Some(old), _) if old == value => {}
+                    (Some(old), Some(entry)) => changes(entry, old, value),
+                    _ => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            for key in before.keys().filter(|key| !after.contains_key(*key)) {
+                base.remove(key);
+            }
+        }
+        (base, _, after) => *base = after.clone(),
+    }
+}
+
+// Sets a value in an existing table.
+#[cfg(feature = "serialize")]
+fn replace(tree: &mut toml::Value, path: &[String], value: toml::Value) -> bool {
+    let (key, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+    let mut table = tree;
+    for parent in parents {
+        table = match table.get_mut(parent) {
+            Some(table) => table,
+            None => return false,
+        };
+    }
+    match table.as_table_mut() {
+        Some(table) => {
+            table.insert(key.clone(), value);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(feature = "serialize")]
+fn contains(tree: &toml::Value, path: &[String]) -> bool {
+    let mut value = tree;
+    for key in path {
+        value = match value.get(key) {
+            Some(value) => value,
+            None => return false,
+        };
+    }
+    true
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use druid::kurbo::{Affine, BezPath, Rect, Shape};
+use tracing::{error, warn};
+use usvg::fontdb;
+
+use super::layout::Glyph;
+
+// Installed fonts looked for when the settings name none.
+const INSTALLED: [&str; 2] = ["Bravura", "Leland"];
+
+static FONT: OnceLock<Option<Font>> = OnceLock::new();
+
+#[test]
+fn test_metadata() {
+    let engraving = Engraving::from_metadata(
+        r#"{
+            "fontName": "Bravura",
+            "engravingDefaults": {
+                "staffLineThickness": 0.13,
+                "legerLineThickness": 0.16,
+                "legerLineExtension": 0.4,
+                "stemThickness": 0.12
+            }
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(engraving.leger_line_extension, 0.4);
+    let engraving =
+        Engraving::from_metadata(r#"{"engravingDefaults": {"staffLineThickness": 0.1}}"#).unwrap();
+    assert_eq!(engraving.staff_line_thickness, 0.1);
+    assert_eq!(
+        engraving.leger_line_thickness,
+        Engraving::default().leger_line_thickness
+    );
+    assert!(Engraving::from_metadata("{").is_err());
+}
+
+#[test]
+fn test_metadata_paths() {
+    let paths = metadata_paths(
+        Some(Path::new("/fonts/bravura/redist/otf/Bravura.otf")),
+        "Bravura",
+    );
+    assert_eq!(
+        paths[0],
+        PathBuf::from("/fonts/bravura/redist/otf/bravura_metadata.json")
+    );
+    assert!(paths.contains(&PathBuf::from(
+        "/fonts/bravura/redist/bravura_metadata.json"
+    )));
+    // Installed fonts also look in the SMuFL folders, under their name.
+    let paths = metadata_paths(None, "Leland");
+    assert!(paths
+        .iter()
+        .all(|path| path.ends_with("SMuFL/Fonts/Leland/leland_metadata.json")));
+}
+
+#[test]
+fn test_fixture_fonts() {
+    let font = load_file(Path::new("resources/fonts/SmuflBoxes.ttf")).unwrap();
+    assert_eq!(font.name, "Smufl Boxes");
+    // Every glyph is a box from 0 to 500 font units wide and 500 high
+    // around the baseline, in an em of 1000.
+    assert_eq!(
+        font.outline(Glyph::Notehead).bbox,
+        Rect::new(0.0, -1.0, 2.0, 1.0)
+    );
+    assert_eq!(font.engraving.leger_line_extension, 0.3);
+    let data = include_bytes!("../resources/fonts/SmuflBoxes.ttf");
+    assert_eq!(
+        Font::parse(data, 0, None).unwrap().engraving,
+        Engraving::default()
+    );
+}
+
+#[test]
+fn test_not_a_music_font() {
+    let data = include_bytes!("../resources/fonts/TextOnly.ttf");
+    let err = Font::parse(data, 0, None).err().unwrap();
+    assert_eq!(err, "Text Only has no gClef");
+    assert!(Font::parse(b"not a font", 0, None).is_err());
+}
+
+// Rectangles with the extents of the Bravura glyphs.
+#[cfg(test)]
+pub fn test_font() -> Font {
+    let boxes = [
+        (Glyph::TrebleClef, Rect::new(0.0, -4.392, 2.684, 2.632)),
+        (Glyph::BassClef, Rect::new(0.0, -1.0, 2.736, 2.024)),
+        (Glyph::Notehead, Rect::new(0.0, -0.5, 1.688, 0.5)),
+        (Glyph::Sharp, Rect::new(0.0, -1.4, 0.996, 1.392)),
+        (Glyph::Flat, Rect::new(0.0, -1.748, 0.904, 0.7)),
+        (Glyph::Natural, Rect::new(0.0, -1.364, 0.672, 1.34)),
+        (Glyph::DoubleSharp, Rect::new(0.0, -0.508, 0.988, 0.5)),
+        (Glyph::DoubleFlat, Rect::new(0.0, -1.748, 1.644, 0.7)),
+    ];
+    Font {
+        name: String::from("Test"),
+        engraving: Engraving::default(),
+        outlines: boxes
+            .iter()
+            .map(|(glyph, bbox)| {
+                let path = bbox.to_path(0.1);
+                (*glyph, Outline { path, bbox: *bbox })
+            })
+            .collect(),
+    }
+}
+
+// Line thicknesses from the font metadata, in staff spaces. The
+// defaults are the ones of Bravura.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Engraving {
+    pub staff_line_thickness: f64,
+    pub leger_line_thickness: f64,
+    pub leger_line_extension: f64,
+}
+impl Default for Engraving {
+    fn default() -> Self {
+        Engraving {
+            staff_line_thickness: 0.13,
+            leger_line_thickness: 0.16,
+            leger_line_extension: 0.4,
+        }
+    }
+}
+
+impl Engraving {
+    // Only the line thicknesses are read. Whole-note heads have no stems
+    // and accidentals are spaced by their boxes, so the glyph anchors are
+    // unused. Missing entries keep the defaults.
+    fn from_metadata(text: &str) -> Result<Engraving, String> {
+        let metadata: serde_json::Value =
+            serde_json::from_str(text).map_err(|err| err.to_string())?;
+        let defaults = &metadata["engravingDefaults"];
+        let thickness = |name: &str, default: f64| defaults[name].as_f64().unwrap_or(default);
+        let default = Engraving::default();
+        Ok(Engraving {
+            staff_line_thickness: thickness("staffLineThickness", default.staff_line_thickness),
+            leger_line_thickness: thickness("legerLineThickness", default.leger_line_thickness),
+            leger_line_extension: thickness("legerLineExtension", default.leger_line_extension),
+        })
+    }
+}
+
+pub struct Outline {
+    pub path: BezPath,
+    pub bbox: Rect,
+}
+
+// Glyphs of a SMuFL font in staff spaces, y pointing down, around the
+// origin SMuFL defines for each of them.
+pub struct Font {
+    pub name: String,
+    pub engraving: Engraving,
+    outlines: HashMap<Glyph, Outline>,
+}
+impl Font {
+    pub fn parse(data: &[u8], index: u32, metadata: Option<&str>) -> Result<Font, String> {
+        let face = ttf_parser::Face::parse(data, index).map_err(|err| err.to_string())?;
+        let name = face
+            .names()
+            .into_iter()
+            .filter(|name| name.name_id == ttf_parser::name_id::FAMILY)
+            .find_map(|name| name.to_string())
+            .unwrap_or_default();
+        // An em is four staff spaces.
+        let scale = 4.0 / face.units_per_em() as f64;
+        let mut outlines = HashMap::new();
+        for glyph in Glyph::ALL.iter() {
+            let id = char::from_u32(glyph.codepoint())
+                .and_then(|c| face.glyph_index(c))
+                .ok_or_else(|| format!("{} has no {}", name, glyph.smufl_name()))?;
+            let mut builder = Builder(BezPath::new());
+            face.outline_glyph(id, &mut builder);
+            let path = Affine::new([scale, 0.0, 0.0, -scale, 0.0, 0.0]) * builder.0;
+            let bbox = path.bounding_box();
+            outlines.insert(*glyph, Outline { path, bbox });
+        }
+        let engraving = match metadata {
+            Some(text) => Engraving::from_metadata(text)?,
+            None => Engraving::default(),
+        };
+        Ok(Font {
+            name,
+            engraving,
+            outlines,
+        })
+    }
+    pub fn outline(&self, glyph: Glyph) -> &Outline {
+        &self.outlines[&glyph]
+    }
+}
+
+struct Builder(BezPath);
+impl ttf_parser::OutlineBuilder for Builder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to((x as f64, y as f64));
+    }
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to((x as f64, y as f64));
+    }
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to((x1 as f64, y1 as f64), (x as f64, y as f64));
+    }
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.curve_to(
+            (x1 as f64, y1 as f64),
+            (x2 as f64, y2 as f64),
+            (x as f64, y as f64),
+        );
+    }
+    fn close(&mut self) {
+        self.0.close_path();
+    }
+}
+
+// The font file from the settings, or an installed font when the path
+// is empty. Problems are logged and leave the SVG glyphs in place.
+pub fn load(path: &str) -> Option<Font> {
+    if !path.is_empty() {
+        return load_file(Path::new(path));
+    }
+    let mut db = fontdb::Database::new();
+    db.load_system_fonts();
+    for family in INSTALLED.iter() {
+        let query = fontdb::Query {
+            families: &[fontdb::Family::Name(family)],
+            ..fontdb::Query::default()
+        };
+        let id = match db.query(&query) {
+            Some(id) => id,
+            None => continue,
+        };
+        // The metadata of an installed font is next to it or in the
+        // SMuFL folders.
+        let path = db.face(id).and_then(|face| match &face.source {
+            fontdb::Source::File(path) => Some(path.clone()),
+            _ => None,
+        });
+        let metadata = read_metadata(&metadata_paths(path.as_deref(), family), family);
+        match db.with_face_data(id, |data, index| {
+            Font::parse(data, index, metadata.as_deref())
+        }) {
+            Some(Ok(font)) => return Some(font),
+            Some(Err(err)) => error!("{}: {}", family, err),
+            None => {}
+        }
+    }
+    None
+}
+
+fn load_file(path: &Path) -> Option<Font> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("{}: {}", path.display(), err);
+            return None;
+        }
+    };
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let metadata = read_metadata(
+        &metadata_paths(Some(path), name),
+        &path.display().to_string(),
+    );
+    match Font::parse(&data, 0, metadata.as_deref()) {
+        Ok(font) => Some(font),
+        Err(err) => {
+            error!("{}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+// The metadata is named after the font, next to it or one directory up
+// as in the Bravura distribution (redist/otf/Bravura.otf and
+// redist/bravura_metadata.json), or in a SMuFL folder as the standard
+// suggests (SMuFL/Fonts/Bravura/bravura_metadata.json).
+fn metadata_paths(font: Option<&Path>, name: &str) -> Vec<PathBuf> {
+    let file = format!("{}_metadata.json", name.to_lowercase());
+    let mut paths = Vec::new();
+    if let Some(dir) = font.and_then(Path::parent) {
+        paths.push(dir.join(&file));
+        paths.push(dir.join("metadata.json"));
+        if let Some(parent) = dir.parent() {
+            paths.push(parent.join(&file));
+        }
+    }
+    let mut shared: Vec<PathBuf> = dirs::data_local_dir().into_iter().collect();
+    if cfg!(target_os = "linux") {
+        shared.push(PathBuf::from("/usr/local/share"));
+        shared.push(PathBuf::from("/usr/share"));
+    }
+    for dir in shared {
+        paths.push(dir.join("SMuFL").join("Fonts").join(name).join(&file));
+    }
+    paths
+}
+
+// Without a metadata file the line thicknesses are the ones of Bravura.
+fn read_metadata(paths: &[PathBuf], font: &str) -> Option<String> {
+    let metadata = paths
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok());
+    if metadata.is_none() {
+        warn!(
+            "{}: no metadata file, using the default line thicknesses",
+            font
+        );
+    }
+    metadata
+}
+
+// Set once at startup, before any staff is drawn.
+pub fn install(font: Option<Font>) {
+    if FONT.set(font).is_err() {
+        warn!("The music font was already installed");
+    }
+}
+
+pub fn font() -> Option<&'static Font> {
+    FONT.get().and_then(|font| font.as_ref())
+}
@@ -0,0 +1,235 @@
+// Reference images of the staff. Every case is rendered with the SVG
+// glyphs from the same layout the Staff widget paints, and compared with
+// tests/snapshots/<case>-<size>.png.
+//
+// The images cover StaffLayout and the SVG export, not the widget
+// itself: druid can't paint a widget without a window. Music font
+// glyphs, scales, degree labels and the highlight and outside colours
+// are drawn by Staff::paint and are not part of them.
+//
+// After an intended change, write new references with
+//
+//     UPDATE_SNAPSHOTS=1 cargo test snapshots
+//
+// and check them in. Failing cases leave the rendered image and a diff
+// in target/snapshots.
+use std::path::PathBuf;
+
+use druid::Size;
+use tiny_skia::{Pixmap, PremultipliedColorU8};
+
+use super::layout::{Glyphs, StaffLayout};
+use super::note::{Key, Note, NoteAlt};
+use super::render;
+use super::settings::{Colors, OttavaLimits};
+use super::staff::ClefType;
+
+// Channels further apart than this make a pixel differ, antialiasing may
+// vary a little between machines.
+const CHANNEL_TOLERANCE: u8 = 48;
+// Share of pixels that may differ. A head moved by half a space changes
+// many times more.
+const PIXEL_TOLERANCE: f64 = 0.002;
+
+const SIZES: [(f64, f64); 2] = [(320.0, 240.0), (160.0, 120.0)];
+
+struct Case {
+    name: &'static str,
+    clef: ClefType,
+    fifths: i8,
+    alteration: NoteAlt,
+    notes: &'static [u8],
+}
+impl Case {
+    const fn new(
+        name: &'static str,
+        clef: ClefType,
+        fifths: i8,
+        alteration: NoteAlt,
+        notes: &'static [u8],
+    ) -> Self {
+        Case {
+            name,
+            clef,
+            fifths,
+            alteration,
+            notes,
+        }
+    }
+    fn render(&self, size: Size) -> Pixmap {
+        let notes: Vec<Note> = self.notes.iter().map(|nr| Note::new(*nr)).collect();
+        let layout = StaffLayout::with_glyphs(
+            Glyphs::Assets,
+            size,
+            self.clef,
+            &Key::new(self.fifths),
+            self.alteration.clone(),
+            &notes,
+            &OttavaLimits::default(),
+            false,
+        );
+        let svg = render::svg(&layout, size, &Colors::default());
+        // No fonts, so the ottava text doesn't depend on the machine.
+        render::pixmap(&svg, size, &usvg::fontdb::Database::new()).unwrap()
+    }
+}
+
+const CASES: [Case; 14] = [
+    Case::new("empty-treble", ClefType::Treble, 0, NoteAlt::Sharp, &[]),
+    Case::new("c4-treble", ClefType::Treble, 0, NoteAlt::Sharp, &[60]),
+    Case::new("a3-treble", ClefType::Treble, 0, NoteAlt::Sharp, &[57]),
+    Case::new("c6-treble", ClefType::Treble, 0, NoteAlt::Sharp, &[84]),
+    Case::new("fis5-treble", ClefType::Treble, 0, NoteAlt::Sharp, &[78]),
+    Case::new(
+        "triad-treble",
+        ClefType::Treble,
+        0,
+        NoteAlt::Sharp,
+        &[60, 64, 67],
+    ),
+    Case::new(
+        "seconds-treble",
+        ClefType::Treble,
+        0,
+        NoteAlt::Sharp,
+        &[64, 65, 67],
+    ),
+    Case::new(
+        "sharps-treble",
+        ClefType::Treble,
+        0,
+        NoteAlt::Sharp,
+        &[63, 66, 70],
+    ),
+    Case::new(
+        "e-major-treble",
+        ClefType::Treble,
+        4,
+        NoteAlt::Sharp,
+        &[64, 68, 71],
+    ),
+    Case::new("empty-bass", ClefType::Bass, 0, NoteAlt::Sharp, &[]),
+    Case::new("c4-bass", ClefType::Bass, 0, NoteAlt::Sharp, &[60]),
+    Case::new("e2-bass", ClefType::Bass, 0, NoteAlt::Sharp, &[40]),
+    Case::new("natural-bass", ClefType::Bass, 2, NoteAlt::Sharp, &[53]),
+    Case::new(
+        "b-flat-minor-bass",
+        ClefType::Bass,
+        -5,
+        NoteAlt::Flat,
+        &[46, 49, 53],
+    ),
+];
+
+#[test]
+fn test_snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut failures = Vec::new();
+    for case in CASES.iter() {
+        for (width, height) in SIZES.iter() {
+            let name = format!("{}-{}x{}", case.name, width, height);
+            let actual = case.render(Size::new(*width, *height));
+            let path = directory("tests/snapshots").join(format!("{}.png", name));
+            if update {
+                actual.save_png(&path).unwrap();
+                continue;
+            }
+            let expected = match Pixmap::load_png(&path) {
+                Ok(expected) => expected,
+                Err(err) => {
+                    failures.push(format!("{}: {}", path.display(), err));
+                    continue;
+                }
+            };
+            if let Err(err) = compare(&actual, &expected) {
+                keep(&name, &actual, &expected);
+                failures.push(format!("{}: {}", name, err));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "Staff images differ from their references, see target/snapshots, \
+         or run with UPDATE_SNAPSHOTS=1 after an intended change:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_compare() {
+    let size = Size::new(SIZES[0].0, SIZES[0].1);
+    let e4 = Case::new("e4", ClefType::Treble, 0, NoteAlt::Sharp, &[64]).render(size);
+    assert!(compare(&e4, &e4).is_ok());
+    // A head half a space off.
+    let f4 = Case::new("f4", ClefType::Treble, 0, NoteAlt::Sharp, &[65]).render(size);
+    assert!(compare(&f4, &e4).is_err());
+    let small = CASES[0].render(Size::new(SIZES[1].0, SIZES[1].1));
+    assert!(compare(&small, &e4).is_err());
+}
+
+fn directory(relative: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(relative);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+fn differs(a: &PremultipliedColorU8, b: &PremultipliedColorU8) -> bool {
+    let channels = [
+        (a.red(), b.red()),
+        (a.green(), b.green()),
+        (a.blue(), b.blue()),
+        (a.alpha(), b.alpha()),
+    ];
+    channels
+        .iter()
+        .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+}
+
+fn compare(actual: &Pixmap, expected: &Pixmap) -> Result<(), String> {
+    if (actual.width(), actual.height()) != (expected.width(), expected.height()) {
+        return Err(format!(
+            "{}x{} instead of {}x{}",
+            actual.width(),
+            actual.height(),
+            expected.width(),
+            expected.height()
+        ));
+    }
+    let total = actual.pixels().len();
+    let differing = actual
+        .pixels()
+        .iter()
+        .zip(expected.pixels())
+        .filter(|(a, b)| differs(a, b))
+        .count();
+    if differing as f64 > total as f64 * PIXEL_TOLERANCE {
+        return Err(format!("{} of {} pixels differ", differing, total));
+    }
+    Ok(())
+}
+
+// Writes the rendered image and one with the differing pixels in red
+// over the faded reference.
+fn keep(name: &str, actual: &Pixmap, expected: &Pixmap) {
+    let dir = directory("target/snapshots");
+    let _ = actual.save_png(dir.join(format!("{}.png", name)));
+    if (actual.width(), actual.height()) != (expected.width(), expected.height()) {
+        return;
+    }
+    let mut diff = expected.clone();
+    let red = PremultipliedColorU8::from_rgba(0xff, 0, 0, 0xff).unwrap();
+    for (pixel, other) in diff.pixels_mut().iter_mut().zip(actual.pixels()) {
+        *pixel = if differs(pixel, other) {
+            red
+        } else {
+            PremultipliedColorU8::from_rgba(
+                pixel.red() / 4,
+                pixel.green() / 4,
+                pixel.blue() / 4,
+                pixel.alpha() / 4,
+            )
+            .unwrap()
+        };
+    }
+    let _ = diff.save_png(dir.join(format!("{}.diff.png", name)));
+}
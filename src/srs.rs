@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use super::note::{Note, NoteAlt};
+use super::profile;
+use super::staff::ClefType;
+
+// Rounds (answers) until a card of the given level is due again.
+const INTERVALS: [u64; 5] = [1, 3, 7, 15, 31];
+// Right answers slower than this don't promote the card.
+pub const SLOW_REACTION: u64 = 2500;
+
+#[test]
+fn test_leitner_levels() {
+    let mut scheduler = Scheduler::new();
+    let note = Note::new(66);
+    scheduler.record(ClefType::Treble, &note, NoteAlt::Sharp, true, 800);
+    let card = scheduler
+        .card(ClefType::Treble, &note, NoteAlt::Sharp)
+        .unwrap();
+    assert_eq!(card.level, 1);
+    assert_eq!(card.due, 1 + INTERVALS[1]);
+    scheduler.record(
+        ClefType::Treble,
+        &note,
+        NoteAlt::Sharp,
+        true,
+        SLOW_REACTION + 1,
+    );
+    let card = scheduler
+        .card(ClefType::Treble, &note, NoteAlt::Sharp)
+        .unwrap();
+    assert_eq!(card.level, 1);
+    scheduler.record(ClefType::Treble, &note, NoteAlt::Sharp, false, 500);
+    let card = scheduler
+        .card(ClefType::Treble, &note, NoteAlt::Sharp)
+        .unwrap();
+    assert_eq!(card.level, 0);
+    assert_eq!(card.lapses, 1);
+    assert_eq!(card.reviews, 3);
+    // Spelling and clef are part of the key.
+    assert!(scheduler
+        .card(ClefType::Treble, &note, NoteAlt::Flat)
+        .is_none());
+    assert!(scheduler
+        .card(ClefType::Bass, &note, NoteAlt::Sharp)
+        .is_none());
+}
+
+#[test]
+fn test_pick_prefers_weak_cards() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut scheduler = Scheduler::new();
+    let notes: Vec<Note> = [60, 62, 64, 65].iter().map(|nr| Note::new(*nr)).collect();
+    scheduler.record(ClefType::Treble, &notes[0], NoteAlt::Sharp, true, 300);
+    scheduler.record(ClefType::Treble, &notes[1], NoteAlt::Sharp, false, 300);
+    scheduler.record(ClefType::Treble, &notes[2], NoteAlt::Sharp, true, 300);
+    scheduler.record(ClefType::Treble, &notes[3], NoteAlt::Sharp, false, 300);
+    scheduler.record(ClefType::Treble, &Note::new(72), NoteAlt::Sharp, true, 300);
+    // All four cards are due, the two missed ones share the random picks.
+    let mut picks = BTreeMap::new();
+    for _ in 0..200 {
+        let pick = scheduler.pick(ClefType::Treble, NoteAlt::Sharp, &notes, &mut rng);
+        *picks.entry(pick.unwrap().midi_nr()).or_insert(0) += 1;
+    }
+    assert_eq!(picks.keys().copied().collect::<Vec<u8>>(), vec![62, 65]);
+    assert!(picks[&62] > 70 && picks[&65] > 70);
+}
+
+#[test]
+fn test_pick_falls_back_to_first_due() {
+    use rand::{rngs::StdRng, SeedableRng};
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut scheduler = Scheduler::new();
+    let known = Note::new(60);
+    let missed = Note::new(62);
+    for _ in 0..3 {
+        scheduler.record(ClefType::Treble, &known, NoteAlt::Sharp, true, 300);
+    }
+    scheduler.record(ClefType::Treble, &missed, NoteAlt::Sharp, false, 300);
+    // Nothing is due yet, the missed card comes back first.
+    let candidates = vec![known, missed.clone()];
+    assert_eq!(
+        scheduler.pick(ClefType::Treble, NoteAlt::Sharp, &candidates, &mut rng),
+        Some(missed)
+    );
+    assert_eq!(
+        scheduler.pick(ClefType::Treble, NoteAlt::Sharp, &[], &mut rng),
+        None
+    );
+}
+
+#[test]
+fn test_save_load() {
+    let path = std::env::temp_dir().join(format!(
+        "rusted_big_note_test_srs_{}.json",
+        std::process::id()
+    ));
+    let mut scheduler = Scheduler::new();
+    scheduler.record(ClefType::Bass, &Note::new(43), NoteAlt::Flat, true, 1000);
+    scheduler.save(&path).unwrap();
+    assert_eq!(Scheduler::load(&path), scheduler);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(Scheduler::load(&path), Scheduler::new());
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Card {
+    pub level: usize,
+    pub due: u64,
+    pub reviews: u32,
+    pub lapses: u32,
+    // Reaction of the last answer in milliseconds.
+    pub reaction: u64,
+}
+impl Card {
+    fn new() -> Self {
+        Card {
+            level: 0,
+            due: 0,
+            reviews: 0,
+            lapses: 0,
+            reaction: 0,
+        }
+    }
+}
+
+// Leitner boxes keyed by clef and spelled note name, e.g. "Treble:F#4".
+// Time is counted in answers rather than days, so drills within one
+// lesson already repeat weak notes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Scheduler {
+    round: u64,
+    cards: BTreeMap<String, Card>,
+}
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            round: 0,
+            cards: BTreeMap::new(),
+        }
+    }
+    fn key(clef: ClefType, note: &Note, alteration: NoteAlt) -> String {
+        format!("{:?}:{}", clef.resolve(Some(note)), note.name(alteration))
+    }
+    pub fn card(&self, clef: ClefType, note: &Note, alteration: NoteAlt) -> Option<&Card> {
+        self.cards.get(&Self::key(clef, note, alteration))
+    }
+    pub fn record(
+        &mut self,
+        clef: ClefType,
+        note: &Note,
+        alteration: NoteAlt,
+        correct: bool,
+        reaction: u64,
+    ) {
+        self.round += 1;
+        let round = self.round;
+        let card = self
+            .cards
+            .entry(Self::key(clef, note, alteration))
+            .or_insert_with(Card::new);
+        card.reviews += 1;
+        card.reaction = reaction;
+        if !correct {
+            card.level = 0;
+            card.lapses += 1;
+        } else if reaction <= SLOW_REACTION {
+            card.level = (card.level + 1).min(INTERVALS.len() - 1);
+        }
+        card.due = round + INTERVALS[card.level];
+    }
+    // Picks among due cards from the lowest level, unseen notes count
+    // as due on level zero. Falls back to the card due first.
+    pub fn pick<R: Rng>(
+        &self,
+        clef: ClefType,
+        alteration: NoteAlt,
+        candidates: &[Note],
+        rng: &mut R,
+    ) -> Option<Note> {
+        let new_card = Card::new();
+        let cards: Vec<(&Note, &Card)> = candidates
+            .iter()
+            .map(|note| {
+                let card = self
+                    .card(clef, note, alteration.clone())
+                    .unwrap_or(&new_card);
+                (note, card)
+            })
+            .collect();
+        let due: Vec<&(&Note, &Card)> = cards
+            .iter()
+            .filter(|(_, card)| card.due <= self.round)
+            .collect();
+        if let Some(level) = due.iter().map(|(_, card)| card.level).min() {
+            let weakest: Vec<&Note> = due
+                .iter()
+                .filter(|(_, card)| card.level == level)
+                .map(|(note, _)| *note)
+                .collect();
+            return Some(weakest[rng.gen_range(0..weakest.len())].clone());
+        }
+        cards
+            .iter()
+            .min_by_key(|(_, card)| card.due)
+            .map(|(note, _)| (*note).clone())
+    }
+
+    // Per-student state file in the profile directory.
+    pub fn path(student: &str) -> Option<PathBuf> {
+        Some(profile::dir(student)?.join("schedule.json"))
+    }
+    // Schedules used to be kept in a shared "students" folder before
+    // profiles had their own directory.
+    fn legacy_path(student: &str) -> Option<PathBuf> {
+        let name = profile::sanitize(student);
+        if name.is_empty() {
+            return None;
+        }
+        let mut path = dirs::data_dir()?;
+        path.push("rusted_big_note");
+        path.push("students");
+        path.push(format!("{}.json", name));
+        Some(path)
+    }
+    // Moves an old schedule into the profile directory once.
+    pub fn migrate(student: &str) {
+        let (old, new) = match (Self::legacy_path(student), Self::path(student)) {
+            (Some(old), Some(new)) if old.exists() && !new.exists() => (old, new),
+            _ => return,
+        };
+        let moved = new
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::rename(&old, &new));
+        match moved {
+            Ok(()) => info!("Moved schedule {} to {}", old.display(), new.display()),
+            Err(err) => error!("{}: {}", old.display(), err),
+        }
+    }
+    // Missing or broken files give a fresh scheduler.
+    pub fn load(path: &Path) -> Self {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Self::new(),
+        };
+        match serde_json::from_str(&text) {
+            Ok(scheduler) => scheduler,
+            Err(err) => {
+                error!("{}: {}", path.display(), err);
+                error!("Starting with an empty schedule.");
+                Self::new()
+            }
+        }
+    }
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, text)
+    }
+}
@@ -1,287 +1,854 @@
+use std::marker::PhantomData;
+#[cfg(feature = "serialize")]
+use std::path::Path;
+use std::sync::Arc;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 use tracing::error;
 
 use druid::{
-    kurbo::Line,
+    lens,
+    piet::{FontFamily, FontStyle, FontWeight, Text, TextLayout, TextLayoutBuilder},
     widget::{Container, FillStrat, Flex, Painter, Svg, SvgData, WidgetExt, WidgetWrapper},
-    AppLauncher, BoxConstraints, Color, Data, Env, Event, EventCtx, Insets, LayoutCtx, LifeCycle,
-    LifeCycleCtx, LocalizedString, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
+    AppLauncher, BoxConstraints, Color, Data, Env, Event, EventCtx, Insets, LayoutCtx, Lens,
+    LifeCycle, LifeCycleCtx, LocalizedString, PaintCtx, RenderContext, Size, UpdateCtx, Widget,
     WidgetPod, WindowDesc,
 };
 
+use super::layout::{Bracket, Glyph, Glyphs, Placed, StaffLayout, DEGREE_SIZE};
 use super::note;
+use super::settings;
+
+// Key signature accidentals on the treble staff, in the order they are
+// added. The bass staff has them two octaves lower.
+const SHARP_POSITIONS: [u8; 7] = [77, 72, 79, 74, 69, 76, 71];
+const FLAT_POSITIONS: [u8; 7] = [71, 76, 69, 74, 67, 72, 65];
+
+#[test]
+fn test_degree_labels() {
+    let major = note::Scale::new(0, note::ScaleType::Major);
+    let white = [60, 62, 64, 65, 67, 69, 71].map(note::Note::new);
+    let numbers: Vec<Option<String>> = white
+        .iter()
+        .map(|note| Degrees::Numbers.label(&major, note))
+        .collect();
+    let expected = ["1", "2", "3", "4", "5", "6", "7"].map(|label| Some(String::from(label)));
+    assert_eq!(numbers, expected);
+    let functions: Vec<Option<String>> = white
+        .iter()
+        .map(|note| Degrees::Functions.label(&major, note))
+        .collect();
+    let expected = ["T", "S", "T", "S", "D", "T", "D"].map(|label| Some(String::from(label)));
+    assert_eq!(functions, expected);
+    assert_eq!(Degrees::Off.label(&major, &white[0]), None);
+    assert_eq!(Degrees::Numbers.label(&major, &note::Note::new(61)), None);
+    // Degrees of a minor scale count from its own tonic.
+    let minor = note::Scale::new(3, note::ScaleType::NaturalMinor);
+    let c = &white[0];
+    assert_eq!(Degrees::Numbers.label(&minor, c), Some(String::from("♭3")));
+    assert_eq!(Degrees::Functions.label(&minor, c), Some(String::from("T")));
+}
+
+#[test]
+fn test_reference_scale() {
+    let a_minor = note::Key::new(0);
+    let dorian = note::Scale::new(2, note::ScaleType::Dorian);
+    let leading = note::Note::new(68);
+    let degree = |reference: Reference| {
+        let scale = reference.scale(&a_minor, &dorian)?;
+        scale.degree(&leading)
+    };
+    assert_eq!(Reference::Off.scale(&a_minor, &dorian), None);
+    assert_eq!(
+        Reference::Key.scale(&a_minor, &dorian),
+        Some(note::Scale::new(0, note::ScaleType::Major))
+    );
+    assert_eq!(degree(Reference::Key), None);
+    assert_eq!(degree(Reference::Minor), Some(6));
+    assert_eq!(Reference::Scale.scale(&a_minor, &dorian), Some(dorian));
+    let e_flat = note::Key::new(-3);
+    assert_eq!(
+        Reference::Minor.scale(&e_flat, &note::Scale::new(0, note::ScaleType::Major)),
+        Some(note::Scale::new(0, note::ScaleType::HarmonicMinor))
+    );
+}
+
+#[test]
+fn test_is_outside() {
+    let notes = |midi_nrs: &[u8]| midi_nrs.iter().map(|nr| note::Note::new(*nr)).collect();
+    let mut staff = Staff::chord();
+    staff.played = notes(&[61]);
+    assert!(!staff.is_outside());
+    let mut staff = staff.with_reference(note::Scale::new(0, note::ScaleType::Major));
+    staff.played = notes(&[60, 64, 67]);
+    assert!(!staff.is_outside());
+    // A chord is outside as a whole.
+    staff.played = notes(&[60, 63, 67]);
+    assert!(staff.is_outside());
+    staff.played = Vec::new();
+    assert!(!staff.is_outside());
+    // The notes of a shown scale are never outside.
+    let mut staff = staff.with_scale(note::Scale::new(-3, note::ScaleType::Major));
+    staff.played = notes(&[61]);
+    assert!(!staff.is_outside());
+}
 
-#[derive(Debug)]
-enum ClefType {
+#[cfg(feature = "serialize")]
+#[test]
+fn test_state_file() {
+    let state = StaffState {
+        notes: vec![note::Note::new(61), note::Note::new(66)],
+        clef: ClefType::Bass,
+        key: note::Key::new(-3),
+        alteration: note::NoteAlt::Sharp,
+    };
+    let path = std::env::temp_dir().join(format!(
+        "rusted_big_note_test_state_{}.toml",
+        std::process::id()
+    ));
+    state.save(&path).unwrap();
+    assert!(std::fs::read_to_string(&path).unwrap().contains("'C#3'"));
+    assert_eq!(StaffState::load(&path).unwrap(), state);
+    std::fs::write(&path, "clef = \"bass\"\n").unwrap();
+    let clef_only = StaffState {
+        clef: ClefType::Bass,
+        ..StaffState::default()
+    };
+    assert_eq!(StaffState::load(&path).unwrap(), clef_only);
+    std::fs::write(&path, "notes = [\"X4\"]\n").unwrap();
+    assert!(StaffState::load(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Clef of the staff.
+#[derive(Debug, Clone, Copy, Data, PartialEq)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ClefType {
     Treble,
     Bass,
     Auto,
 }
 impl ClefType {
-    pub fn svgdata(&self) -> SvgData {
-        let file: Result<SvgData, Box<dyn std::error::Error + 'static>>;
+    /// Auto picks the clef closest to the note.
+    pub fn resolve(&self, note: Option<&note::Note>) -> ClefType {
+        match *self {
+            ClefType::Auto => match note {
+                Some(note) if note.midi_nr() < 60 => ClefType::Bass,
+                _ => ClefType::Treble,
+            },
+            clef => clef,
+        }
+    }
+    /// Note on the bottom staff line.
+    pub fn bottom_note(&self) -> note::Note {
+        match *self {
+            ClefType::Bass => note::Note::new(43),
+            _ => note::Note::new(64),
+        }
+    }
+    /// Notes whose lines carry the key signature accidentals.
+    pub fn signature_notes(&self, key: &note::Key) -> Vec<note::Note> {
+        let positions = if key.fifths() < 0 {
+            &FLAT_POSITIONS
+        } else {
+            &SHARP_POSITIONS
+        };
+        let octaves = match *self {
+            ClefType::Bass => 24,
+            _ => 0,
+        };
+        positions[..key.altered_steps().len()]
+            .iter()
+            .map(|nr| note::Note::new(nr - octaves))
+            .collect()
+    }
+    /// Note on the line the clef is named for, where its glyph origin sits.
+    pub fn reference_note(&self) -> note::Note {
         match *self {
-            ClefType::Auto => file = include_str!("../assets/treble clef.svg").parse::<SvgData>(),
-            ClefType::Treble => file = include_str!("../assets/treble clef.svg").parse::<SvgData>(),
-            ClefType::Bass => file = include_str!("../assets/bass clef.svg").parse::<SvgData>(),
-        }
-        match file {
-            Ok(svg) => svg,
-            Err(err) => {
-                error!("{}", err);
-                error!("Using an empty SVG instead.");
-                SvgData::default()
+            ClefType::Bass => note::Note::new(53),
+            _ => note::Note::new(67),
+        }
+    }
+}
+
+/// Spelling of notes outside the key signature.
+#[derive(Debug, Clone, Copy, Data, PartialEq)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Spelling {
+    /// Sharps in sharp keys and C, flats in flat keys.
+    Key,
+    Sharps,
+    Flats,
+}
+impl Spelling {
+    pub const ALL: [Spelling; 3] = [Spelling::Key, Spelling::Sharps, Spelling::Flats];
+    /// Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Spelling::Key => "spelling-key",
+            Spelling::Sharps => "spelling-sharps",
+            Spelling::Flats => "spelling-flats",
+        }
+    }
+    /// Accidental black keys are spelled with in the key.
+    pub fn alteration(&self, key: &note::Key) -> note::NoteAlt {
+        match self {
+            Spelling::Key => key.alteration(),
+            Spelling::Sharps => note::NoteAlt::Sharp,
+            Spelling::Flats => note::NoteAlt::Flat,
+        }
+    }
+}
+
+/// What played notes are checked against, notes outside it are drawn
+/// in the [`settings::OUTSIDE`] colour.
+#[derive(Debug, Clone, Copy, Data, PartialEq)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Reference {
+    Off,
+    /// The major scale of the key signature. In a minor key the raised
+    /// leading tone is outside it, G♯ in A minor.
+    Key,
+    /// The harmonic minor scale of the key signature, with the raised
+    /// leading tone and without the natural seventh.
+    Minor,
+    /// The scale of the scale view.
+    Scale,
+}
+impl Reference {
+    pub const ALL: [Reference; 4] = [
+        Reference::Off,
+        Reference::Key,
+        Reference::Minor,
+        Reference::Scale,
+    ];
+    /// Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Reference::Off => "reference-off",
+            Reference::Key => "reference-key",
+            Reference::Minor => "reference-minor",
+            Reference::Scale => "reference-scale",
+        }
+    }
+    /// Scale to check notes against in the key, given the scale of the
+    /// scale view. None when off.
+    pub fn scale(&self, key: &note::Key, scale: &note::Scale) -> Option<note::Scale> {
+        match self {
+            Reference::Off => None,
+            Reference::Key => Some(note::Scale::new(key.fifths(), note::ScaleType::Major)),
+            // The relative minor is three fifths up.
+            Reference::Minor => Some(note::Scale::new(
+                key.fifths() + 3,
+                note::ScaleType::HarmonicMinor,
+            )),
+            Reference::Scale => Some(scale.clone()),
+        }
+    }
+}
+
+/// Label written under the notes that are in the scale of a [`Staff`].
+#[derive(Debug, Clone, Copy, Data, PartialEq)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Degrees {
+    Off,
+    /// Degree number, "♭3" for the third of a minor scale.
+    Numbers,
+    /// Tonic, subdominant or dominant function as "T", "S" or "D".
+    Functions,
+}
+impl Degrees {
+    pub const ALL: [Degrees; 3] = [Degrees::Off, Degrees::Numbers, Degrees::Functions];
+    /// Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Degrees::Off => "degrees-off",
+            Degrees::Numbers => "degrees-numbers",
+            Degrees::Functions => "degrees-functions",
+        }
+    }
+    /// Label of the note, none for notes outside the scale.
+    pub fn label(&self, scale: &note::Scale, note: &note::Note) -> Option<String> {
+        let degree = scale.degree(note)?;
+        match self {
+            Degrees::Off => None,
+            Degrees::Numbers => scale.degree_number(degree),
+            Degrees::Functions => {
+                // The third and sixth stand in for the tonic, the second
+                // for the subdominant and the seventh for the dominant.
+                let (letters, _) = scale.interval(degree)?;
+                Some(String::from(["T", "S", "T", "S", "D", "T", "D"][letters]))
             }
         }
     }
 }
 
-pub struct Staff {
-    midi_note: Option<note::Note>,
-    max_lines: i32,
-    clef_svg: WidgetPod<Option<note::Note>, Svg>,
-    note_svg: WidgetPod<Option<note::Note>, Svg>,
-    sharp_svg: WidgetPod<Option<note::Note>, Svg>,
-    flat_svg: WidgetPod<Option<note::Note>, Svg>,
+/// Everything a [`Staff`] shows, to save a session or set up another
+/// staff the same way with [`Staff::with_state`]. [`Staff::state`]
+/// reads it back from a staff.
+#[derive(Debug, Clone, Data, Lens, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+pub struct StaffState {
+    /// Lowest first.
+    #[data(same_fn = "PartialEq::eq")]
+    pub notes: Vec<note::Note>,
+    pub clef: ClefType,
+    pub key: note::Key,
+    pub alteration: note::NoteAlt,
+}
+impl Default for StaffState {
+    fn default() -> Self {
+        StaffState {
+            notes: Vec::new(),
+            clef: ClefType::Treble,
+            key: note::Key::new(0),
+            alteration: note::NoteAlt::Flat,
+        }
+    }
+}
+#[cfg(feature = "serialize")]
+impl StaffState {
+    /// Reads a state written by [`StaffState::save`], missing entries
+    /// keep their defaults.
+    pub fn load(path: &Path) -> Result<StaffState, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+    /// Writes the state as TOML, with the notes by name.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(path, text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+// Glyph file drawn in the given colour, shared with the dictation
+// staff.
+pub(crate) fn glyph_svg(glyph: Glyph, color: &Color) -> Svg {
+    let data = match glyph.tinted(color).parse::<SvgData>() {
+        Ok(data) => data,
+        Err(err) => {
+            error!("{}", err);
+            error!("Using an empty SVG instead.");
+            SvgData::default()
+        }
+    };
+    Svg::new(data).fill_mode(FillStrat::Fill)
+}
+
+// Ottava label with its baseline on the bottom of its box.
+pub(crate) fn paint_label(ctx: &mut PaintCtx, bracket: &Bracket, color: &Color) {
+    let text = ctx
+        .text()
+        .new_text_layout(String::from(bracket.ottava.label()))
+        .font(FontFamily::SERIF, bracket.label.height())
+        .default_attribute(FontStyle::Italic)
+        .default_attribute(FontWeight::BOLD)
+        .text_color(color.clone())
+        .build();
+    if let Ok(text) = text {
+        let baseline = text.line_metric(0).map_or(0.0, |line| line.baseline);
+        ctx.draw_text(&text, (bracket.label.x0, bracket.label.y1 - baseline));
+    }
+}
+
+// Degree label centred under the head, on the line the layout keeps
+// for the labels below the staff and the notes.
+fn paint_degree(
+    ctx: &mut PaintCtx,
+    layout: &StaffLayout,
+    head: &Placed,
+    label: &str,
+    color: &Color,
+) {
+    let (space, top) = match (layout.lines.first(), layout.lines.get(1), layout.degrees) {
+        (Some(bottom), Some(next), Some(top)) => (bottom.p0.y - next.p0.y, top),
+        _ => return,
+    };
+    let text = ctx
+        .text()
+        .new_text_layout(String::from(label))
+        .font(FontFamily::SERIF, space * DEGREE_SIZE)
+        .text_color(color.clone())
+        .build();
+    if let Ok(text) = text {
+        let x = head.rect.center().x - text.size().width / 2.0;
+        ctx.draw_text(&text, (x, top));
+    }
+}
+
+/// Notes a [`Staff`] reads from the app data, a single note or the keys
+/// of a chord.
+pub trait Played {
+    /// Notes to show, in any order.
+    fn notes(&self) -> Vec<note::Note>;
+}
+impl Played for Option<note::Note> {
+    fn notes(&self) -> Vec<note::Note> {
+        self.iter().cloned().collect()
+    }
+}
+impl Played for Arc<Vec<note::Note>> {
+    fn notes(&self) -> Vec<note::Note> {
+        self.to_vec()
+    }
+}
+
+/// Five line staff with a clef, a key signature and the note or chord
+/// a lens picks from the app data, drawn in the theme colours of
+/// [`settings::Colors`].
+pub struct Staff<T = Option<note::Note>, L = lens::Identity, N = Option<note::Note>> {
+    notes: L,
+    // Lowest first, without doubles, as in the layout.
+    played: Vec<note::Note>,
+    clef: ClefType,
+    alteration: note::NoteAlt,
+    key: note::Key,
+    scale: Option<note::Scale>,
+    reference: Option<note::Scale>,
+    degrees: Degrees,
+    layout: Option<StaffLayout>,
+    clef_svg: WidgetPod<T, Svg>,
+    key_svgs: Vec<WidgetPod<T, Svg>>,
+    // Heads and then accidentals of the played notes or the scale, with
+    // the glyph and colour each was drawn with.
+    note_svgs: Vec<WidgetPod<T, Svg>>,
+    note_tints: Vec<(Glyph, Color)>,
+    played_type: PhantomData<N>,
 }
-const MAX_LINES: i32 = 11;
 impl Staff {
+    /// Staff whose data is the note itself.
     pub fn new() -> Self {
+        Self::of(lens::Identity)
+    }
+}
+impl Staff<Arc<Vec<note::Note>>, lens::Identity, Arc<Vec<note::Note>>> {
+    /// Staff whose data are the notes of a chord.
+    pub fn chord() -> Self {
+        Self::of(lens::Identity)
+    }
+}
+impl<T: Data, N: Played, L: Lens<T, N>> Staff<T, L, N> {
+    /// Staff showing the notes the lens points to, treble clef and C
+    /// major. The glyphs are drawn once the widget is added, in the
+    /// colours of the theme.
+    pub fn of(notes: L) -> Self {
         Staff {
-            midi_note: None,
-            max_lines: MAX_LINES,
-            clef_svg: WidgetPod::new(Self::make_clef_svg(ClefType::Treble)),
-            note_svg: WidgetPod::new(Self::make_note_svg()),
-            sharp_svg: WidgetPod::new(Self::make_alt_svg(note::NoteAlt::Sharp)),
-            flat_svg: WidgetPod::new(Self::make_alt_svg(note::NoteAlt::Flat)),
+            notes,
+            played: Vec::new(),
+            clef: ClefType::Treble,
+            alteration: note::NoteAlt::Flat,
+            key: note::Key::new(0),
+            scale: None,
+            reference: None,
+            degrees: Degrees::Off,
+            layout: None,
+            clef_svg: Self::pod(Glyph::TrebleClef, &Color::BLACK),
+            key_svgs: Vec::new(),
+            note_svgs: Vec::new(),
+            note_tints: Vec::new(),
+            played_type: PhantomData,
         }
     }
-    pub fn from_note(note: note::Note) -> Self {
-        Staff {
-            midi_note: Some(note),
-            max_lines: MAX_LINES,
-            clef_svg: WidgetPod::new(Self::make_clef_svg(ClefType::Treble)),
-            note_svg: WidgetPod::new(Self::make_note_svg()),
-            sharp_svg: WidgetPod::new(Self::make_alt_svg(note::NoteAlt::Sharp)),
-            flat_svg: WidgetPod::new(Self::make_alt_svg(note::NoteAlt::Flat)),
-        }
-    }
-    fn make_clef_svg(cleftype: ClefType) -> Svg {
-        Svg::new(cleftype.svgdata()).fill_mode(FillStrat::Fill)
-    }
-    fn make_note_svg() -> Svg {
-        Svg::new(Self::make_svg(
-            include_str!("../assets/note.svg").parse::<SvgData>(),
-        ))
-    }
-    fn make_alt_svg(alttype: note::NoteAlt) -> Svg {
-        Svg::new(alttype.svgdata())
-    }
-    fn make_svg(file: Result<SvgData, Box<dyn std::error::Error + 'static>>) -> SvgData {
-        match file {
-            Ok(svg) => svg,
-            Err(err) => {
-                error!("{}", err);
-                error!("Using an empty SVG instead.");
-                SvgData::default()
+    /// Auto switches between treble and bass with the note.
+    pub fn with_clef(mut self, clef: ClefType) -> Self {
+        self.clef = clef;
+        self
+    }
+    /// Spelling of black keys outside the key signature.
+    pub fn with_alteration(mut self, alteration: note::NoteAlt) -> Self {
+        self.alteration = alteration;
+        self
+    }
+    /// Key signature, also sets the spelling to the one of the key.
+    pub fn with_key(mut self, key: note::Key) -> Self {
+        self.alteration = key.alteration();
+        self.key = key;
+        self
+    }
+    /// Clef, key signature and spelling of the state, the note still
+    /// comes from the lens.
+    pub fn with_state(mut self, state: &StaffState) -> Self {
+        self.clef = state.clef;
+        self.key = state.key.clone();
+        self.alteration = state.alteration.clone();
+        self
+    }
+    /// What the staff shows now, the notes as last read from the lens.
+    pub fn state(&self) -> StaffState {
+        StaffState {
+            notes: self.played.clone(),
+            clef: self.clef,
+            key: self.key.clone(),
+            alteration: self.alteration.clone(),
+        }
+    }
+    /// Shows the scale instead of the note, with the heads of the degrees
+    /// the notes play highlighted. The scale starts near the bottom line
+    /// and is written with accidentals against the key signature.
+    pub fn with_scale(mut self, scale: note::Scale) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+    /// Draws notes outside the scale in the [`settings::OUTSIDE`]
+    /// colour. [`Reference::scale`] picks the scale for a key.
+    pub fn with_reference(mut self, scale: note::Scale) -> Self {
+        self.reference = Some(scale);
+        self
+    }
+    /// Label under the notes in the reference scale, or under the
+    /// degrees of a scale shown with [`Staff::with_scale`].
+    pub fn with_degrees(mut self, degrees: Degrees) -> Self {
+        self.degrees = degrees;
+        self
+    }
+    fn pod(glyph: Glyph, color: &Color) -> WidgetPod<T, Svg> {
+        WidgetPod::new(glyph_svg(glyph, color))
+    }
+    fn read(&self, data: &T) -> Vec<note::Note> {
+        let mut played = self.notes.with(data, |notes| notes.notes());
+        played.sort_by_key(|note| note.midi_nr());
+        played.dedup();
+        played
+    }
+    // Draws the clef and key signature in the theme colours, for the
+    // current clef and key, then the notes. Returns whether glyphs were
+    // added, they need WidgetAdded.
+    fn tint(&mut self, env: &Env) -> bool {
+        let staff = settings::Colors::from_env(env).staff;
+        let clef = match self.current_clef() {
+            ClefType::Bass => Glyph::BassClef,
+            _ => Glyph::TrebleClef,
+        };
+        let signature = match self.key.alteration() {
+            note::NoteAlt::Flat => Glyph::Flat,
+            _ => Glyph::Sharp,
+        };
+        *self.clef_svg.widget_mut() = glyph_svg(clef, &staff);
+        let count = self.key.altered_steps().len();
+        let added = count > self.key_svgs.len();
+        self.key_svgs.truncate(count);
+        for svg in self.key_svgs.iter_mut() {
+            *svg.widget_mut() = glyph_svg(signature, &staff);
+        }
+        while self.key_svgs.len() < count {
+            self.key_svgs.push(Self::pod(signature, &staff));
+        }
+        self.tint_notes(env) || added
+    }
+    // Draws the heads and accidentals in their colours. Glyphs already
+    // there keep their widget and only one whose glyph or colour changed
+    // is drawn again, so a highlight moving along a scale or a note
+    // leaving the reference touches only those. Returns whether glyphs
+    // were added.
+    fn tint_notes(&mut self, env: &Env) -> bool {
+        let (heads, accidentals) = match &self.scale {
+            Some(_) => StaffLayout::sequence_glyphs(&self.key, &self.scale_pitches()),
+            None => StaffLayout::chord_glyphs(&self.key, self.alteration.clone(), &self.played),
+        };
+        let colors = settings::Colors::from_env(env);
+        let (notehead, accidental) = self.colors(&colors);
+        let lit = self.highlighted();
+        let heads = heads
+            .into_iter()
+            .enumerate()
+            .map(|(index, glyph)| match lit.get(index) {
+                Some(true) => (glyph, colors.highlight.clone()),
+                _ => (glyph, notehead.clone()),
+            });
+        let accidentals = accidentals
+            .into_iter()
+            .map(|glyph| (glyph, accidental.clone()));
+        let tints: Vec<(Glyph, Color)> = heads.chain(accidentals).collect();
+        let added = tints.len() > self.note_svgs.len();
+        self.note_svgs.truncate(tints.len());
+        for (index, tint) in tints.iter().enumerate() {
+            if self.note_tints.get(index) == Some(tint) {
+                continue;
+            }
+            let (glyph, color) = tint;
+            match self.note_svgs.get_mut(index) {
+                Some(svg) => *svg.widget_mut() = glyph_svg(*glyph, color),
+                None => self.note_svgs.push(Self::pod(*glyph, color)),
             }
         }
+        self.note_tints = tints;
+        added
     }
-    fn line_w(staff_height: f64) -> f64 {
-        let mut w = staff_height / 200.0;
-        if w < 2.0 {
-            w = 2.0;
+    // Colours of the heads and accidentals.
+    fn colors(&self, colors: &settings::Colors) -> (Color, Color) {
+        if self.is_outside() {
+            (colors.outside.clone(), colors.outside.clone())
+        } else {
+            (colors.notehead.clone(), colors.accidental.clone())
         }
-        return w;
     }
-    fn line_h(&self, staff_height: f64) -> f64 {
-        staff_height / (self.max_lines - 1) as f64
+    fn current_clef(&self) -> ClefType {
+        match self.scale {
+            Some(_) => self.clef.resolve(None),
+            // Auto picks the clef for the middle note of a chord, as the
+            // layout does.
+            None => self.clef.resolve(self.played.get(self.played.len() / 2)),
+        }
     }
-    fn lines_rect(&self, staff_rect: Rect) -> Rect {
-        let vpad = self.line_h(staff_rect.height()) * ((self.max_lines - 5) / 2) as f64;
-        Rect::new(
-            staff_rect.x0,
-            staff_rect.y0 + vpad,
-            staff_rect.x1,
-            staff_rect.y1 - vpad,
-        )
+    // Degrees of the scale from the octave whose tonic is nearest the
+    // bottom line.
+    fn scale_pitches(&self) -> Vec<note::SpelledPitch> {
+        let scale = match &self.scale {
+            Some(scale) => scale,
+            None => return Vec::new(),
+        };
+        let bottom = self.current_clef().bottom_note().line(note::NoteAlt::White) + 0.5;
+        let octave = (-1..8)
+            .min_by(|a, b| {
+                let distance = |octave: &i32| (scale.tonic(*octave).line() - bottom).abs();
+                distance(a).partial_cmp(&distance(b)).unwrap()
+            })
+            .unwrap_or(4);
+        scale.pitches(octave)
     }
-    fn line_coords(
-        &self,
-        staff_rect: Rect,
-        line_nr: i32,
-        width: Option<f64>,
-        center: Option<f64>,
-    ) -> Rect {
-        let lines_rect = self.lines_rect(staff_rect);
-        let bottom = lines_rect.y1;
-        let m_width = width.unwrap_or(lines_rect.width());
-        let m_center = center.unwrap_or(lines_rect.center().x);
-        let pos_y = bottom - self.line_h(staff_rect.height()) * line_nr as f64;
-        Rect::new(
-            m_center - (m_width / 2.0),
-            pos_y,
-            m_center + (m_width / 2.0),
-            pos_y,
-        )
+    // Whether a played note is outside the reference scale, a chord is
+    // coloured as a whole.
+    fn is_outside(&self) -> bool {
+        match (&self.reference, &self.scale) {
+            (Some(reference), None) => self
+                .played
+                .iter()
+                .any(|note| reference.degree(note).is_none()),
+            _ => false,
+        }
+    }
+    // Degree labels of the heads in the layout.
+    fn labels(&self) -> Vec<Option<String>> {
+        match (&self.scale, &self.reference) {
+            (Some(scale), _) => self
+                .scale_pitches()
+                .iter()
+                .map(|pitch| self.degrees.label(scale, &pitch.note()?))
+                .collect(),
+            (None, Some(reference)) => self
+                .played
+                .iter()
+                .map(|note| self.degrees.label(reference, note))
+                .collect(),
+            (None, None) => Vec::new(),
+        }
     }
-    fn note_line(&self) -> f64 {
-        let note = self.midi_note.clone().unwrap();
-        note.line(self.note_alt())
+    // Whether each degree of the scale is one the notes play, the tonic
+    // on both ends.
+    fn highlighted(&self) -> Vec<bool> {
+        let degrees: Vec<usize> = match &self.scale {
+            Some(scale) => self
+                .played
+                .iter()
+                .filter_map(|note| scale.degree(note))
+                .collect(),
+            None => return Vec::new(),
+        };
+        let count = self.scale_pitches().len();
+        (0..count)
+            .map(|index| degrees.contains(&index) || (index == count - 1 && degrees.contains(&0)))
+            .collect()
     }
-    fn note_alt(&self) -> note::NoteAlt {
-        let note = self.midi_note.clone().unwrap();
-        note.alteration(note::NoteAlt::Flat)
+    fn layout_for(&self, size: Size, limits: &settings::OttavaLimits) -> StaffLayout {
+        let degrees = self.degrees != Degrees::Off;
+        if self.scale.is_some() {
+            return StaffLayout::sequence_with_glyphs(
+                Glyphs::current(),
+                size,
+                self.clef,
+                &self.key,
+                &self.scale_pitches(),
+                limits,
+                degrees,
+            );
+        }
+        StaffLayout::with_glyphs(
+            Glyphs::current(),
+            size,
+            self.clef,
+            &self.key,
+            self.alteration.clone(),
+            &self.played,
+            limits,
+            degrees,
+        )
     }
-    fn staff_line(&self) -> f64 {
-        note::Note::new(64).line(self.note_alt())
+    fn place(
+        svg: &mut WidgetPod<T, Svg>,
+        ctx: &mut LayoutCtx,
+        placed: &Placed,
+        data: &T,
+        env: &Env,
+    ) {
+        svg.layout(ctx, &BoxConstraints::tight(placed.rect.size()), data, env);
+        svg.set_origin(ctx, data, env, placed.rect.origin());
     }
 }
 
-impl Widget<Option<note::Note>> for Staff {
-    fn event(
-        &mut self,
-        ctx: &mut EventCtx,
-        event: &Event,
-        data: &mut Option<note::Note>,
-        env: &Env,
-    ) {
+impl<T: Data, N: Played, L: Lens<T, N>> Widget<T> for Staff<T, L, N> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
         self.clef_svg.event(ctx, event, data, env);
-        self.note_svg.event(ctx, event, data, env);
-        self.sharp_svg.event(ctx, event, data, env);
-        self.flat_svg.event(ctx, event, data, env);
+        for svg in self.key_svgs.iter_mut().chain(self.note_svgs.iter_mut()) {
+            svg.event(ctx, event, data, env);
+        }
     }
 
-    fn lifecycle(
-        &mut self,
-        ctx: &mut LifeCycleCtx,
-        event: &LifeCycle,
-        data: &Option<note::Note>,
-        env: &Env,
-    ) {
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            self.played = self.read(data);
+            self.tint(env);
+        }
         self.clef_svg.lifecycle(ctx, event, data, env);
-        self.note_svg.lifecycle(ctx, event, data, env);
-        self.sharp_svg.lifecycle(ctx, event, data, env);
-        self.flat_svg.lifecycle(ctx, event, data, env);
+        for svg in self.key_svgs.iter_mut().chain(self.note_svgs.iter_mut()) {
+            svg.lifecycle(ctx, event, data, env);
+        }
     }
 
-    fn update(
-        &mut self,
-        ctx: &mut UpdateCtx,
-        _old_data: &Option<note::Note>,
-        data: &Option<note::Note>,
-        env: &Env,
-    ) {
+    fn update(&mut self, ctx: &mut UpdateCtx, _old_data: &T, data: &T, env: &Env) {
+        let theme_changed = [
+            settings::STAFF,
+            settings::NOTEHEAD,
+            settings::ACCIDENTAL,
+            settings::HIGHLIGHT,
+            settings::OUTSIDE,
+        ]
+        .iter()
+        .any(|key| ctx.env_key_changed(key));
+        if ctx.env_key_changed(&settings::BACKGROUND) {
+            ctx.request_paint();
+        }
+        if ctx.env_key_changed(&settings::OTTAVA)
+            || ctx.env_key_changed(&settings::OTTAVA_ABOVE)
+            || ctx.env_key_changed(&settings::OTTAVA_BELOW)
+        {
+            ctx.request_layout();
+        }
+        let played = self.read(data);
+        if played != self.played || theme_changed {
+            let old_clef = self.current_clef();
+            self.played = played;
+            ctx.request_layout();
+            // The heads and accidentals change with the notes, the clef
+            // only when auto switches it.
+            let added = if self.current_clef() != old_clef || theme_changed {
+                self.tint(env)
+            } else {
+                self.tint_notes(env)
+            };
+            // New glyphs get their data with WidgetAdded.
+            if added {
+                ctx.children_changed();
+                return;
+            }
+        }
         self.clef_svg.update(ctx, data, env);
-        self.note_svg.update(ctx, data, env);
-        self.sharp_svg.update(ctx, data, env);
-        self.flat_svg.update(ctx, data, env);
+        for svg in self.key_svgs.iter_mut().chain(self.note_svgs.iter_mut()) {
+            svg.update(ctx, data, env);
+        }
     }
 
-    fn layout(
-        &mut self,
-        ctx: &mut LayoutCtx,
-        bc: &BoxConstraints,
-        data: &Option<note::Note>,
-        env: &Env,
-    ) -> Size {
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
         let size = bc.max();
-        let staff_origin = Point::new(0.0, 0.0);
-        let line_h = self.line_h(size.height);
-        let lines_rect = self.lines_rect(Rect::from_origin_size(staff_origin, size));
-        let clef_size = Size::new(size.width, line_h * 6.0);
-        let clef_bc = BoxConstraints::new(Size::new(0.0, clef_size.height), clef_size);
-        self.clef_svg.layout(ctx, &clef_bc, data, env);
-        self.clef_svg.set_origin(
-            ctx,
-            data,
-            env,
-            Point::new(lines_rect.x0, lines_rect.y0 - line_h),
-        );
-
-        if self.midi_note.is_some() {
-            let diff = self.note_line() - self.staff_line();
-            // let staff_line = ;
-            let note_size = Size::new(size.width, line_h);
-            let note_bc = BoxConstraints::new(Size::new(0.0, note_size.height), note_size);
-            let note_size = self.note_svg.layout(ctx, &note_bc, data, env);
-            let note_origin = Point::new(
-                lines_rect.center().x - note_size.width / 2.0,
-                lines_rect.y1 - (line_h * diff + line_h * 0.5),
-            );
-            self.note_svg.set_origin(ctx, data, env, note_origin);
-            let note_alt = self.note_alt();
-            if note_alt != note::NoteAlt::White {
-                let mut curr_svg = &mut self.sharp_svg;
-                let mut up_coeff = 4.0;
-                if note_alt == note::NoteAlt::Flat {
-                    curr_svg = &mut self.flat_svg;
-                    up_coeff = 1.5;
-                }
-                let alt_size = Size::new(size.width, line_h * 1.5);
-                let alt_bc = BoxConstraints::new(Size::new(0.0, alt_size.height), alt_size);
-                let alt_size = curr_svg.layout(ctx, &alt_bc, data, env);
-                let alt_origin = Point::new(
-                    note_origin.x - alt_size.width * 2.0,
-                    note_origin.y - line_h / up_coeff,
-                );
-                curr_svg.set_origin(ctx, data, env, alt_origin);
-            }
+        let layout = self.layout_for(size, &settings::OttavaLimits::from_env(env));
+        // A music font is painted directly, the SVG widgets only stand in
+        // without one.
+        if let Glyphs::Font(_) = Glyphs::current() {
+            self.layout = Some(layout);
+            return size;
         }
-
+        Self::place(&mut self.clef_svg, ctx, &layout.clef, data, env);
+        for (svg, placed) in self.key_svgs.iter_mut().zip(layout.signature.iter()) {
+            Self::place(svg, ctx, placed, data, env);
+        }
+        let placed = layout.notes.iter().chain(layout.accidentals.iter());
+        for (svg, placed) in self.note_svgs.iter_mut().zip(placed) {
+            Self::place(svg, ctx, placed, data, env);
+        }
+        self.layout = Some(layout);
         size
-        // bc.shrink(diff: impl Into<Size>)
     }
 
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &Option<note::Note>, env: &Env) {
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        let colors = settings::Colors::from_env(env);
         let bounds = ctx.size().to_rect();
-        ctx.fill(bounds, &Color::WHITE);
-        for i in 0..5 {
-            let line_rect = self.line_coords(bounds, i, None, None);
-            let line = Line::new(
-                Point::new(line_rect.x0, line_rect.y0),
-                Point::new(line_rect.x1, line_rect.y1),
-            );
-            ctx.stroke(line, &Color::BLACK, Self::line_w(bounds.height()));
+        ctx.fill(bounds, &colors.background);
+        let layout = match &self.layout {
+            Some(layout) => layout,
+            None => return,
+        };
+        let staff = colors.staff.clone();
+        for line in layout.lines.iter() {
+            ctx.stroke(*line, &staff, layout.line_width);
         }
-        self.clef_svg.paint(ctx, data, env);
-
-        if self.midi_note.is_some() {
-            self.note_svg.paint(ctx, data, env);
-            let bounds = ctx.size().to_rect();
-            let note_line = self.note_line();
-            let staff_line = self.staff_line();
-            let diff = (note_line - staff_line) as i32;
-            let diff_range: Option<std::ops::Range<i32>>;
-            if diff < 0 {
-                diff_range = Some(diff..0);
-            } else if diff > 4 {
-                diff_range = Some(5..(diff + 1));
-            } else {
-                diff_range = None;
+        for line in layout.ledgers.iter() {
+            ctx.stroke(*line, &staff, layout.ledger_width);
+        }
+        if let Some(bracket) = &layout.bracket {
+            for dash in bracket.dashes.iter() {
+                ctx.stroke(*dash, &staff, layout.line_width);
             }
-            if diff_range.is_some() {
-                for i in diff_range.unwrap() {
-                    let line_rect = self.line_coords(
-                        bounds,
-                        i,
-                        Some(bounds.width() / 7.0),
-                        Some(bounds.center().x),
-                    );
-                    let line = Line::new(
-                        Point::new(line_rect.x0, line_rect.y0),
-                        Point::new(line_rect.x1, line_rect.y1),
-                    );
-                    ctx.stroke(line, &Color::BLACK, Self::line_w(bounds.height()));
+            paint_label(ctx, bracket, &staff);
+        }
+        let glyphs = Glyphs::current();
+        if let Glyphs::Font(_) = glyphs {
+            let (notehead, accidental) = self.colors(&colors);
+            let highlight = colors.highlight.clone();
+            let lit = self.highlighted();
+            let heads =
+                layout
+                    .notes
+                    .iter()
+                    .enumerate()
+                    .map(|(index, placed)| match lit.get(index) {
+                        Some(true) => (placed, &highlight),
+                        _ => (placed, &notehead),
+                    });
+            let placed = std::iter::once(&layout.clef)
+                .chain(layout.signature.iter())
+                .map(|placed| (placed, &staff))
+                .chain(heads)
+                .chain(
+                    layout
+                        .accidentals
+                        .iter()
+                        .map(|placed| (placed, &accidental)),
+                );
+            for (placed, color) in placed {
+                if let Some(path) = glyphs.path(placed) {
+                    ctx.fill(path, color);
                 }
             }
-            match self.note_alt() {
-                note::NoteAlt::Sharp => {
-                    self.sharp_svg.paint(ctx, data, env);
-                }
-                note::NoteAlt::Flat => {
-                    self.flat_svg.paint(ctx, data, env);
-                }
-                note::NoteAlt::White => {}
+        } else {
+            self.clef_svg.paint(ctx, data, env);
+            for svg in self.key_svgs.iter_mut().chain(self.note_svgs.iter_mut()) {
+                svg.paint(ctx, data, env);
+            }
+        }
+        let layout = match &self.layout {
+            Some(layout) => layout,
+            None => return,
+        };
+        for (head, label) in layout.notes.iter().zip(self.labels()) {
+            if let Some(label) = label {
+                paint_degree(ctx, layout, head, &label, &staff);
             }
         }
     }
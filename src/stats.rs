@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use druid::{
+    BoxConstraints, Color, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx,
+    RenderContext, Size, UpdateCtx, Widget,
+};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use super::keyboard::{is_black, Keyboard};
+use super::naming::Naming;
+#[cfg(test)]
+use super::naming::{Octaves, System};
+use super::note::{Key, Note, NoteAlt};
+use super::staff::ClefType;
+use super::trainer::Answer;
+
+#[test]
+fn test_breakdowns() {
+    let mut stats = Stats::new();
+    let answer = |target: u8, played: u8, reaction: u64| Answer {
+        target: Note::new(target),
+        played: Note::new(played),
+        correct: target == played,
+        reaction,
+        retry: false,
+    };
+    stats.record(ClefType::Treble, &answer(66, 66, 1000), NoteAlt::Sharp);
+    stats.record(ClefType::Treble, &answer(66, 67, 3000), NoteAlt::Sharp);
+    stats.record(ClefType::Bass, &answer(48, 48, 500), NoteAlt::Flat);
+    stats.record(ClefType::Auto, &answer(70, 70, 500), NoteAlt::Flat);
+
+    let by_note = stats.by_note();
+    assert_eq!(by_note[&66].answers, 2);
+    assert_eq!(by_note[&66].accuracy(), 0.5);
+    assert_eq!(by_note[&66].mean_reaction(), 2000);
+    let by_clef = stats.by_clef();
+    assert_eq!(by_clef["Treble"].answers, 3);
+    assert_eq!(by_clef["Bass"].correct, 1);
+    let by_accidental = stats.by_accidental();
+    assert_eq!(by_accidental["sharp"].answers, 2);
+    assert_eq!(by_accidental["natural"].answers, 1);
+    assert_eq!(by_accidental["flat"].answers, 1);
+    let by_register = stats.by_register();
+    assert_eq!(by_register[&3].answers, 3);
+    assert_eq!(by_register[&2].answers, 1);
+}
+
+#[test]
+fn test_export() {
+    let mut stats = Stats::new();
+    stats.record(
+        ClefType::Treble,
+        &Answer {
+            target: Note::new(60),
+            played: Note::new(60),
+            correct: true,
+            reaction: 700,
+            retry: false,
+        },
+        NoteAlt::Sharp,
+    );
+    let csv = stats.to_csv(&Naming::default());
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("category,value,answers,correct,accuracy,mean_reaction_ms")
+    );
+    assert!(csv.contains("note,C3,1,1,1.000,700"));
+    assert!(csv.contains("clef,Treble,1,1,1.000,700"));
+    let json: serde_json::Value =
+        serde_json::from_str(&stats.to_json(&Naming::default()).unwrap()).unwrap();
+    assert_eq!(json["clef"]["Treble"]["correct"], 1);
+    assert_eq!(json["note"]["C3"]["mean_reaction_ms"], 700);
+    let german = Naming::new(System::German, Octaves::MiddleC4);
+    assert!(stats.to_csv(&german).contains("note,C4,1,1,1.000,700"));
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Record {
+    pub target: u8,
+    pub played: u8,
+    pub clef: String,
+    // Alteration of the spelled target: -1 flat, 0 natural, 1 sharp.
+    pub alter: i8,
+    pub correct: bool,
+    pub reaction: u64,
+}
+impl Record {
+    pub fn accidental(&self) -> &'static str {
+        match self.alter {
+            a if a > 0 => "sharp",
+            a if a < 0 => "flat",
+            _ => "natural",
+        }
+    }
+    pub fn register(&self) -> i32 {
+        self.target as i32 / 12 - 2
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct Tally {
+    pub answers: u32,
+    pub correct: u32,
+    #[serde(skip)]
+    pub total_reaction: u64,
+}
+impl Tally {
+    fn add(&mut self, record: &Record) {
+        self.answers += 1;
+        if record.correct {
+            self.correct += 1;
+        }
+        self.total_reaction += record.reaction;
+    }
+    pub fn accuracy(&self) -> f64 {
+        if self.answers == 0 {
+            return 0.0;
+        }
+        self.correct as f64 / self.answers as f64
+    }
+    pub fn mean_reaction(&self) -> u64 {
+        if self.answers == 0 {
+            return 0;
+        }
+        self.total_reaction / self.answers as u64
+    }
+}
+
+#[derive(Serialize)]
+struct TallySummary {
+    answers: u32,
+    correct: u32,
+    accuracy: f64,
+    mean_reaction_ms: u64,
+}
+
+// All answers of one profile.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Stats {
+    records: Vec<Record>,
+}
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            records: Vec::new(),
+        }
+    }
+    pub fn record(&mut self, clef: ClefType, answer: &Answer, alteration: NoteAlt) {
+        self.records.push(Record {
+            target: answer.target.midi_nr(),
+            played: answer.played.midi_nr(),
+            clef: format!("{:?}", clef.resolve(Some(&answer.target))),
+            alter: answer.target.spelled(alteration).alter(),
+            correct: answer.correct,
+            reaction: answer.reaction,
+        });
+    }
+    fn group<K: Ord, F: Fn(&Record) -> K>(&self, key: F) -> BTreeMap<K, Tally> {
+        let mut groups: BTreeMap<K, Tally> = BTreeMap::new();
+        for record in self.records.iter() {
+            groups.entry(key(record)).or_default().add(record);
+        }
+        groups
+    }
+    pub fn by_note(&self) -> BTreeMap<u8, Tally> {
+        self.group(|r| r.target)
+    }
+    pub fn by_clef(&self) -> BTreeMap<String, Tally> {
+        self.group(|r| r.clef.clone())
+    }
+    pub fn by_accidental(&self) -> BTreeMap<String, Tally> {
+        self.group(|r| String::from(r.accidental()))
+    }
+    pub fn by_register(&self) -> BTreeMap<i32, Tally> {
+        self.group(|r| r.register())
+    }
+    fn summary(&self, naming: &Naming) -> Vec<(&'static str, String, Tally)> {
+        let mut rows = Vec::new();
+        for (nr, tally) in self.by_note() {
+            let name = naming.name(&Note::new(nr), NoteAlt::Sharp, &Key::new(0));
+            rows.push(("note", name, tally));
+        }
+        for (clef, tally) in self.by_clef() {
+            rows.push(("clef", clef, tally));
+        }
+        for (accidental, tally) in self.by_accidental() {
+            rows.push(("accidental", accidental, tally));
+        }
+        for (register, tally) in self.by_register() {
+            rows.push(("register", register.to_string(), tally));
+        }
+        rows
+    }
+    pub fn to_csv(&self, naming: &Naming) -> String {
+        let mut csv = String::from("category,value,answers,correct,accuracy,mean_reaction_ms\n");
+        for (category, value, tally) in self.summary(naming) {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.3},{}\n",
+                category,
+                value,
+                tally.answers,
+                tally.correct,
+                tally.accuracy(),
+                tally.mean_reaction()
+            ));
+        }
+        csv
+    }
+    pub fn to_json(&self, naming: &Naming) -> serde_json::Result<String> {
+        let mut categories: BTreeMap<&str, BTreeMap<String, TallySummary>> = BTreeMap::new();
+        for (category, value, tally) in self.summary(naming) {
+            categories.entry(category).or_default().insert(
+                value,
+                TallySummary {
+                    answers: tally.answers,
+                    correct: tally.correct,
+                    accuracy: tally.accuracy(),
+                    mean_reaction_ms: tally.mean_reaction(),
+                },
+            );
+        }
+        serde_json::to_string_pretty(&categories)
+    }
+    pub fn load(path: &Path) -> Self {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Self::new(),
+        };
+        match serde_json::from_str(&text) {
+            Ok(stats) => stats,
+            Err(err) => {
+                error!("{}: {}", path.display(), err);
+                error!("Starting with empty statistics.");
+                Self::new()
+            }
+        }
+    }
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string(self)?;
+        std::fs::write(path, text)
+    }
+}
+
+// Keyboard coloured by accuracy per key: red is wrong, green is right,
+// grey keys have no answers yet.
+pub struct HeatMap {
+    keyboard: Keyboard,
+}
+impl HeatMap {
+    pub fn new(low: u8, high: u8) -> Self {
+        HeatMap {
+            keyboard: Keyboard::new(low, high),
+        }
+    }
+    fn color(tally: Option<&Tally>, black: bool) -> Color {
+        match tally {
+            Some(tally) => {
+                let accuracy = tally.accuracy();
+                Color::rgb(1.0 - accuracy, 0.2 + accuracy * 0.6, 0.2)
+            }
+            None if black => Color::grey(0.3),
+            None => Color::grey(0.9),
+        }
+    }
+}
+
+impl Widget<std::sync::Arc<Stats>> for HeatMap {
+    fn event(
+        &mut self,
+        _ctx: &mut EventCtx,
+        _event: &Event,
+        _data: &mut std::sync::Arc<Stats>,
+        _env: &Env,
+    ) {
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &std::sync::Arc<Stats>,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &std::sync::Arc<Stats>,
+        _data: &std::sync::Arc<Stats>,
+        _env: &Env,
+    ) {
+        ctx.request_paint();
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &std::sync::Arc<Stats>,
+        _env: &Env,
+    ) -> Size {
+        let width = bc.max().width;
+        bc.constrain(Size::new(width, width / 8.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &std::sync::Arc<Stats>, _env: &Env) {
+        let by_note = data.by_note();
+        for (nr, rect) in self.keyboard.key_rects(ctx.size()) {
+            let color = Self::color(by_note.get(&nr), is_black(nr));
+            ctx.fill(rect, &color);
+            ctx.stroke(rect, &Color::BLACK, 1.0);
+        }
+    }
+}
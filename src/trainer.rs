@@ -0,0 +1,190 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use druid::{Data, Lens, Selector};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::note::{Key, Note};
+use super::srs::Scheduler;
+use super::staff::ClefType;
+
+pub const NEXT: Selector = Selector::new("rusted_big_note.trainer-next");
+
+#[test]
+fn test_pool() {
+    let pool = Pool::new(ClefType::Treble, 60, 72, false, Key::new(0));
+    assert_eq!(pool.candidates(), vec![60, 62, 64, 65, 67, 69, 71, 72]);
+    let pool = Pool::new(ClefType::Treble, 60, 64, true, Key::new(0));
+    assert_eq!(pool.candidates(), vec![60, 61, 62, 63, 64]);
+    let pool = Pool::new(ClefType::Bass, 60, 64, false, Key::new(-3));
+    assert_eq!(pool.candidates(), vec![60, 62, 63]);
+    let pool = Pool::new(ClefType::Bass, 64, 60, false, Key::new(0));
+    assert!(pool.candidates().is_empty());
+}
+
+#[test]
+fn test_trainer_is_reproducible() {
+    let pool = Pool::new(ClefType::Treble, 48, 84, true, Key::new(0));
+    let mut first = Trainer::new(42);
+    let mut second = Trainer::new(42);
+    for _ in 0..20 {
+        let target = first.next(&pool).unwrap();
+        assert_eq!(Some(target.clone()), second.next(&pool));
+        assert!(pool.candidates().contains(&target.midi_nr()));
+    }
+    assert_eq!(
+        Trainer::new(1).next(&Pool::new(ClefType::Treble, 64, 60, false, Key::new(0))),
+        None
+    );
+}
+
+#[test]
+fn test_answer() {
+    let pool = Pool::new(ClefType::Treble, 60, 60, false, Key::new(0));
+    let mut trainer = Trainer::new(7);
+    assert_eq!(trainer.answer(60), None);
+    let target = trainer.next(&pool).unwrap();
+    let answer = trainer.answer(61).unwrap();
+    assert_eq!(answer.target, target);
+    assert!(!answer.correct);
+    assert!(!answer.retry);
+    // The target stays until it is played correctly.
+    let answer = trainer.answer(60).unwrap();
+    assert!(answer.correct);
+    assert!(answer.retry);
+    assert_eq!(trainer.answer(60), None);
+    trainer.next(&pool);
+    let answer = trainer.answer(60).unwrap();
+    assert!(answer.correct);
+    assert!(!answer.retry);
+}
+
+#[test]
+fn test_next_scheduled() {
+    let pool = Pool::new(ClefType::Treble, 60, 62, false, Key::new(0));
+    let mut trainer = Trainer::new(5);
+    let mut scheduler = Scheduler::new();
+    scheduler.record(pool.clef, &Note::new(60), pool.key.alteration(), false, 100);
+    scheduler.record(pool.clef, &Note::new(62), pool.key.alteration(), true, 100);
+    // The missed note is due again, the known one is not.
+    assert_eq!(
+        trainer.next_scheduled(&pool, &scheduler),
+        Some(Note::new(60))
+    );
+    assert_eq!(trainer.answer(60).map(|a| a.correct), Some(true));
+}
+
+// Set of notes the trainer picks targets from.
+#[derive(Clone, Data, Lens, Debug)]
+pub struct Pool {
+    pub clef: ClefType,
+    pub low: f64,
+    pub high: f64,
+    pub accidentals: bool,
+    pub key: Key,
+}
+impl Pool {
+    pub fn new(clef: ClefType, low: u8, high: u8, accidentals: bool, key: Key) -> Self {
+        Pool {
+            clef,
+            low: low as f64,
+            high: high as f64,
+            accidentals,
+            key,
+        }
+    }
+    pub fn candidates(&self) -> Vec<u8> {
+        (self.low as u8..=self.high as u8)
+            .filter(|nr| self.accidentals || self.key.contains(&Note::new(*nr)))
+            .collect()
+    }
+}
+
+#[derive(Clone, Data, Debug, PartialEq)]
+pub struct Answer {
+    pub target: Note,
+    pub played: Note,
+    pub correct: bool,
+    // Milliseconds from showing the target to the answer.
+    pub reaction: u64,
+    // The target was missed before, so the answer does not count.
+    pub retry: bool,
+}
+
+#[derive(Clone, Data, Lens)]
+pub struct TrainerState {
+    pub pool: Pool,
+    pub target: Option<Note>,
+    pub last: Option<Answer>,
+    pub answers: Arc<Vec<Answer>>,
+    // Pick targets with the spaced repetition scheduler of the profile.
+    pub spaced: bool,
+}
+impl TrainerState {
+    pub fn new() -> Self {
+        TrainerState {
+            pool: Pool::new(ClefType::Treble, 60, 79, false, Key::new(0)),
+            target: None,
+            last: None,
+            answers: Arc::new(Vec::new()),
+            spaced: false,
+        }
+    }
+    pub fn correct(&self) -> usize {
+        self.answers.iter().filter(|a| a.correct).count()
+    }
+}
+
+// Owns the random generator and the timing of the current target,
+// which are not part of the UI data.
+pub struct Trainer {
+    rng: StdRng,
+    target: Option<(Note, Instant)>,
+    missed: bool,
+}
+impl Trainer {
+    pub fn new(seed: u64) -> Self {
+        Trainer {
+            rng: StdRng::seed_from_u64(seed),
+            target: None,
+            missed: false,
+        }
+    }
+    pub fn next(&mut self, pool: &Pool) -> Option<Note> {
+        self.missed = false;
+        let candidates = pool.candidates();
+        if candidates.is_empty() {
+            self.target = None;
+            return None;
+        }
+        let nr = candidates[self.rng.gen_range(0..candidates.len())];
+        let note = Note::new(nr);
+        self.target = Some((note.clone(), Instant::now()));
+        Some(note)
+    }
+    pub fn next_scheduled(&mut self, pool: &Pool, scheduler: &Scheduler) -> Option<Note> {
+        self.missed = false;
+        let candidates: Vec<Note> = pool.candidates().into_iter().map(Note::new).collect();
+        let note = scheduler.pick(pool.clef, pool.key.alteration(), &candidates, &mut self.rng);
+        self.target = note.clone().map(|note| (note, Instant::now()));
+        note
+    }
+    // Checks a played note against the current target, which stays until
+    // it is played correctly. Only the first answer to each target counts.
+    pub fn answer(&mut self, played: u8) -> Option<Answer> {
+        let (target, shown) = self.target.as_ref()?;
+        let answer = Answer {
+            target: target.clone(),
+            played: Note::new(played),
+            correct: target.midi_nr() == played,
+            reaction: shown.elapsed().as_millis() as u64,
+            retry: self.missed,
+        };
+        if answer.correct {
+            self.target = None;
+        } else {
+            self.missed = true;
+        }
+        Some(answer)
+    }
+}
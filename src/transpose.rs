@@ -0,0 +1,177 @@
+use druid::{Data, Lens};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use super::note::ScaleType;
+use super::note::{Key, Note, Scale};
+
+#[test]
+fn test_instruments() {
+    let c4 = Note::new(60);
+    assert_eq!(Instrument::ClarinetBb.written(&c4), Some(Note::new(62)));
+    assert_eq!(Instrument::HornF.written(&c4), Some(Note::new(67)));
+    assert_eq!(Instrument::AltoSaxEb.written(&c4), Some(Note::new(69)));
+    assert_eq!(Instrument::ClarinetA.written(&c4), Some(Note::new(63)));
+    assert_eq!(Instrument::Piccolo.written(&c4), Some(Note::new(48)));
+    assert_eq!(
+        Instrument::Guitar.concert(&Note::new(64)),
+        Some(Note::new(52))
+    );
+    assert_eq!(Instrument::Piccolo.written(&Note::new(5)), None);
+    // Concert Eb major: Bb trumpet reads F, horn Bb, alto sax C, A clarinet Gb.
+    let es = Key::new(-3);
+    assert_eq!(Instrument::TrumpetBb.written_key(&es), Key::new(-1));
+    assert_eq!(Instrument::HornF.written_key(&es), Key::new(-2));
+    assert_eq!(Instrument::AltoSaxEb.written_key(&es), Key::new(0));
+    assert_eq!(Instrument::ClarinetA.written_key(&es), Key::new(-6));
+    for instrument in Instrument::ALL.iter() {
+        assert_eq!(instrument.concert_key(&instrument.written_key(&es)), es);
+    }
+}
+
+#[test]
+fn test_transposition_state() {
+    let mut state = TranspositionState::new();
+    state.instrument = Instrument::ClarinetBb;
+    state.key = Key::new(-2);
+    assert_eq!(state.shown(&Note::new(70)), Some(Note::new(72)));
+    assert_eq!(state.shown_key(), Key::new(0));
+    state.from_written = true;
+    state.key = Key::new(0);
+    assert_eq!(state.shown(&Note::new(72)), Some(Note::new(70)));
+    assert_eq!(state.shown_key(), Key::new(-2));
+    let dorian = Scale::new(2, ScaleType::Dorian);
+    assert_eq!(state.shown_scale(&dorian), Scale::new(0, ScaleType::Dorian));
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Instrument {
+    Concert,
+    ClarinetBb,
+    TrumpetBb,
+    ClarinetA,
+    HornF,
+    AltoSaxEb,
+    TenorSaxBb,
+    BaritoneSaxEb,
+    Piccolo,
+    Guitar,
+    DoubleBass,
+}
+impl Instrument {
+    pub const ALL: [Instrument; 11] = [
+        Instrument::Concert,
+        Instrument::ClarinetBb,
+        Instrument::TrumpetBb,
+        Instrument::ClarinetA,
+        Instrument::HornF,
+        Instrument::AltoSaxEb,
+        Instrument::TenorSaxBb,
+        Instrument::BaritoneSaxEb,
+        Instrument::Piccolo,
+        Instrument::Guitar,
+        Instrument::DoubleBass,
+    ];
+    // Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Instrument::Concert => "instrument-concert",
+            Instrument::ClarinetBb => "instrument-clarinet-bb",
+            Instrument::TrumpetBb => "instrument-trumpet-bb",
+            Instrument::ClarinetA => "instrument-clarinet-a",
+            Instrument::HornF => "instrument-horn-f",
+            Instrument::AltoSaxEb => "instrument-alto-sax-eb",
+            Instrument::TenorSaxBb => "instrument-tenor-sax-bb",
+            Instrument::BaritoneSaxEb => "instrument-baritone-sax-eb",
+            Instrument::Piccolo => "instrument-piccolo",
+            Instrument::Guitar => "instrument-guitar",
+            Instrument::DoubleBass => "instrument-double-bass",
+        }
+    }
+    // Written pitch minus sounding pitch in semitones, and the matching
+    // shift of the key signature in fifths.
+    fn interval(&self) -> (i32, i8) {
+        match self {
+            Instrument::Concert => (0, 0),
+            Instrument::ClarinetBb | Instrument::TrumpetBb => (2, 2),
+            Instrument::ClarinetA => (3, -3),
+            Instrument::HornF => (7, 1),
+            Instrument::AltoSaxEb => (9, 3),
+            Instrument::TenorSaxBb => (14, 2),
+            Instrument::BaritoneSaxEb => (21, 3),
+            Instrument::Piccolo => (-12, 0),
+            Instrument::Guitar | Instrument::DoubleBass => (12, 0),
+        }
+    }
+    fn shift(note: &Note, semitones: i32) -> Option<Note> {
+        let midi_nr = note.midi_nr() as i32 + semitones;
+        if (0..=127).contains(&midi_nr) {
+            Some(Note::new(midi_nr as u8))
+        } else {
+            None
+        }
+    }
+    pub fn written(&self, concert: &Note) -> Option<Note> {
+        Self::shift(concert, self.interval().0)
+    }
+    pub fn concert(&self, written: &Note) -> Option<Note> {
+        Self::shift(written, -self.interval().0)
+    }
+    pub fn written_key(&self, concert: &Key) -> Key {
+        concert.transpose(self.interval().1)
+    }
+    pub fn concert_key(&self, written: &Key) -> Key {
+        written.transpose(-self.interval().1)
+    }
+}
+
+#[derive(Clone, Data, Lens)]
+pub struct TranspositionState {
+    pub instrument: Instrument,
+    // Input comes from a transposed part and is shown at concert pitch,
+    // otherwise concert pitch input is shown as written for the player.
+    pub from_written: bool,
+    // Key of the input.
+    pub key: Key,
+    // Show the input on a second, smaller staff.
+    pub second_staff: bool,
+}
+impl TranspositionState {
+    pub fn new() -> Self {
+        TranspositionState {
+            instrument: Instrument::Concert,
+            from_written: false,
+            key: Key::new(0),
+            second_staff: false,
+        }
+    }
+    pub fn shown(&self, input: &Note) -> Option<Note> {
+        if self.from_written {
+            self.instrument.concert(input)
+        } else {
+            self.instrument.written(input)
+        }
+    }
+    pub fn shown_key(&self) -> Key {
+        if self.from_written {
+            self.instrument.concert_key(&self.key)
+        } else {
+            self.instrument.written_key(&self.key)
+        }
+    }
+    // The scale of the input as the shown notes have it.
+    pub fn shown_scale(&self, scale: &Scale) -> Scale {
+        let fifths = self.instrument.interval().1;
+        if self.from_written {
+            scale.transpose(-fifths)
+        } else {
+            scale.transpose(fifths)
+        }
+    }
+}
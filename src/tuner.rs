@@ -0,0 +1,316 @@
+use druid::{
+    kurbo::{Circle, Line},
+    piet::{FontFamily, FontWeight, Text, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Color, Data, Env, Event, EventCtx, LayoutCtx, Lens, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, UpdateCtx, Vec2, Widget,
+};
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use super::midi::MidiMessage;
+use super::note::{Note, NoteAlt};
+use super::settings;
+
+// Common concert pitches, from baroque to modern orchestras.
+pub const REFERENCES: [f64; 5] = [415.0, 430.0, 432.0, 440.0, 442.0];
+// The usual default pitch bend range of two semitones.
+const BEND_RANGE: f64 = 200.0;
+// Deviations within this many cents count as in tune.
+const IN_TUNE: f64 = 5.0;
+// Needle deflection at the end of the scale, in radians.
+const MAX_ANGLE: f64 = std::f64::consts::PI / 3.0;
+
+#[test]
+fn test_temperament_offsets() {
+    assert_eq!(Temperament::Equal.offsets(0), [0.0; 12]);
+    for temperament in Temperament::ALL.iter() {
+        for tonic in 0..12 {
+            assert!(temperament.offsets(tonic)[9].abs() < 1e-9);
+        }
+    }
+    // Vallotti on C: the fifth A-E is a sixth comma narrow, Bb-F is pure.
+    let vallotti = Temperament::Vallotti.offsets(0);
+    assert!((vallotti[4] - (-7.82 + 5.865)).abs() < 0.01);
+    assert!((vallotti[10] - 5.865).abs() < 0.01);
+    // Meantone on D has the pure thirds shifted by a tone.
+    let on_c = Temperament::Meantone.offsets(0);
+    let on_d = Temperament::Meantone.offsets(2);
+    assert!(((on_d[6] - on_d[2]) - (on_c[4] - on_c[0])).abs() < 1e-9);
+}
+
+#[test]
+fn test_measure() {
+    let mut tuner = TunerState::new();
+    let reading = tuner.measure(440.0).unwrap();
+    assert_eq!(reading.note, Note::new(69));
+    assert!(reading.cents.abs() < 1e-9);
+    tuner.a4 = 415.0;
+    let reading = tuner.measure(440.0).unwrap();
+    assert_eq!(reading.note, Note::new(70));
+    assert!((reading.cents - 1.27).abs() < 0.01);
+    // An equal tempered E is sharp in quarter comma meantone on C.
+    tuner.a4 = 440.0;
+    tuner.temperament = Temperament::Meantone;
+    let reading = tuner.measure(329.628).unwrap();
+    assert_eq!(reading.note, Note::new(64));
+    assert!((reading.cents - 3.42).abs() < 0.01);
+    assert_eq!(tuner.measure(0.0), None);
+}
+
+#[test]
+fn test_midi_bend() {
+    let mut tuner = TunerState::new();
+    tuner.handle(&MidiMessage::PitchBend(-4096));
+    assert_eq!(tuner.reading, None);
+    // A semitone down from A.
+    tuner.handle(&MidiMessage::NoteOn(69, 100));
+    let reading = tuner.reading.clone().unwrap();
+    assert_eq!(reading.note, Note::new(68));
+    assert!(reading.cents.abs() < 1e-6);
+    tuner.handle(&MidiMessage::PitchBend(410));
+    let reading = tuner.reading.clone().unwrap();
+    assert_eq!(reading.note, Note::new(69));
+    assert!((reading.cents - 10.0).abs() < 0.01);
+    tuner.handle(&MidiMessage::NoteOff(69));
+    assert_eq!(tuner.reading, None);
+}
+
+#[derive(Clone, Copy, Data, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Temperament {
+    Equal,
+    Werckmeister3,
+    Meantone,
+    Vallotti,
+}
+impl Temperament {
+    pub const ALL: [Temperament; 4] = [
+        Temperament::Equal,
+        Temperament::Werckmeister3,
+        Temperament::Meantone,
+        Temperament::Vallotti,
+    ];
+    // Message id of the display name, see i18n.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Temperament::Equal => "temperament-equal",
+            Temperament::Werckmeister3 => "temperament-werckmeister3",
+            Temperament::Meantone => "temperament-meantone",
+            Temperament::Vallotti => "temperament-vallotti",
+        }
+    }
+    // Deviation from equal temperament in cents for C, C#, ... B
+    // when tuned from C.
+    fn table(&self) -> [f64; 12] {
+        match self {
+            Temperament::Equal => [0.0; 12],
+            Temperament::Werckmeister3 => [
+                0.0, -9.775, -7.82, -5.865, -9.775, -1.955, -11.73, -3.91, -7.82, -11.73, -3.91,
+                -7.82,
+            ],
+            Temperament::Meantone => [
+                0.0, -23.95, -6.843, 10.265, -13.686, 3.422, -20.529, -3.422, -27.372, -10.265,
+                6.843, -17.108,
+            ],
+            Temperament::Vallotti => [
+                0.0, -5.865, -3.91, -1.955, -7.82, 1.955, -7.82, -1.955, -3.91, -5.865, 0.0, -9.775,
+            ],
+        }
+    }
+    // Offsets per pitch class for the given tonic, shifted so that
+    // A keeps the reference pitch.
+    pub fn offsets(&self, tonic: u8) -> [f64; 12] {
+        let table = self.table();
+        let mut offsets = [0.0; 12];
+        for (class, offset) in offsets.iter_mut().enumerate() {
+            *offset = table[(class + 12 - tonic as usize % 12) % 12];
+        }
+        let a = offsets[9];
+        for offset in offsets.iter_mut() {
+            *offset -= a;
+        }
+        offsets
+    }
+}
+
+#[derive(Clone, Data, PartialEq, Debug)]
+pub struct Reading {
+    pub note: Note,
+    // Deviation from the tempered target, not from equal temperament.
+    pub cents: f64,
+    pub frequency: f64,
+}
+
+#[derive(Clone, Data, Lens)]
+pub struct TunerState {
+    pub a4: f64,
+    pub temperament: Temperament,
+    // Pitch class the temperament is tuned from, 0 is C.
+    pub tonic: u8,
+    pub reading: Option<Reading>,
+    // Key held on the MIDI input and the current pitch bend in cents.
+    midi_key: Option<u8>,
+    bend: f64,
+}
+impl TunerState {
+    pub fn new() -> Self {
+        TunerState {
+            a4: 440.0,
+            temperament: Temperament::Equal,
+            tonic: 0,
+            reading: None,
+            midi_key: None,
+            bend: 0.0,
+        }
+    }
+    // Tonic spelled the way temperament tables usually name it.
+    pub fn tonic_note(&self) -> (Note, NoteAlt) {
+        let alteration = match self.tonic % 12 {
+            3 | 10 => NoteAlt::Flat,
+            _ => NoteAlt::Sharp,
+        };
+        (Note::new(60 + self.tonic % 12), alteration)
+    }
+    pub fn target_frequency(&self, midi_nr: u8) -> f64 {
+        let offset = self.temperament.offsets(self.tonic)[midi_nr as usize % 12];
+        self.a4 * 2f64.powf((midi_nr as f64 - 69.0 + offset / 100.0) / 12.0)
+    }
+    pub fn measure(&self, frequency: f64) -> Option<Reading> {
+        if frequency <= 0.0 {
+            return None;
+        }
+        let nearest = (69.0 + 12.0 * (frequency / self.a4).log2()).round();
+        if !(0.0..=127.0).contains(&nearest) {
+            return None;
+        }
+        let midi_nr = nearest as u8;
+        Some(Reading {
+            note: Note::new(midi_nr),
+            cents: 1200.0 * (frequency / self.target_frequency(midi_nr)).log2(),
+            frequency,
+        })
+    }
+    pub fn set_frequency(&mut self, frequency: Option<f64>) {
+        self.reading = frequency.and_then(|frequency| self.measure(frequency));
+    }
+    // A MIDI instrument sounds the equal tempered key moved by the bend.
+    pub fn handle(&mut self, message: &MidiMessage) {
+        match *message {
+            MidiMessage::NoteOn(key, _) => self.midi_key = Some(key),
+            MidiMessage::NoteOff(key) if self.midi_key == Some(key) => self.midi_key = None,
+            MidiMessage::PitchBend(value) => self.bend = value as f64 / 8192.0 * BEND_RANGE,
+            _ => return,
+        }
+        let frequency = self
+            .midi_key
+            .map(|key| self.a4 * 2f64.powf((key as f64 - 69.0) / 12.0 + self.bend / 1200.0));
+        self.set_frequency(frequency);
+    }
+}
+
+// Half-circle scale from -50 to +50 cents with a needle, large enough
+// to read from the back of a rehearsal room.
+pub struct Needle;
+impl Needle {
+    fn point(center: Point, radius: f64, cents: f64) -> Point {
+        let angle = cents.clamp(-50.0, 50.0) / 50.0 * MAX_ANGLE;
+        center + Vec2::new(radius * angle.sin(), -radius * angle.cos())
+    }
+}
+
+impl Widget<Option<Reading>> for Needle {
+    fn event(
+        &mut self,
+        _ctx: &mut EventCtx,
+        _event: &Event,
+        _data: &mut Option<Reading>,
+        _env: &Env,
+    ) {
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &Option<Reading>,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &Option<Reading>,
+        data: &Option<Reading>,
+        _env: &Env,
+    ) {
+        if !old_data.same(data)
+            || ctx.env_key_changed(&settings::BACKGROUND)
+            || ctx.env_key_changed(&settings::STAFF)
+        {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &Option<Reading>,
+        _env: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Option<Reading>, env: &Env) {
+        let size = ctx.size();
+        let ink = env.get(settings::STAFF);
+        ctx.fill(size.to_rect(), &env.get(settings::BACKGROUND));
+        let radius = (size.width / 2.0).min(size.height * 0.8) * 0.9;
+        let center = Point::new(size.width / 2.0, size.height * 0.85);
+        for cents in (-50..=50).step_by(10) {
+            let cents = cents as f64;
+            let inner = if cents == 0.0 { 0.8 } else { 0.88 };
+            ctx.stroke(
+                Line::new(
+                    Self::point(center, radius * inner, cents),
+                    Self::point(center, radius, cents),
+                ),
+                &ink,
+                radius / 60.0,
+            );
+        }
+        ctx.fill(Circle::new(center, radius / 20.0), &ink);
+        let reading = match data {
+            Some(reading) => reading,
+            None => return,
+        };
+        let color = if reading.cents.abs() <= IN_TUNE {
+            Color::rgb8(0x2e, 0xa0, 0x43)
+        } else {
+            Color::rgb8(0xd0, 0x30, 0x30)
+        };
+        ctx.stroke(
+            Line::new(center, Self::point(center, radius * 0.95, reading.cents)),
+            &color,
+            radius / 40.0,
+        );
+        let layout = ctx
+            .text()
+            .new_text_layout(format!("{:+.0}", reading.cents))
+            .font(FontFamily::SERIF, radius / 4.0)
+            .default_attribute(FontWeight::BOLD)
+            .text_color(color)
+            .build()
+            .unwrap();
+        let text_size = layout.size();
+        ctx.draw_text(
+            &layout,
+            center - Vec2::new(text_size.width / 2.0, radius * 0.5 + text_size.height / 2.0),
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use druid::Size;
+use rand::{rngs::StdRng, seq::index, SeedableRng};
+
+use super::i18n::{self, Language};
+use super::layout::StaffLayout;
+use super::naming::Naming;
+use super::note::{Key, Note, NoteAlt};
+use super::render::{self, Format};
+use super::settings::{Colors, OttavaLimits};
+use super::staff::ClefType;
+use super::trainer::Pool;
+
+// A4 in points, the unit of PDF pages.
+pub const PAGE: Size = Size::new(595.0, 842.0);
+const MARGIN: f64 = 48.0;
+// Title above the grid, page number below it.
+const HEADER: f64 = 40.0;
+const FOOTER: f64 = 24.0;
+// Room for the answer blank under each staff, and between staves.
+const BLANK: f64 = 36.0;
+const GAP: f64 = 12.0;
+const FONT: &str = "DejaVu Sans, Liberation Sans, Noto Sans, Arial, sans-serif";
+
+#[cfg(test)]
+fn sheet(count: usize, chord: usize, seed: u64) -> Worksheet {
+    let pool = Pool::new(ClefType::Treble, 60, 79, false, Key::new(0));
+    Worksheet {
+        clef: ClefType::Treble,
+        key: Key::new(0),
+        alteration: NoteAlt::Sharp,
+        naming: Naming::default(),
+        language: Language::English,
+        columns: 4,
+        ottava: OttavaLimits::default(),
+        seed,
+        items: items(&pool, count, chord, seed).unwrap(),
+    }
+}
+
+#[test]
+fn test_items() {
+    let pool = Pool::new(ClefType::Treble, 60, 72, false, Key::new(0));
+    let first = items(&pool, 30, 3, 9).unwrap();
+    assert_eq!(first, items(&pool, 30, 3, 9).unwrap());
+    assert_ne!(first, items(&pool, 30, 3, 10).unwrap());
+    for chord in first.iter() {
+        assert_eq!(chord.len(), 3);
+        assert!(chord
+            .windows(2)
+            .all(|pair| pair[0].midi_nr() < pair[1].midi_nr()));
+        assert!(chord
+            .iter()
+            .all(|note| pool.candidates().contains(&note.midi_nr())));
+    }
+    assert!(items(&pool, 5, 9, 1).is_err());
+}
+
+#[test]
+fn test_pages() {
+    let sheet = sheet(45, 1, 3);
+    let pages = sheet.pages(false);
+    let answers = sheet.pages(true);
+    assert_eq!(pages.len(), 3);
+    assert_eq!(answers.len(), pages.len());
+    assert!(pages[0].contains("Name the notes"));
+    assert!(pages[2].contains("Page 3 of 3, seed 3"));
+    assert!(answers[0].contains("Answer key"));
+    let first = sheet
+        .naming
+        .name(&sheet.items[0][0], NoteAlt::Sharp, &sheet.key);
+    assert!(answers[0].contains(&format!(">{}</text>", first)));
+    assert!(!pages[0].contains(&format!(">{}</text>", first)));
+    // Every page is a valid document.
+    for page in pages.iter().chain(answers.iter()) {
+        assert!(render::tree(page, &usvg::fontdb::Database::new()).is_ok());
+    }
+}
+
+#[test]
+fn test_page_paths() {
+    let path = Path::new("out/sheet.svg");
+    assert_eq!(page_path(path, "", 2), PathBuf::from("out/sheet-2.svg"));
+    assert_eq!(
+        page_path(path, "-answers", 1),
+        PathBuf::from("out/sheet-answers-1.svg")
+    );
+}
+
+// Picks `count` items of `chord` different notes each, the same ones
+// for the same seed.
+pub fn items(pool: &Pool, count: usize, chord: usize, seed: u64) -> Result<Vec<Vec<Note>>, String> {
+    let candidates = pool.candidates();
+    if chord == 0 || candidates.len() < chord {
+        return Err(format!(
+            "{} notes in the range, {} needed for each item",
+            candidates.len(),
+            chord
+        ));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    Ok((0..count)
+        .map(|_| {
+            let mut picked: Vec<u8> = index::sample(&mut rng, candidates.len(), chord)
+                .into_iter()
+                .map(|i| candidates[i])
+                .collect();
+            picked.sort_unstable();
+            picked.into_iter().map(Note::new).collect()
+        })
+        .collect())
+}
+
+pub struct Worksheet {
+    pub clef: ClefType,
+    pub key: Key,
+    pub alteration: NoteAlt,
+    // Names on the answer key.
+    pub naming: Naming,
+    pub language: Language,
+    pub columns: usize,
+    pub ottava: OttavaLimits,
+    // Printed on every page, so the sheet can be made again.
+    pub seed: u64,
+    pub items: Vec<Vec<Note>>,
+}
+impl Worksheet {
+    fn cell(&self) -> Size {
+        let width = (PAGE.width - MARGIN * 2.0) / self.columns.max(1) as f64;
+        Size::new(width, width * 0.8 + BLANK)
+    }
+    fn per_page(&self) -> usize {
+        let grid = PAGE.height - MARGIN * 2.0 - HEADER - FOOTER;
+        let rows = (grid / self.cell().height).floor().max(1.0) as usize;
+        rows * self.columns.max(1)
+    }
+    // One SVG document per page, the answer key has the names filled in.
+    pub fn pages(&self, answers: bool) -> Vec<String> {
+        let per_page = self.per_page();
+        let count = self.items.len().div_ceil(per_page).max(1);
+        let title = i18n::translate(
+            self.language,
+            if answers {
+                "worksheet-answers"
+            } else {
+                "worksheet-title"
+            },
+            None,
+        );
+        (0..count)
+            .map(|page| {
+                let first = page * per_page;
+                let last = (first + per_page).min(self.items.len());
+                let footer = self.footer(page + 1, count);
+                self.page(&title, &footer, first, &self.items[first..last], answers)
+            })
+            .collect()
+    }
+    fn footer(&self, page: usize, pages: usize) -> String {
+        i18n::translate_args(
+            self.language,
+            "worksheet-footer",
+            &[
+                ("page", page.to_string()),
+                ("pages", pages.to_string()),
+                ("seed", self.seed.to_string()),
+            ],
+        )
+    }
+    fn page(
+        &self,
+        title: &str,
+        footer: &str,
+        first: usize,
+        items: &[Vec<Note>],
+        answers: bool,
+    ) -> String {
+        let cell = self.cell();
+        let staff = Size::new(cell.width - GAP, cell.height - BLANK);
+        let columns = self.columns.max(1);
+        let mut out = render::document(PAGE);
+        out.push_str("<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n");
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"18\">{}</text>",
+            MARGIN,
+            MARGIN + 18.0,
+            FONT,
+            render::escape(title)
+        );
+        for (i, notes) in items.iter().enumerate() {
+            let x = MARGIN + GAP / 2.0 + (i % columns) as f64 * cell.width;
+            let y = MARGIN + HEADER + (i / columns) as f64 * cell.height;
+            let layout = StaffLayout::new(
+                staff,
+                self.clef,
+                &self.key,
+                self.alteration.clone(),
+                notes,
+                &self.ottava,
+            );
+            let _ = writeln!(out, "<g transform=\"translate({} {})\">", x, y);
+            // Printed sheets keep black on white whatever the theme.
+            out.push_str(&render::staff(&layout, &Colors::default()));
+            let _ = writeln!(
+                out,
+                "<text x=\"2\" y=\"10\" font-family=\"{}\" font-size=\"9\">{}.</text>",
+                FONT,
+                first + i + 1
+            );
+            let blank_y = staff.height + BLANK * 0.7;
+            let _ = writeln!(
+                out,
+                "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"#000000\" stroke-width=\"0.5\"/>",
+                staff.width * 0.15,
+                staff.width * 0.85,
+                y = blank_y
+            );
+            if answers {
+                let names: Vec<String> = notes
+                    .iter()
+                    .map(|note| self.naming.name(note, self.alteration.clone(), &self.key))
+                    .collect();
+                let _ = writeln!(
+                    out,
+                    "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"12\" text-anchor=\"middle\">{}</text>",
+                    staff.width / 2.0,
+                    blank_y - 4.0,
+                    FONT,
+                    render::escape(&names.join(" "))
+                );
+            }
+            out.push_str("</g>\n");
+        }
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"9\" text-anchor=\"middle\">{}</text>",
+            PAGE.width / 2.0,
+            PAGE.height - MARGIN,
+            FONT,
+            render::escape(footer)
+        );
+        out.push_str("</svg>\n");
+        out
+    }
+}
+
+// sheet.svg becomes sheet-1.svg, sheet-2.svg and so on.
+fn page_path(path: &Path, suffix: &str, page: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("worksheet");
+    path.with_file_name(format!("{}{}-{}.svg", stem, suffix, page))
+}
+
+// A PDF gets the worksheet pages followed by the answer key, SVG output
+// one file per page. Returns the written files.
+pub fn write(path: &Path, sheet: &Worksheet) -> Result<Vec<PathBuf>, String> {
+    let pages = sheet.pages(false);
+    let answers = sheet.pages(true);
+    match Format::from_path(path) {
+        Some(Format::Pdf) => {
+            let all: Vec<String> = pages.into_iter().chain(answers).collect();
+            let pdf = render::pdf(&all, PAGE)?;
+            std::fs::write(path, pdf).map_err(|err| format!("{}: {}", path.display(), err))?;
+            Ok(vec![path.to_path_buf()])
+        }
+        Some(Format::Svg) => {
+            let mut written = Vec::new();
+            for (suffix, pages) in [("", pages), ("-answers", answers)].iter() {
+                for (i, page) in pages.iter().enumerate() {
+                    let page_path = page_path(path, suffix, i + 1);
+                    std::fs::write(&page_path, page)
+                        .map_err(|err| format!("{}: {}", page_path.display(), err))?;
+                    written.push(page_path);
+                }
+            }
+            Ok(written)
+        }
+        _ => Err(format!("{}: expected a .pdf or .svg file", path.display())),
+    }
+}
@@ -0,0 +1,50 @@
+// Runs the app binary without a window, what goes wrong has to reach
+// stderr. The config directory is moved with XDG_CONFIG_HOME, which
+// only Linux follows.
+#![cfg(all(feature = "app", target_os = "linux"))]
+
+use std::process::Command;
+
+#[test]
+fn test_broken_settings_are_logged() {
+    let home = std::env::temp_dir().join(format!(
+        "rusted_big_note_test_settings_log_{}",
+        std::process::id()
+    ));
+    let dir = home.join("rusted_big_note");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("settings.toml"), "clef = \"alto\"\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rusted_big_note"))
+        .env("XDG_CONFIG_HOME", &home)
+        .env_remove("RUST_LOG")
+        .arg("render")
+        .arg("-o")
+        .arg(home.join("staff.svg"))
+        .arg("C3")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("invalid clef, using the default"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_missing_wav_is_reported() {
+    let path = std::env::temp_dir().join(format!(
+        "rusted_big_note_test_missing_{}.wav",
+        std::process::id()
+    ));
+    let output = Command::new(env!("CARGO_BIN_EXE_rusted_big_note"))
+        .arg("--detect-wav")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&*path.to_string_lossy()), "{}", stderr);
+}